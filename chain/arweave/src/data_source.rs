@@ -285,6 +285,14 @@ impl blockchain::DataSourceTemplate<Chain> for DataSourceTemplate {
         &self.name
     }
 
+    fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    fn network(&self) -> Option<&str> {
+        self.network.as_deref()
+    }
+
     fn api_version(&self) -> semver::Version {
         self.mapping.api_version.clone()
     }
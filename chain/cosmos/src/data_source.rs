@@ -342,6 +342,14 @@ impl blockchain::DataSourceTemplate<Chain> for DataSourceTemplate {
     fn runtime(&self) -> &[u8] {
         unimplemented!("{}", TEMPLATE_ERROR);
     }
+
+    fn kind(&self) -> &str {
+        unimplemented!("{}", TEMPLATE_ERROR);
+    }
+
+    fn network(&self) -> Option<&str> {
+        unimplemented!("{}", TEMPLATE_ERROR);
+    }
 }
 
 #[derive(Clone, Debug, Default, Hash, Eq, PartialEq, Deserialize)]
@@ -1,4 +1,4 @@
-use crate::subgraph::SubgraphInstance;
+use crate::subgraph::{EventSink, SubgraphInstance};
 use graph::{
     blockchain::Blockchain,
     components::store::DeploymentId,
@@ -14,4 +14,6 @@ pub struct IndexingContext<T: RuntimeHostBuilder<C>, C: Blockchain> {
     pub instance: SubgraphInstance<C, T>,
     pub instances: SharedInstanceKeepAliveMap,
     pub filter: C::TriggerFilter,
+    /// Where to record the block stream events that were handled, if anywhere
+    pub event_sink: Option<EventSink>,
 }
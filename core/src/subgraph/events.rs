@@ -0,0 +1,33 @@
+use graph::blockchain::BlockPtr;
+use std::sync::{Arc, Mutex};
+
+/// A block stream event that a subgraph runner handled
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProcessedEvent {
+    /// The block was processed
+    ProcessBlock(BlockPtr),
+    /// The subgraph was reverted to this block
+    Revert(BlockPtr),
+}
+
+/// Records the block stream events that subgraphs handle, in the order in
+/// which they were handled. This is only meant for tests that need to know
+/// exactly which blocks were processed and reverted; nothing sets it up in
+/// a running node.
+#[derive(Clone, Debug, Default)]
+pub struct EventSink(Arc<Mutex<Vec<ProcessedEvent>>>);
+
+impl EventSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&self, event: ProcessedEvent) {
+        self.0.lock().unwrap().push(event);
+    }
+
+    /// The events that were recorded so far
+    pub fn events(&self) -> Vec<ProcessedEvent> {
+        self.0.lock().unwrap().clone()
+    }
+}
@@ -1,6 +1,5 @@
 use futures01::sync::mpsc::Sender;
 use graph::components::subgraph::ProofOfIndexingVersion;
-use graph::data::subgraph::SPEC_VERSION_0_0_6;
 
 use std::collections::HashMap;
 use std::time::Instant;
@@ -48,11 +47,7 @@ where
         let network = manifest.network_name();
         let templates = Arc::new(manifest.templates);
 
-        let poi_version = if manifest.spec_version.ge(&SPEC_VERSION_0_0_6) {
-            ProofOfIndexingVersion::Fast
-        } else {
-            ProofOfIndexingVersion::Legacy
-        };
+        let poi_version = ProofOfIndexingVersion::for_spec_version(&manifest.spec_version);
 
         let mut this = SubgraphInstance {
             host_builder,
@@ -5,7 +5,7 @@ use crate::subgraph::metrics::{
     RunnerMetrics, SubgraphInstanceManagerMetrics, SubgraphInstanceMetrics,
 };
 use crate::subgraph::runner::SubgraphRunner;
-use crate::subgraph::SubgraphInstance;
+use crate::subgraph::{EventSink, SubgraphInstance};
 use graph::blockchain::block_stream::BlockStreamMetrics;
 use graph::blockchain::Blockchain;
 use graph::blockchain::NodeCapabilities;
@@ -23,6 +23,7 @@ pub struct SubgraphInstanceManager<S: SubgraphStore> {
     instances: SharedInstanceKeepAliveMap,
     link_resolver: Arc<dyn LinkResolver>,
     static_filters: bool,
+    event_sink: Option<EventSink>,
 }
 
 #[async_trait]
@@ -120,9 +121,17 @@ impl<S: SubgraphStore> SubgraphInstanceManager<S> {
             instances: SharedInstanceKeepAliveMap::default(),
             link_resolver,
             static_filters,
+            event_sink: None,
         }
     }
 
+    /// Records the block stream events that the subgraphs this manager starts handle in
+    /// `event_sink`, so that tests can check them.
+    pub fn with_event_sink(mut self, event_sink: EventSink) -> Self {
+        self.event_sink = Some(event_sink);
+        self
+    }
+
     async fn start_subgraph_inner<C: Blockchain>(
         self: Arc<Self>,
         logger: Logger,
@@ -270,6 +279,7 @@ impl<S: SubgraphStore> SubgraphInstanceManager<S> {
             instance,
             instances: self.instances.cheap_clone(),
             filter,
+            event_sink: self.event_sink.clone(),
         };
 
         let metrics = RunnerMetrics {
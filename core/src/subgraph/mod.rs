@@ -1,5 +1,6 @@
 mod context;
 mod error;
+mod events;
 mod inputs;
 mod instance;
 mod instance_manager;
@@ -11,6 +12,7 @@ mod runner;
 mod state;
 mod stream;
 
+pub use self::events::{EventSink, ProcessedEvent};
 pub use self::instance::SubgraphInstance;
 pub use self::instance_manager::SubgraphInstanceManager;
 pub use self::provider::SubgraphAssignmentProvider;
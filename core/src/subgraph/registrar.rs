@@ -6,6 +6,7 @@ use graph::blockchain::Blockchain;
 use graph::blockchain::BlockchainKind;
 use graph::blockchain::BlockchainMap;
 use graph::components::store::{DeploymentId, DeploymentLocator, SubscriptionManager};
+use graph::data::subgraph::features::detect_features;
 use graph::data::subgraph::schema::DeploymentCreate;
 use graph::prelude::{
     CreateSubgraphResult, SubgraphAssignmentProvider as SubgraphAssignmentProviderTrait,
@@ -167,13 +168,20 @@ where
                     match operation {
                         EntityChangeOperation::Set => {
                             store
-                                .assigned_node(&deployment)
+                                .assignment_status(&deployment)
                                 .map_err(|e| {
                                     anyhow!("Failed to get subgraph assignment entity: {}", e)
                                 })
                                 .map(|assigned| -> Box<dyn Stream<Item = _, Error = _> + Send> {
-                                    if let Some(assigned) = assigned {
-                                        if assigned == node_id {
+                                    if let Some((assigned, paused)) = assigned {
+                                        if assigned == node_id && paused {
+                                            // Paused deployments keep their assignment but must not run
+                                            debug!(logger, "Deployment is paused, broadcasting remove event"; "assigned_to" => assigned, "node_id" => &node_id);
+                                            Box::new(stream::once(Ok(AssignmentEvent::Remove {
+                                                deployment,
+                                                node_id: node_id.clone(),
+                                            })))
+                                        } else if assigned == node_id {
                                             // Start subgraph on this node
                                             debug!(logger, "Deployment assignee is this node, broadcasting add event"; "assigned_to" => assigned, "node_id" => &node_id);
                                             Box::new(stream::once(Ok(AssignmentEvent::Add {
@@ -276,7 +284,9 @@ where
             .logger_factory
             .subgraph_logger(&DeploymentLocator::new(DeploymentId(0), hash.clone()));
 
-        let raw: serde_yaml::Mapping = {
+        // Keep the manifest exactly as it was deployed, so that it can be stored alongside the
+        // deployment
+        let (raw, raw_yaml): (serde_yaml::Mapping, _) = {
             let file_bytes = self
                 .resolver
                 .cat(&logger, &hash.to_ipfs_link())
@@ -287,8 +297,12 @@ where
                     )
                 })?;
 
-            serde_yaml::from_slice(&file_bytes)
-                .map_err(|e| SubgraphRegistrarError::ResolveError(e.into()))?
+            let raw_yaml = String::from_utf8(file_bytes).map_err(|_| {
+                SubgraphRegistrarError::ResolveError(SubgraphManifestResolveError::NonUtf8)
+            })?;
+            let raw = serde_yaml::from_str(&raw_yaml)
+                .map_err(|e| SubgraphRegistrarError::ResolveError(e.into()))?;
+            (raw, raw_yaml)
         };
 
         let kind = BlockchainKind::from_manifest(&raw).map_err(|e| {
@@ -305,6 +319,7 @@ where
                     hash.cheap_clone(),
                     start_block,
                     raw,
+                    raw_yaml,
                     node_id,
                     debug_fork,
                     self.version_switching_mode,
@@ -322,6 +337,7 @@ where
                     hash.cheap_clone(),
                     start_block,
                     raw,
+                    raw_yaml,
                     node_id,
                     debug_fork,
                     self.version_switching_mode,
@@ -339,6 +355,7 @@ where
                     hash.cheap_clone(),
                     start_block,
                     raw,
+                    raw_yaml,
                     node_id,
                     debug_fork,
                     self.version_switching_mode,
@@ -356,6 +373,7 @@ where
                     hash.cheap_clone(),
                     start_block,
                     raw,
+                    raw_yaml,
                     node_id,
                     debug_fork,
                     self.version_switching_mode,
@@ -533,6 +551,7 @@ async fn create_subgraph_version<C: Blockchain, S: SubgraphStore>(
     deployment: DeploymentHash,
     start_block_ptr: Option<BlockPtr>,
     raw: serde_yaml::Mapping,
+    raw_yaml: String,
     node_id: NodeId,
     debug_fork: Option<DeploymentHash>,
     version_switching_mode: SubgraphVersionSwitchingMode,
@@ -594,11 +613,18 @@ async fn create_subgraph_version<C: Blockchain, S: SubgraphStore>(
         "block" => format!("{:?}", base_block.as_ref().map(|(_,ptr)| ptr.number))
     );
 
+    // Validation has already detected the features, but only reports the
+    // undeclared ones; detecting them again is cheap compared to resolving
+    // the manifest whenever someone asks for them
+    let detected_features = detect_features(&manifest).ok();
+
     // Apply the subgraph versioning and deployment operations,
     // creating a new subgraph deployment if one doesn't exist.
     let deployment = DeploymentCreate::new(&manifest, start_block)
         .graft(base_block)
-        .debug(debug_fork);
+        .debug(debug_fork)
+        .raw_yaml(Some(raw_yaml))
+        .detected_features(detected_features);
     deployment_store
         .create_subgraph_deployment(
             name,
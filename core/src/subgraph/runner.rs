@@ -4,7 +4,7 @@ use crate::subgraph::inputs::IndexingInputs;
 use crate::subgraph::metrics::RunnerMetrics;
 use crate::subgraph::state::IndexingState;
 use crate::subgraph::stream::new_block_stream;
-use crate::subgraph::SubgraphInstance;
+use crate::subgraph::{ProcessedEvent, SubgraphInstance};
 use atomic_refcell::AtomicRefCell;
 use graph::blockchain::block_stream::{BlockStreamEvent, BlockWithTriggers, FirehoseCursor};
 use graph::blockchain::{Block, Blockchain, DataSource, TriggerFilter as _};
@@ -527,11 +527,17 @@ where
     ) -> Result<Action, Error> {
         let action = match event {
             Some(Ok(BlockStreamEvent::ProcessBlock(block, cursor))) => {
-                self.handle_process_block(block, cursor, cancel_handle)
-                    .await?
+                let block_ptr = block.ptr();
+                let action = self
+                    .handle_process_block(block, cursor, cancel_handle)
+                    .await?;
+                self.record_event(ProcessedEvent::ProcessBlock(block_ptr));
+                action
             }
             Some(Ok(BlockStreamEvent::Revert(revert_to_ptr, cursor))) => {
-                self.handle_revert(revert_to_ptr, cursor).await?
+                let action = self.handle_revert(revert_to_ptr.clone(), cursor).await?;
+                self.record_event(ProcessedEvent::Revert(revert_to_ptr));
+                action
             }
             // Log and drop the errors from the block_stream
             // The block stream will continue attempting to produce blocks
@@ -543,6 +549,12 @@ where
 
         Ok(action)
     }
+
+    fn record_event(&self, event: ProcessedEvent) {
+        if let Some(event_sink) = &self.ctx.event_sink {
+            event_sink.record(event);
+        }
+    }
 }
 
 enum Action {
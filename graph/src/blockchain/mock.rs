@@ -149,6 +149,14 @@ impl<C: Blockchain> DataSourceTemplate<C> for MockDataSourceTemplate {
     fn name(&self) -> &str {
         todo!()
     }
+
+    fn kind(&self) -> &str {
+        todo!()
+    }
+
+    fn network(&self) -> Option<&str> {
+        todo!()
+    }
 }
 
 #[derive(Clone, Default, Deserialize)]
@@ -247,6 +247,8 @@ pub trait DataSourceTemplate<C: Blockchain>: Send + Sync + Debug {
     fn api_version(&self) -> semver::Version;
     fn runtime(&self) -> &[u8];
     fn name(&self) -> &str;
+    fn kind(&self) -> &str;
+    fn network(&self) -> Option<&str>;
 }
 
 #[async_trait]
@@ -392,6 +394,11 @@ impl BlockchainMap {
             .downcast()
             .map_err(|_| anyhow!("unable to downcast, wrong type for blockchain {}", C::KIND))
     }
+
+    /// The names of all networks in the map, regardless of their `BlockchainKind`
+    pub fn networks(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(|(_, network)| network.as_str())
+    }
 }
 
 pub struct TriggerWithHandler<C: Blockchain> {
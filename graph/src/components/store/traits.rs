@@ -4,7 +4,7 @@ use super::*;
 use crate::blockchain::block_stream::FirehoseCursor;
 use crate::components::server::index_node::VersionInfo;
 use crate::components::transaction_receipt;
-use crate::data::subgraph::status;
+use crate::data::subgraph::{status, SubgraphFeature};
 use crate::data::value::Word;
 use crate::data::{query::QueryTarget, subgraph::schema::*};
 
@@ -90,12 +90,46 @@ pub trait SubgraphStore: Send + Sync + 'static {
 
     fn assigned_node(&self, deployment: &DeploymentLocator) -> Result<Option<NodeId>, StoreError>;
 
+    /// The node that `deployment` is assigned to, and whether it is paused.
+    /// Returns `None` if the deployment is not assigned to any node
+    fn assignment_status(
+        &self,
+        deployment: &DeploymentLocator,
+    ) -> Result<Option<(NodeId, bool)>, StoreError>;
+
+    /// Stop indexing `deployment` without removing its assignment. If there
+    /// is no assignment for the given deployment, report an error.
+    fn pause_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError>;
+
+    /// Start indexing the paused `deployment` again on the node it is
+    /// assigned to. If there is no assignment for the given deployment,
+    /// report an error.
+    fn resume_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError>;
+
+    /// Remove everything that `deployment` did after `block_ptr_to` so that
+    /// indexing continues from there. The block must be before the current
+    /// block of the deployment, which should not be indexing while it is
+    /// rewound
+    fn rewind(
+        &self,
+        deployment: &DeploymentLocator,
+        block_ptr_to: BlockPtr,
+    ) -> Result<(), StoreError>;
+
+    /// The deployments that are assigned to `node` and are not paused
     fn assignments(&self, node: &NodeId) -> Result<Vec<DeploymentLocator>, StoreError>;
 
     /// Return `true` if a subgraph `name` exists, regardless of whether the
     /// subgraph has any deployments attached to it
     fn subgraph_exists(&self, name: &SubgraphName) -> Result<bool, StoreError>;
 
+    /// All versions of the subgraph `name`, from oldest to newest. Returns
+    /// an empty list if there is no subgraph with that name
+    fn subgraph_versions(
+        &self,
+        name: &SubgraphName,
+    ) -> Result<Vec<status::SubgraphVersion>, StoreError>;
+
     /// Returns a collection of all [`EntityModification`] items in relation to
     /// the given [`BlockNumber`]. No distinction is made between inserts and
     /// updates, which may be returned as either [`EntityModification::Insert`]
@@ -141,6 +175,27 @@ pub trait SubgraphStore: Send + Sync + 'static {
 
     /// Find the deployment locators for the subgraph with the given hash
     fn locators(&self, hash: &str) -> Result<Vec<DeploymentLocator>, StoreError>;
+
+    /// Find the locator of the active copy of the deployment with the given
+    /// hash, or `None` if there is no such deployment
+    fn active_locator(
+        &self,
+        hash: &DeploymentHash,
+    ) -> Result<Option<DeploymentLocator>, StoreError>;
+
+    /// Return the raw manifest that was used to create the deployment with
+    /// the given hash. Returns `None` if the deployment is not known to this
+    /// store, or if it was created before manifests were recorded
+    fn raw_manifest(&self, id: &DeploymentHash) -> Result<Option<String>, StoreError>;
+
+    /// Return the features that the deployment with the given hash uses, as
+    /// they were detected when it was created. Returns `None` if the
+    /// deployment is not known to this store, or if it was created before
+    /// features were recorded
+    fn detected_features(
+        &self,
+        id: &DeploymentHash,
+    ) -> Result<Option<BTreeSet<SubgraphFeature>>, StoreError>;
 }
 
 /// A view of the store for indexing. All indexing-related operations need
@@ -425,6 +480,55 @@ pub trait StatusStore: Send + Sync + 'static {
 
     fn status(&self, filter: status::Filter) -> Result<Vec<status::Info>, StoreError>;
 
+    /// Like `status`, but only the statuses that `page` picks, in its order. Ordering and paging
+    /// happen in the database, so that only the statuses that are returned get loaded.
+    fn status_page(
+        &self,
+        filter: status::Filter,
+        page: status::Page,
+    ) -> Result<Vec<status::Info>, StoreError>;
+
+    /// The number of current entities of each entity type in the deployment, sorted by entity
+    /// type. This has to count the rows of every table in the deployment and can be expensive.
+    fn entity_counts(&self, id: &DeploymentHash) -> Result<Vec<(String, u64)>, StoreError>;
+
+    /// The space that the tables and indexes of the deployment take up in the database. This
+    /// has to look at every table in the deployment and can be expensive.
+    fn deployment_size(&self, id: &DeploymentHash) -> Result<status::DeploymentSize, StoreError>;
+
+    /// The health of all deployments together. The numbers are computed in the database so
+    /// that this stays cheap no matter how many deployments there are.
+    fn node_health(&self) -> Result<status::NodeHealth, StoreError>;
+
+    /// The deployments that are assigned to a node but have not processed any block yet, e.g.
+    /// because they were assigned only moments ago. Their status reports them as being at no
+    /// block, just like deployments that have not been assigned at all.
+    fn pending_assignments(&self) -> Result<Vec<status::PendingAssignment>, StoreError>;
+
+    /// A page of the changes in the health of the deployment, sorted from first to last.
+    fn health_history(
+        &self,
+        id: &DeploymentHash,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<status::HealthTransition>, StoreError>;
+
+    /// The last change in the health of the deployment at or before `block`, or `None` if its
+    /// health had not changed by then.
+    fn health_at_block(
+        &self,
+        id: &DeploymentHash,
+        block: BlockNumber,
+    ) -> Result<Option<status::HealthTransition>, StoreError>;
+
+    /// A page of the non-fatal errors of the deployment, sorted from first to last.
+    fn non_fatal_errors(
+        &self,
+        id: &DeploymentHash,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<SubgraphError>, StoreError>;
+
     /// Support for the explorer-specific API
     fn version_info(&self, version_id: &str) -> Result<VersionInfo, StoreError>;
 
@@ -456,6 +560,16 @@ pub trait StatusStore: Send + Sync + 'static {
         block: BlockPtr,
     ) -> Result<Option<[u8; 32]>, StoreError>;
 
+    /// Like `get_proof_of_indexing`, but for several blocks at once, which
+    /// is cheaper than asking for each block separately. The proofs are in
+    /// the same order as `blocks`
+    async fn get_proofs_of_indexing(
+        &self,
+        subgraph_id: &DeploymentHash,
+        indexer: &Option<Address>,
+        blocks: Vec<BlockPtr>,
+    ) -> Result<Vec<Option<[u8; 32]>>, StoreError>;
+
     /// Like `get_proof_of_indexing` but returns a Proof of Indexing signed by
     /// address `0x00...0`, which allows it to be shared in public without
     /// revealing the indexers _real_ Proof of Indexing.
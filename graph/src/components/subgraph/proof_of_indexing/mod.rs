@@ -7,14 +7,36 @@ pub use online::{ProofOfIndexing, ProofOfIndexingFinisher};
 pub use reference::CausalityRegion;
 
 use atomic_refcell::AtomicRefCell;
+use semver::Version;
 use std::sync::Arc;
 
-#[derive(Copy, Clone, Debug)]
+use crate::data::subgraph::SPEC_VERSION_0_0_6;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ProofOfIndexingVersion {
     Fast,
     Legacy,
 }
 
+impl ProofOfIndexingVersion {
+    /// The version that deployments whose manifest has `spec_version` compute their proofs of
+    /// indexing with. Proofs of indexing of different versions can't be compared.
+    pub fn for_spec_version(spec_version: &Version) -> Self {
+        if spec_version.ge(&SPEC_VERSION_0_0_6) {
+            ProofOfIndexingVersion::Fast
+        } else {
+            ProofOfIndexingVersion::Legacy
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProofOfIndexingVersion::Fast => "fast",
+            ProofOfIndexingVersion::Legacy => "legacy",
+        }
+    }
+}
+
 /// This concoction of types is to allow MappingContext to be static, yet still
 /// have shared mutable data for derive_with_empty_block_state. The static
 /// requirement is so that host exports can be static for wasmtime.
@@ -7,6 +7,7 @@ use std::error::Error;
 use std::fmt;
 use std::string::FromUtf8Error;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::data::subgraph::*;
 use crate::prelude::q;
@@ -81,6 +82,9 @@ pub enum QueryExecutionError {
     InvalidSubgraphManifest,
     ResultTooBig(usize, usize),
     DeploymentNotFound(String),
+    QueryPermitTimeout(Duration),
+    BlockHashNotFound(i32, String),
+    AccessTokenRequired(String),
 }
 
 impl QueryExecutionError {
@@ -137,7 +141,10 @@ impl QueryExecutionError {
             | InvalidSubgraphManifest
             | ValidationError(_, _)
             | ResultTooBig(_, _)
-            | DeploymentNotFound(_) => false,
+            | DeploymentNotFound(_)
+            | QueryPermitTimeout(_)
+            | BlockHashNotFound(_, _)
+            | AccessTokenRequired(_) => false,
         }
     }
 }
@@ -281,7 +288,10 @@ impl fmt::Display for QueryExecutionError {
             SubgraphManifestResolveError(e) => write!(f, "failed to resolve subgraph manifest: {}", e),
             InvalidSubgraphManifest => write!(f, "invalid subgraph manifest file"),
             ResultTooBig(actual, limit) => write!(f, "the result size of {} is larger than the allowed limit of {}", actual, limit),
-            DeploymentNotFound(id_or_name) => write!(f, "deployment `{}` does not exist", id_or_name)
+            DeploymentNotFound(id_or_name) => write!(f, "deployment `{}` does not exist", id_or_name),
+            BlockHashNotFound(number, reason) => write!(f, "can not determine the hash of block {}: {}", number, reason),
+            AccessTokenRequired(field) => write!(f, "`{}` requires a valid access token", field),
+            QueryPermitTimeout(timeout) => write!(f, "the store is too busy to run the query; gave up after waiting {}s for it", timeout.as_secs()),
         }
     }
 }
@@ -361,6 +361,9 @@ pub struct ApiSchema {
     // Root types for the api schema.
     pub query_type: Arc<ObjectType>,
     pub subscription_type: Option<Arc<ObjectType>>,
+    /// Only schemas that are not generated from a subgraph's schema, like
+    /// the one for the index node, have a mutation type
+    pub mutation_type: Option<Arc<ObjectType>>,
     object_types: HashMap<String, Arc<ObjectType>>,
 }
 
@@ -384,6 +387,11 @@ impl ApiSchema {
             .get_root_subscription_type()
             .cloned()
             .map(Arc::new);
+        let mutation_type = api_schema
+            .document
+            .get_root_mutation_type()
+            .cloned()
+            .map(Arc::new);
 
         let object_types = HashMap::from_iter(
             api_schema
@@ -397,6 +405,7 @@ impl ApiSchema {
             schema: api_schema,
             query_type: Arc::new(query_type),
             subscription_type,
+            mutation_type,
             object_types,
         })
     }
@@ -641,6 +641,18 @@ impl<C: Blockchain> UnvalidatedSubgraphManifest<C> {
     pub fn spec_version(&self) -> &Version {
         &self.0.spec_version
     }
+
+    pub fn api_versions(&self) -> impl Iterator<Item = semver::Version> + '_ {
+        self.0.api_versions()
+    }
+
+    pub fn data_sources(&self) -> &[C::DataSource] {
+        &self.0.data_sources
+    }
+
+    pub fn templates(&self) -> &[C::DataSourceTemplate] {
+        &self.0.templates
+    }
 }
 
 impl<C: Blockchain> SubgraphManifest<C> {
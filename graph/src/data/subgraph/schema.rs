@@ -5,13 +5,14 @@ use hex;
 use lazy_static::lazy_static;
 use rand::rngs::OsRng;
 use rand::Rng;
+use std::collections::BTreeSet;
 use std::str::FromStr;
 use std::{fmt, fmt::Display};
 
 use super::DeploymentHash;
 use crate::data::graphql::TryFromValue;
 use crate::data::store::Value;
-use crate::data::subgraph::SubgraphManifest;
+use crate::data::subgraph::{SubgraphFeature, SubgraphManifest};
 use crate::prelude::*;
 use crate::util::stable_hash_glue::impl_stable_hash;
 use crate::{blockchain::Blockchain, components::store::EntityType};
@@ -134,6 +135,19 @@ impl DeploymentCreate {
         self.debug_fork = fork;
         self
     }
+
+    /// Record the raw YAML of the manifest alongside the deployment
+    pub fn raw_yaml(mut self, raw_yaml: Option<String>) -> Self {
+        self.manifest.raw_yaml = raw_yaml;
+        self
+    }
+
+    /// Record the features that the subgraph uses alongside the deployment
+    pub fn detected_features(mut self, features: Option<BTreeSet<SubgraphFeature>>) -> Self {
+        self.manifest.detected_features =
+            features.map(|features| features.iter().map(|f| f.to_string()).collect());
+        self
+    }
 }
 
 /// The representation of a subgraph deployment when reading an existing
@@ -163,6 +177,10 @@ pub struct SubgraphManifestEntity {
     pub repository: Option<String>,
     pub features: Vec<String>,
     pub schema: String,
+    pub raw_yaml: Option<String>,
+    /// The features that the subgraph uses, as opposed to the ones it
+    /// declares in `features`
+    pub detected_features: Option<Vec<String>>,
 }
 
 impl<'a, C: Blockchain> From<&'a super::SubgraphManifest<C>> for SubgraphManifestEntity {
@@ -173,6 +191,8 @@ impl<'a, C: Blockchain> From<&'a super::SubgraphManifest<C>> for SubgraphManifes
             repository: manifest.repository.clone(),
             features: manifest.features.iter().map(|f| f.to_string()).collect(),
             schema: manifest.schema.document.clone().to_string(),
+            raw_yaml: None,
+            detected_features: None,
         }
     }
 }
@@ -3,6 +3,7 @@
 use super::schema::{SubgraphError, SubgraphHealth};
 use crate::blockchain::BlockHash;
 use crate::components::store::{BlockNumber, DeploymentId};
+use crate::components::subgraph::ProofOfIndexingVersion;
 use crate::data::graphql::{object, IntoValue};
 use crate::prelude::{r, BlockPtr, Value};
 
@@ -14,12 +15,65 @@ pub enum Filter {
     SubgraphVersion(String, bool),
     /// Get the status of all deployments whose the given given IPFS hashes
     Deployments(Vec<String>),
+    /// Get the status of the deployments with the given IPFS hashes, or of
+    /// all deployments if there are none, whose health is one of the given
+    DeploymentsWithHealth(Vec<String>, Vec<SubgraphHealth>),
     /// Get the status of all deployments with the given ids
     DeploymentIds(Vec<DeploymentId>),
+    /// Get the status of all deployments whose health is `failed`
+    Failed,
+}
+
+/// The order in which `StatusStore::status_page` returns statuses. Deployments that are tied
+/// are ordered by when they were created
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// In the order in which the deployments were created
+    Created,
+    /// By the number of the latest block, deployments that haven't processed a block first
+    LatestBlock,
+    /// By how far the deployments are behind their chain head, least first. Deployments whose
+    /// lag isn't known come last
+    LagAsc,
+    /// Like `LagAsc`, but furthest behind first
+    LagDesc,
+}
+
+impl Order {
+    /// The key to sort the deployment with `id` by when it is at block `latest` on a chain whose
+    /// head is at `head`. A deployment that hasn't processed any blocks yet is as far behind as
+    /// the chain is long, and its lag isn't known if the chain head isn't.
+    pub fn key(
+        self,
+        id: DeploymentId,
+        latest: Option<BlockNumber>,
+        head: Option<BlockNumber>,
+    ) -> (bool, i64, i32) {
+        let lag = head.map(|head| (head - latest.unwrap_or(0)).max(0) as i64);
+        match self {
+            Order::Created => (false, 0, id.0),
+            Order::LatestBlock => (latest.is_some(), latest.unwrap_or(0) as i64, id.0),
+            Order::LagAsc => (lag.is_none(), lag.unwrap_or(0), id.0),
+            Order::LagDesc => (lag.is_none(), -lag.unwrap_or(0), id.0),
+        }
+    }
+}
+
+/// Which of the statuses that a `Filter` selects `StatusStore::status_page` returns
+pub struct Page {
+    /// Only deployments that index this network
+    pub network: Option<String>,
+    /// Only deployments that are assigned to this node
+    pub node: Option<String>,
+    pub order: Order,
+    /// At most this many statuses; all of them if this is `None`
+    pub first: Option<usize>,
+    /// How many statuses to leave out before the ones that are returned
+    pub skip: usize,
 }
 
 /// Light wrapper around `EthereumBlockPointer` that is compatible with GraphQL values.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct EthereumBlock(BlockPtr);
 
 impl EthereumBlock {
@@ -91,6 +145,275 @@ impl IntoValue for ChainInfo {
     }
 }
 
+/// A deployment that is assigned to a node but has not processed any block yet
+#[derive(Debug)]
+pub struct PendingAssignment {
+    /// The deployment hash
+    pub deployment: String,
+    pub node: String,
+    pub paused: bool,
+    pub network: String,
+}
+
+impl IntoValue for PendingAssignment {
+    fn into_value(self) -> r::Value {
+        let PendingAssignment {
+            deployment,
+            node,
+            paused,
+            network,
+        } = self;
+        object! {
+            __typename: "PendingAssignment",
+            deployment: deployment,
+            node: node,
+            paused: paused,
+            network: network,
+        }
+    }
+}
+
+/// A change in the health of a deployment
+#[derive(Debug)]
+pub struct HealthTransition {
+    /// The block at which the health changed, if it is known
+    pub block: Option<BlockNumber>,
+    pub health: SubgraphHealth,
+    /// The message of the fatal error that caused the change, if any
+    pub error: Option<String>,
+}
+
+impl IntoValue for HealthTransition {
+    fn into_value(self) -> r::Value {
+        let HealthTransition {
+            block,
+            health,
+            error,
+        } = self;
+        object! {
+            __typename: "HealthTransition",
+            block: block,
+            health: r::Value::from(health),
+            error: error,
+        }
+    }
+}
+
+/// A version of a subgraph name, i.e., one of the deployments that the name pointed to at
+/// some point
+#[derive(Clone, Debug)]
+pub struct SubgraphVersion {
+    pub deployment: String,
+    /// When the version was created, in seconds since the epoch
+    pub created_at: u64,
+    pub current: bool,
+    pub pending: bool,
+}
+
+impl IntoValue for SubgraphVersion {
+    fn into_value(self) -> r::Value {
+        let SubgraphVersion {
+            deployment,
+            created_at,
+            current,
+            pending,
+        } = self;
+        object! {
+            __typename: "SubgraphVersion",
+            deployment: deployment,
+            createdAt: format!("{}", created_at),
+            current: current,
+            pending: pending,
+        }
+    }
+}
+
+/// How much space a deployment takes up in the database, in bytes
+#[derive(Clone, Copy, Debug)]
+pub struct DeploymentSize {
+    /// The size of the deployment's tables, including data stored out of line
+    pub entities_bytes: u64,
+    /// The size of all indexes on the deployment's tables
+    pub index_bytes: u64,
+}
+
+impl IntoValue for DeploymentSize {
+    fn into_value(self) -> r::Value {
+        let DeploymentSize {
+            entities_bytes,
+            index_bytes,
+        } = self;
+        object! {
+            __typename: "DeploymentSize",
+            entitiesBytes: format!("{}", entities_bytes),
+            indexBytes: format!("{}", index_bytes),
+            totalBytes: format!("{}", entities_bytes + index_bytes),
+        }
+    }
+}
+
+/// The health of all the deployments in the store taken together
+#[derive(Clone, Debug, Default)]
+pub struct NodeHealth {
+    pub total_deployments: u64,
+    pub failed_deployments: u64,
+    /// Deployments that have neither failed nor caught up with the chain head
+    pub syncing_deployments: u64,
+    /// How far the deployment that is furthest behind its chain head lags,
+    /// ignoring failed and unassigned deployments
+    pub max_lag_blocks: Option<BlockNumber>,
+    pub store_reachable: bool,
+}
+
+impl NodeHealth {
+    /// The health to report when the store can not be queried
+    pub fn unreachable() -> Self {
+        NodeHealth {
+            store_reachable: false,
+            ..Default::default()
+        }
+    }
+}
+
+impl IntoValue for NodeHealth {
+    fn into_value(self) -> r::Value {
+        let NodeHealth {
+            total_deployments,
+            failed_deployments,
+            syncing_deployments,
+            max_lag_blocks,
+            store_reachable,
+        } = self;
+        object! {
+            __typename: "NodeHealth",
+            totalDeployments: total_deployments as i32,
+            failedDeployments: failed_deployments as i32,
+            syncingDeployments: syncing_deployments as i32,
+            maxLagBlocks: max_lag_blocks,
+            storeReachable: store_reachable,
+        }
+    }
+}
+
+/// How far the node has gotten with one of the networks it is configured
+/// for, taken across all deployments that index that network
+#[derive(Debug)]
+pub struct NetworkInfo {
+    pub network: String,
+    /// The current head block of the chain
+    pub chain_head_block: Option<EthereumBlock>,
+    /// The smallest earliest block of any deployment on the network, or
+    /// `None` if no deployment indexes it
+    pub earliest_block_number: Option<BlockNumber>,
+    /// The largest latest block of any deployment on the network
+    pub latest_indexed_block: Option<EthereumBlock>,
+}
+
+impl NetworkInfo {
+    pub fn new(network: String, chain_head_block: Option<EthereumBlock>) -> Self {
+        NetworkInfo {
+            network,
+            chain_head_block,
+            earliest_block_number: None,
+            latest_indexed_block: None,
+        }
+    }
+
+    /// Take the chain of one deployment on this network into account
+    pub fn add_deployment(&mut self, chain: &ChainInfo) {
+        self.earliest_block_number = Some(
+            self.earliest_block_number
+                .map_or(chain.earliest_block_number, |earliest| {
+                    earliest.min(chain.earliest_block_number)
+                }),
+        );
+        if let Some(latest) = &chain.latest_block {
+            let is_later = self
+                .latest_indexed_block
+                .as_ref()
+                .map_or(true, |indexed| latest.number() > indexed.number());
+            if is_later {
+                self.latest_indexed_block = Some(latest.clone());
+            }
+        }
+    }
+}
+
+impl IntoValue for NetworkInfo {
+    fn into_value(self) -> r::Value {
+        let NetworkInfo {
+            network,
+            chain_head_block,
+            earliest_block_number,
+            latest_indexed_block,
+        } = self;
+        object! {
+            __typename: "NetworkInfo",
+            network: network,
+            chainHeadBlock: chain_head_block,
+            earliestBlock: earliest_block_number,
+            latestIndexedBlock: latest_indexed_block,
+        }
+    }
+}
+
+fn subgraph_error_to_value(subgraph_error: SubgraphError) -> r::Value {
+    let SubgraphError {
+        subgraph_id,
+        message,
+        block_ptr,
+        handler,
+        deterministic,
+    } = subgraph_error;
+
+    object! {
+        __typename: "SubgraphError",
+        subgraphId: subgraph_id.to_string(),
+        message: message,
+        handler: handler,
+        block: object! {
+            __typename: "Block",
+            number: block_ptr.as_ref().map(|x| x.number),
+            hash: block_ptr.map(|x| r::Value::from(Value::Bytes(x.hash.into()))),
+        },
+        deterministic: deterministic,
+    }
+}
+
+/// A deployment whose health is `failed`, together with the error that made it fail
+#[derive(Debug)]
+pub struct FailedDeployment {
+    pub subgraph: String,
+    pub node: Option<String>,
+    pub fatal_error: Option<SubgraphError>,
+}
+
+impl From<Info> for FailedDeployment {
+    fn from(info: Info) -> Self {
+        FailedDeployment {
+            subgraph: info.subgraph,
+            node: info.node,
+            fatal_error: info.fatal_error,
+        }
+    }
+}
+
+impl IntoValue for FailedDeployment {
+    fn into_value(self) -> r::Value {
+        let FailedDeployment {
+            subgraph,
+            node,
+            fatal_error,
+        } = self;
+        object! {
+            __typename: "FailedDeployment",
+            subgraph: subgraph,
+            node: node,
+            fatalError: fatal_error.map_or(r::Value::Null, subgraph_error_to_value),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Info {
     pub id: DeploymentId,
@@ -109,8 +432,38 @@ pub struct Info {
 
     pub entity_count: u64,
 
+    /// The number of entities of each entity type. Counting them is expensive, and this is
+    /// therefore only filled in on request.
+    pub entities_by_type: Option<Vec<(String, u64)>>,
+
+    /// The space the deployment takes up in the database. Measuring it is expensive, and this
+    /// is therefore only filled in on request.
+    pub deployment_size: Option<DeploymentSize>,
+
+    /// The number of blocks that were reverted because of reorgs since the deployment started
+    pub reorg_count: i32,
+    /// The number of the last block that was reverted, if any were
+    pub last_reorg_block: Option<BlockNumber>,
+
+    /// The earliest block for which the deployment still has entity data, and therefore the
+    /// earliest block it can be queried at. Unless history was pruned, this is the block the
+    /// deployment started at, or the genesis block.
+    pub earliest_block_number: BlockNumber,
+
+    /// How the deployment computes its proofs of indexing, which depends on the spec version of
+    /// its manifest. Proofs of indexing of different versions can't be compared. `None` if the
+    /// spec version of the manifest is not known.
+    pub poi_version: Option<ProofOfIndexingVersion>,
+
     /// ID of the Graph Node that the subgraph is indexed by.
     pub node: Option<String>,
+    /// Whether indexing the subgraph is paused; `None` if the subgraph is not assigned to
+    /// any node
+    pub paused: Option<bool>,
+
+    /// The number of blocks per second that the subgraph processed recently. The store
+    /// doesn't know this; it is filled in by the index node.
+    pub sync_rate: Option<f64>,
 }
 
 impl IntoValue for Info {
@@ -120,41 +473,52 @@ impl IntoValue for Info {
             subgraph,
             chains,
             entity_count,
+            entities_by_type,
+            deployment_size,
+            reorg_count,
+            last_reorg_block,
+            earliest_block_number,
+            poi_version,
             fatal_error,
             health,
             node,
+            paused,
             non_fatal_errors,
             synced,
+            sync_rate,
         } = self;
 
-        fn subgraph_error_to_value(subgraph_error: SubgraphError) -> r::Value {
-            let SubgraphError {
-                subgraph_id,
-                message,
-                block_ptr,
-                handler,
-                deterministic,
-            } = subgraph_error;
-
-            object! {
-                __typename: "SubgraphError",
-                subgraphId: subgraph_id.to_string(),
-                message: message,
-                handler: handler,
-                block: object! {
-                    __typename: "Block",
-                    number: block_ptr.as_ref().map(|x| x.number),
-                    hash: block_ptr.map(|x| r::Value::from(Value::Bytes(x.hash.into()))),
-                },
-                deterministic: deterministic,
-            }
-        }
+        // The chain head can be behind the latest block for a moment after a
+        // reorg; the subgraph is then at the chain head, not ahead of it
+        let estimated_seconds_to_chain_head = match (synced, sync_rate, chains.first()) {
+            (false, Some(rate), Some(chain)) if rate > 0.0 => chain
+                .chain_head_block
+                .as_ref()
+                .zip(chain.latest_block.as_ref())
+                .map(|(head, latest)| {
+                    let lag = (head.number() - latest.number()).max(0);
+                    (lag as f64 / rate).ceil() as i32
+                }),
+            _ => None,
+        };
 
         let non_fatal_errors: Vec<_> = non_fatal_errors
             .into_iter()
             .map(subgraph_error_to_value)
             .collect();
         let fatal_error_val = fatal_error.map_or(r::Value::Null, subgraph_error_to_value);
+        let entities_by_type = entities_by_type.map(|counts| {
+            counts
+                .into_iter()
+                .map(|(entity_type, count)| {
+                    object! {
+                        __typename: "EntityTypeCount",
+                        type: entity_type,
+                        count: format!("{}", count),
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
 
         object! {
             __typename: "SubgraphIndexingStatus",
@@ -165,7 +529,16 @@ impl IntoValue for Info {
             nonFatalErrors: non_fatal_errors,
             chains: chains.into_iter().map(|chain| chain.into_value()).collect::<Vec<_>>(),
             entityCount: format!("{}", entity_count),
+            entitiesByType: entities_by_type,
+            deploymentSize: deployment_size,
+            reorgCount: reorg_count,
+            lastReorgBlock: last_reorg_block,
+            earliestBlock: earliest_block_number,
+            poiVersion: poi_version.map(|version| r::Value::Enum(version.as_str().to_string())),
             node: node,
+            paused: paused,
+            syncRate: sync_rate,
+            estimatedSecondsToChainHead: estimated_seconds_to_chain_head,
         }
     }
 }
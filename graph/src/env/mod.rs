@@ -197,6 +197,35 @@ pub struct EnvVars {
     /// Set by the environment variable `GRAPH_EXPLORER_QUERY_THRESHOLD`
     /// (expressed in milliseconds). The default value is 500ms.
     pub explorer_query_threshold: Duration,
+    /// Set by the environment variable `GRAPH_SUBGRAPH_FEATURES_CACHE_SIZE`.
+    /// The default value is 1000.
+    pub subgraph_features_cache_size: usize,
+    /// Set by the environment variable `GRAPH_SUBGRAPH_FEATURES_CACHE_TTL`
+    /// (expressed in seconds). The default value is 3600s.
+    pub subgraph_features_cache_ttl: Duration,
+    /// Set by the environment variable `GRAPH_SYNC_RATE_WINDOW` (expressed
+    /// in seconds). The default value is 300s.
+    pub sync_rate_window: Duration,
+    /// Set by the environment variable `GRAPH_DEPLOYMENT_SIZE_CACHE_TTL`
+    /// (expressed in seconds). The default value is 60s.
+    pub deployment_size_cache_ttl: Duration,
+    /// Set by the environment variable
+    /// `GRAPH_INDEX_NODE_QUERY_PERMIT_TIMEOUT` (expressed in seconds). The
+    /// default value is 30s.
+    pub index_node_query_permit_timeout: Duration,
+    /// Set by the environment variable `GRAPH_INDEX_NODE_STATUS_DEBOUNCE`
+    /// (expressed in milliseconds). The default value is 500ms.
+    pub index_node_status_debounce: Duration,
+    /// Set by the environment variable
+    /// `GRAPH_INDEX_NODE_STATUS_POLL_INTERVAL` (expressed in seconds). The
+    /// default value is 10s.
+    pub index_node_status_poll_interval: Duration,
+    /// Set by the environment variable `GRAPH_POI_RANGE_MAX_BLOCKS`. The
+    /// default value is 1000.
+    pub poi_range_max_blocks: usize,
+    /// Set by the environment variable `GRAPH_INDEXING_STATUSES_MAX_FIRST`.
+    /// The default value is 1000.
+    pub indexing_statuses_max_first: usize,
     /// Set by the environment variable `EXTERNAL_HTTP_BASE_URL`. No default
     /// value is provided.
     pub external_http_base_url: Option<String>,
@@ -256,6 +285,23 @@ impl EnvVars {
             explorer_ttl: Duration::from_secs(inner.explorer_ttl_in_secs),
             explorer_lock_threshold: Duration::from_millis(inner.explorer_lock_threshold_in_msec),
             explorer_query_threshold: Duration::from_millis(inner.explorer_query_threshold_in_msec),
+            subgraph_features_cache_size: inner.subgraph_features_cache_size,
+            subgraph_features_cache_ttl: Duration::from_secs(
+                inner.subgraph_features_cache_ttl_in_secs,
+            ),
+            sync_rate_window: Duration::from_secs(inner.sync_rate_window_in_secs),
+            deployment_size_cache_ttl: Duration::from_secs(inner.deployment_size_cache_ttl_in_secs),
+            index_node_query_permit_timeout: Duration::from_secs(
+                inner.index_node_query_permit_timeout_in_secs,
+            ),
+            index_node_status_debounce: Duration::from_millis(
+                inner.index_node_status_debounce_in_msec,
+            ),
+            index_node_status_poll_interval: Duration::from_secs(
+                inner.index_node_status_poll_interval_in_secs,
+            ),
+            poi_range_max_blocks: inner.poi_range_max_blocks,
+            indexing_statuses_max_first: inner.indexing_statuses_max_first,
             external_http_base_url: inner.external_http_base_url,
             external_ws_base_url: inner.external_ws_base_url,
         })
@@ -367,6 +413,24 @@ struct Inner {
     explorer_lock_threshold_in_msec: u64,
     #[envconfig(from = "GRAPH_EXPLORER_QUERY_THRESHOLD", default = "500")]
     explorer_query_threshold_in_msec: u64,
+    #[envconfig(from = "GRAPH_SUBGRAPH_FEATURES_CACHE_SIZE", default = "1000")]
+    subgraph_features_cache_size: usize,
+    #[envconfig(from = "GRAPH_SUBGRAPH_FEATURES_CACHE_TTL", default = "3600")]
+    subgraph_features_cache_ttl_in_secs: u64,
+    #[envconfig(from = "GRAPH_SYNC_RATE_WINDOW", default = "300")]
+    sync_rate_window_in_secs: u64,
+    #[envconfig(from = "GRAPH_DEPLOYMENT_SIZE_CACHE_TTL", default = "60")]
+    deployment_size_cache_ttl_in_secs: u64,
+    #[envconfig(from = "GRAPH_INDEX_NODE_QUERY_PERMIT_TIMEOUT", default = "30")]
+    index_node_query_permit_timeout_in_secs: u64,
+    #[envconfig(from = "GRAPH_INDEX_NODE_STATUS_DEBOUNCE", default = "500")]
+    index_node_status_debounce_in_msec: u64,
+    #[envconfig(from = "GRAPH_INDEX_NODE_STATUS_POLL_INTERVAL", default = "10")]
+    index_node_status_poll_interval_in_secs: u64,
+    #[envconfig(from = "GRAPH_POI_RANGE_MAX_BLOCKS", default = "1000")]
+    poi_range_max_blocks: usize,
+    #[envconfig(from = "GRAPH_INDEXING_STATUSES_MAX_FIRST", default = "1000")]
+    indexing_statuses_max_first: usize,
     #[envconfig(from = "EXTERNAL_HTTP_BASE_URL")]
     external_http_base_url: Option<String>,
     #[envconfig(from = "EXTERNAL_WS_BASE_URL")]
@@ -3,7 +3,7 @@ use crate::{
     blockchain::BlockPtr,
     cheap_clone::CheapClone,
     components::store::BlockNumber,
-    firehose::{decode_firehose_block, ForkStep},
+    firehose::{helpers::decode_firehose_block_as, ForkStep},
     prelude::{debug, info},
 };
 use futures03::StreamExt;
@@ -89,6 +89,20 @@ impl FirehoseEndpoint {
         logger: &Logger,
         number: BlockNumber,
     ) -> Result<BlockPtr, anyhow::Error>
+    where
+        M: prost::Message + BlockchainBlock + Default + 'static,
+    {
+        self.block_for_number::<M>(logger, number)
+            .await
+            .map(|block| block.ptr())
+    }
+
+    /// Retrieves the block with `number` on the canonical chain, as far as Firehose knows it
+    pub async fn block_for_number<M>(
+        &self,
+        logger: &Logger,
+        number: BlockNumber,
+    ) -> Result<M, anyhow::Error>
     where
         M: prost::Message + BlockchainBlock + Default + 'static,
     {
@@ -138,17 +152,17 @@ impl FirehoseEndpoint {
 
         debug!(logger, "Retrieving block(s) from firehose");
 
-        let mut latest_received_block: Option<BlockPtr> = None;
+        let mut latest_received_block: Option<M> = None;
         while let Some(message) = block_stream.next().await {
             match message {
                 Ok(v) => {
-                    let block = decode_firehose_block::<M>(&v)?.ptr();
+                    let block = decode_firehose_block_as::<M>(&v)?;
 
                     match latest_received_block {
                         None => {
                             latest_received_block = Some(block);
                         }
-                        Some(ref actual_ptr) => {
+                        Some(ref actual_block) => {
                             // We want to receive all events related to a specific block number,
                             // however, in some circumstances, it seems Firehose would not stop sending
                             // blocks (`start_block_num: 0 and stop_block_num: 0` on NEAR seems to trigger
@@ -157,7 +171,7 @@ impl FirehoseEndpoint {
                             // To prevent looping infinitely, we stop as soon as a new received block's
                             // number is higher than the latest received block's number, in which case it
                             // means it's an event for a block we are not interested in.
-                            if block.number > actual_ptr.number {
+                            if block.number() > actual_block.number() {
                                 break;
                             }
 
@@ -170,7 +184,7 @@ impl FirehoseEndpoint {
         }
 
         match latest_received_block {
-            Some(block_ptr) => Ok(block_ptr),
+            Some(block) => Ok(block),
             None => Err(anyhow::format_err!(
                 "Firehose should have returned at least one block for request"
             )),
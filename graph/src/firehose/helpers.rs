@@ -7,6 +7,14 @@ use anyhow::Error;
 pub fn decode_firehose_block<M>(
     block_response: &firehose::Response,
 ) -> Result<Arc<dyn BlockchainBlock>, Error>
+where
+    M: prost::Message + BlockchainBlock + Default + 'static,
+{
+    Ok(Arc::new(decode_firehose_block_as::<M>(block_response)?))
+}
+
+/// Like `decode_firehose_block`, but keeps the concrete type of the block
+pub(crate) fn decode_firehose_block_as<M>(block_response: &firehose::Response) -> Result<M, Error>
 where
     M: prost::Message + BlockchainBlock + Default + 'static,
 {
@@ -15,5 +23,5 @@ where
         .as_ref()
         .expect("block payload information should always be present");
 
-    Ok(Arc::new(M::decode(any_block.value.as_ref())?))
+    Ok(M::decode(any_block.value.as_ref())?)
 }
@@ -71,6 +71,7 @@ pub enum ComplexityError {
 enum Kind {
     Query,
     Subscription,
+    Mutation,
 }
 
 /// Helper to log the fields in a `SelectionSet` without cloning. Writes
@@ -200,6 +201,13 @@ impl Query {
             q::OperationDefinition::Subscription(q::Subscription { selection_set, .. }) => {
                 (Kind::Subscription, selection_set)
             }
+            // Only schemas with a `Mutation` type, which subgraph schemas
+            // never have, support mutations
+            q::OperationDefinition::Mutation(q::Mutation { selection_set, .. })
+                if schema.mutation_type.is_some() =>
+            {
+                (Kind::Mutation, selection_set)
+            }
             q::OperationDefinition::Mutation(_) => {
                 return Err(vec![QueryExecutionError::NotSupported(
                     "Mutations are not supported".to_owned(),
@@ -223,6 +231,7 @@ impl Query {
         let root_type = match kind {
             Kind::Query => schema.query_type.as_ref(),
             Kind::Subscription => schema.subscription_type.as_ref().unwrap(),
+            Kind::Mutation => schema.mutation_type.as_ref().unwrap(),
         };
         // Use an intermediate struct so we can modify the query before
         // enclosing it in an Arc
@@ -318,7 +327,7 @@ impl Query {
     pub fn is_query(&self) -> bool {
         match self.kind {
             Kind::Query => true,
-            Kind::Subscription => false,
+            Kind::Subscription | Kind::Mutation => false,
         }
     }
 
@@ -326,10 +335,28 @@ impl Query {
     pub fn is_subscription(&self) -> bool {
         match self.kind {
             Kind::Subscription => true,
-            Kind::Query => false,
+            Kind::Query | Kind::Mutation => false,
+        }
+    }
+
+    /// Return `true` if this is a mutation, not a query or a subscription
+    pub fn is_mutation(&self) -> bool {
+        match self.kind {
+            Kind::Mutation => true,
+            Kind::Query | Kind::Subscription => false,
         }
     }
 
+    /// The type whose fields the toplevel fields of the query select
+    pub fn root_type(&self) -> sast::ObjectType {
+        let root_type = match self.kind {
+            Kind::Query => &self.schema.query_type,
+            Kind::Subscription => self.schema.subscription_type.as_ref().unwrap(),
+            Kind::Mutation => self.schema.mutation_type.as_ref().unwrap(),
+        };
+        root_type.cheap_clone().into()
+    }
+
     /// Log details about the overall execution of the query
     pub fn log_execution(&self, block: BlockNumber) {
         if ENV_VARS.log_gql_timing() {
@@ -587,7 +614,7 @@ impl<'s> RawQuery<'s> {
     /// If the query is invalid, returns `Ok(0)` so that execution proceeds and
     /// gives a proper error.
     fn complexity(&self, max_depth: u8) -> Result<u64, QueryExecutionError> {
-        let root_type = self.schema.get_root_query_type_def().unwrap();
+        let root_type = self.schema.get_named_type(&self.root_type.name).unwrap();
 
         match self.complexity_inner(
             root_type,
@@ -609,10 +636,10 @@ impl<'s> RawQuery<'s> {
     }
 
     fn validate_fields(&self) -> Result<(), Vec<QueryExecutionError>> {
-        let root_type = self.schema.query_type.as_ref();
+        let root_type = self.root_type;
 
         let errors =
-            self.validate_fields_inner(&"Query".to_owned(), root_type.into(), &self.selection_set);
+            self.validate_fields_inner(&root_type.name, root_type.into(), &self.selection_set);
         if errors.is_empty() {
             Ok(())
         } else {
@@ -28,7 +28,9 @@ mod runner;
 pub mod prelude {
     pub use super::execution::{ast as a, ExecutionContext, Query, Resolver};
     pub use super::introspection::IntrospectionResolver;
-    pub use super::query::{execute_query, ext::BlockConstraint, QueryExecutionOptions};
+    pub use super::query::{
+        execute_query, execute_subscription_once, ext::BlockConstraint, QueryExecutionOptions,
+    };
     pub use super::schema::{api_schema, APISchemaError};
     pub use super::store::StoreResolver;
     pub use super::subscription::SubscriptionExecutionOptions;
@@ -51,22 +51,26 @@ where
         cache_status: Default::default(),
     });
 
-    if !query.is_query() {
+    if !query.is_query() && !query.is_mutation() {
         return Arc::new(
-            QueryExecutionError::NotSupported("Only queries are supported".to_string()).into(),
+            QueryExecutionError::NotSupported(
+                "Only queries and mutations are supported".to_string(),
+            )
+            .into(),
         );
     }
     let selection_set = selection_set
         .map(Arc::new)
         .unwrap_or_else(|| query.selection_set.cheap_clone());
 
-    // Execute top-level `query { ... }` and `{ ... }` expressions.
-    let query_type = ctx.query.schema.query_type.cheap_clone().into();
+    // Execute top-level `query { ... }`, `{ ... }` and `mutation { ... }`
+    // expressions.
+    let root_type = query.root_type();
     let start = Instant::now();
     let result = execute_root_selection_set(
         ctx.cheap_clone(),
         selection_set.cheap_clone(),
-        query_type,
+        root_type,
         block_ptr.clone(),
     )
     .await;
@@ -83,3 +87,39 @@ where
     );
     result
 }
+
+/// Executes a subscription once, for a single event. This is for
+/// subscriptions whose events are not store changes; the resolver has to
+/// know which event it resolves the subscription's fields for.
+pub async fn execute_subscription_once<R>(
+    query: Arc<Query>,
+    options: QueryExecutionOptions<R>,
+) -> Arc<QueryResult>
+where
+    R: Resolver,
+{
+    let ctx = Arc::new(ExecutionContext {
+        logger: query.logger.clone(),
+        resolver: options.resolver,
+        query: query.clone(),
+        deadline: options.deadline,
+        max_first: options.max_first,
+        max_skip: options.max_skip,
+        cache_status: Default::default(),
+    });
+
+    if !query.is_subscription() {
+        return Arc::new(
+            QueryExecutionError::NotSupported("Only subscriptions are supported".to_string())
+                .into(),
+        );
+    }
+
+    execute_root_selection_set(
+        ctx,
+        query.selection_set.cheap_clone(),
+        query.root_type(),
+        None,
+    )
+    .await
+}
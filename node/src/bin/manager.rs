@@ -1,7 +1,7 @@
 use config::PoolSize;
 use git_testament::{git_testament, render_testament};
-use graph::{data::graphql::effort::LoadManager, prelude::chrono, prometheus::Registry};
 use graph::{
+    blockchain::BlockchainKind,
     log::logger,
     prelude::{
         anyhow::{self, Context as AnyhowContextTrait},
@@ -9,14 +9,21 @@ use graph::{
     },
     url::Url,
 };
-use graph_chain_ethereum::{EthereumAdapter, EthereumNetworks};
+use graph::{data::graphql::effort::LoadManager, prelude::chrono, prometheus::Registry};
+use graph_chain_ethereum::EthereumNetworks;
 use graph_core::MetricsRegistry;
 use graph_graphql::prelude::GraphQlRunner;
-use graph_node::config::{self, Config as Cfg};
+use graph_node::config::{self, Config as Cfg, ProviderDetails};
 use graph_node::manager::commands;
 use graph_node::{
-    chain::create_ethereum_networks,
-    manager::{deployment::DeploymentSearch, PanicSubscriptionManager},
+    chain::{create_ethereum_networks, create_firehose_networks},
+    manager::{
+        commands::check_blocks::{
+            BlockList, BlockProvider, BlockSource, FirehoseProvider, JsonRpcProvider,
+        },
+        deployment::DeploymentSearch,
+        PanicSubscriptionManager,
+    },
     store_builder::StoreBuilder,
     MetricsContext,
 };
@@ -26,7 +33,9 @@ use graph_store_postgres::{
     SubscriptionManager, PRIMARY_SHARD,
 };
 use lazy_static::lazy_static;
-use std::{collections::HashMap, env, num::ParseIntError, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap, env, num::ParseIntError, path::PathBuf, sync::Arc, time::Duration,
+};
 use structopt::StructOpt;
 
 const VERSION_LABEL_KEY: &str = "version";
@@ -195,6 +204,21 @@ pub enum Command {
         /// The variables in the form `key=value`
         vars: Vec<String>,
     },
+    /// Find the first block where the proof of indexing differs from another indexer's
+    ///
+    /// Compares the public proofs of indexing of the deployment in this
+    /// installation with the ones that the index node at `reference-url`
+    /// reports, and prints the first block in the range where they differ
+    ComparePoi {
+        /// The deployment (see `help info`)
+        deployment: DeploymentSearch,
+        /// The URL of the reference indexer's index node GraphQL API
+        reference_url: String,
+        /// The first block of the range to compare
+        from: i32,
+        /// The last block of the range to compare
+        to: i32,
+    },
     /// Get information about chains and manipulate them
     Chain(ChainCommand),
     /// Manipulate internal subgraph statistics
@@ -238,6 +262,18 @@ pub enum UnusedCommand {
         #[structopt(short, long)]
         older: Option<u32>,
     },
+    /// List deployments that no subgraph version refers to
+    ///
+    /// Such deployments are left behind, e.g., when deploying a subgraph
+    /// fails after its deployment was created. Deployments that are
+    /// assigned to a node or take part in a copy are not listed
+    ListOrphans,
+    /// Remove the deployments that `list-orphans` lists
+    RemoveOrphans {
+        /// Actually remove the deployments instead of only listing them
+        #[structopt(long)]
+        yes: bool,
+    },
 }
 
 #[derive(Clone, Debug, StructOpt)]
@@ -350,15 +386,148 @@ pub enum ChainCommand {
         /// Chain name (must be an existing chain, see 'chain list')
         #[structopt(empty_values = false)]
         chain_name: String,
+
+        /// Only report differences, never delete any blocks from the cache
+        #[structopt(long)]
+        dry_run: bool,
+
+        /// Write the block from the provider to the cache after deleting a diverging block
+        ///
+        /// Without this, deleted blocks are only fetched again if block ingestion needs them,
+        /// which it doesn't for blocks far below the chain head.
+        #[structopt(long, conflicts_with = "dry-run")]
+        reinsert: bool,
+
+        /// Change the block cache even if another run is changing it for the same chain
+        ///
+        /// Only meant for emergencies, e.g., when the lock was left behind by a run that is
+        /// stuck.
+        #[structopt(long)]
+        force: bool,
+
+        /// Also compare the transaction receipts of blocks with the ones from the provider
+        ///
+        /// Blocks whose receipts diverge are deleted just like blocks that diverge themselves.
+        #[structopt(long)]
+        with_receipts: bool,
+
+        /// Fields of blocks to leave out when comparing them, separated by commas
+        ///
+        /// By default, fields that providers compute on the fly and that can change after a
+        /// block was cached are ignored. Pass an empty list to compare all fields.
+        #[structopt(long, use_delimiter = true, default_value = "totalDifficulty,size")]
+        ignore_fields: Vec<String>,
+
+        /// Don't color diffs. Diffs are never colored when they aren't written to a terminal
+        #[structopt(long)]
+        no_color: bool,
+
+        /// Also show the top-level fields of blocks that are the same in diffs, not just the
+        /// fields that differ
+        #[structopt(long)]
+        full_context: bool,
+
+        /// How many times to try fetching a block from the provider before giving up on it
+        #[structopt(long, default_value = "3")]
+        provider_attempts: usize,
+
+        /// How many milliseconds to wait before fetching a block from the provider again
+        ///
+        /// The delay doubles with every further attempt.
+        #[structopt(
+            long,
+            default_value = "500",
+            parse(try_from_str = parse_duration_in_millis)
+        )]
+        provider_retry_delay: Duration,
+
+        /// Write the number and hash of every block that gets deleted from the cache to this
+        /// file, as newline-delimited JSON
+        #[structopt(long, conflicts_with = "dry-run", parse(from_os_str))]
+        report_file: Option<PathBuf>,
+    },
+    /// Writes a cached block, the same block from the provider, or both, as JSON files for
+    /// offline inspection.
+    ///
+    /// The files are named `<hash>.cache.json` and `<hash>.provider.json`.
+    ExportBlock {
+        /// Chain name (must be an existing chain, see 'chain list')
+        #[structopt(empty_values = false)]
+        chain_name: String,
+        /// The hash of the block; the block must be in the cache
+        hash: String,
+        /// Which versions of the block to write: `cache`, `provider` or `both`
+        #[structopt(long, default_value = "both")]
+        source: BlockSource,
+        /// The directory to write the files to
+        #[structopt(long, default_value = ".", parse(from_os_str))]
+        out: PathBuf,
+        /// How many times to try fetching the block from the provider before giving up on it
+        #[structopt(long, default_value = "3")]
+        provider_attempts: usize,
+        /// How many milliseconds to wait before fetching the block from the provider again
+        ///
+        /// The delay doubles with every further attempt.
+        #[structopt(
+            long,
+            default_value = "500",
+            parse(try_from_str = parse_duration_in_millis)
+        )]
+        provider_retry_delay: Duration,
     },
-    /// Truncates the whole block cache for the given chain.
+    /// Truncates the block cache for the given chain.
+    ///
+    /// Without `--from` and `--to`, the whole block cache is truncated. With either of them,
+    /// only the blocks in that range are deleted, and the genesis block is always kept.
     Truncate {
         /// Chain name (must be an existing chain, see 'chain list')
         #[structopt(empty_values = false)]
         chain_name: String,
+        /// The first block number to delete
+        #[structopt(long)]
+        from: Option<i32>,
+        /// The last block number to delete. Defaults to the chain head
+        #[structopt(long)]
+        to: Option<i32>,
         /// Skips confirmation prompt
         #[structopt(long, short)]
         force: bool,
+        /// Print what was deleted as a JSON object, for use in scripts.
+        ///
+        /// Only for truncating the whole cache; combine with `--force` to avoid the prompt.
+        #[structopt(long, conflicts_with_all = &["from", "to"])]
+        json: bool,
+    },
+    /// Points the chain head at the highest cached block whose parent is also cached.
+    ///
+    /// Deleting blocks near the chain head, e.g., with `check-blocks` or `purge-range`, can leave
+    /// the chain head pointing at a block that is no longer cached. Prints the chain head before
+    /// and after the repair.
+    RepairChainHead {
+        /// Chain name (must be an existing chain, see 'chain list')
+        #[structopt(empty_values = false)]
+        chain_name: String,
+        /// Only print which block the chain head would point at, don't change it
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Deletes the cached blocks in a range of block numbers for the given chain.
+    ///
+    /// Unlike `check-blocks by-range`, this does not compare the blocks with a provider; all
+    /// blocks in the range are deleted. The genesis block can't be deleted.
+    PurgeRange {
+        /// Chain name (must be an existing chain, see 'chain list')
+        #[structopt(empty_values = false)]
+        chain_name: String,
+        /// The first block number to delete
+        #[structopt(long, short)]
+        from: Option<i32>,
+        /// The last block number to delete. Defaults to the chain head
+        #[structopt(long, short)]
+        to: Option<i32>,
+        /// Skips confirmation prompt
+        #[structopt(long)]
+        force: bool,
     },
 }
 
@@ -462,17 +631,83 @@ pub enum IndexCommand {
 #[derive(Clone, Debug, StructOpt)]
 pub enum CheckBlockMethod {
     /// The number of the target block
-    ByHash { hash: String },
+    ByHash {
+        hash: String,
+        /// Also check up to this many ancestors of the block, following the parent hashes of
+        /// the cached blocks
+        #[structopt(long, default_value = "0")]
+        ancestors: usize,
+    },
 
     /// The hash of the target block
-    ByNumber { number: i32 },
+    ByNumber {
+        number: i32,
+        /// Also check up to this many ancestors of the block, following the parent hashes of
+        /// the cached blocks
+        #[structopt(long, default_value = "0")]
+        ancestors: usize,
+    },
+
+    /// A Unix timestamp; the target is the first cached block with a timestamp at or after it
+    ByTimestamp { timestamp: u64 },
 
     /// A block number range, inclusive on both ends.
+    ///
+    /// Prints a summary at the end, and exits with an error if any blocks diverged.
     ByRange {
         #[structopt(long, short)]
         from: Option<i32>,
         #[structopt(long, short)]
         to: Option<i32>,
+        /// Check exactly these blocks instead of a range, e.g. `500` or `100,205,4001`
+        #[structopt(long, conflicts_with_all = &["from", "to", "resume-from"])]
+        blocks: Option<BlockList>,
+        /// Only check every Nth block of the range, starting with its first block
+        #[structopt(long, conflicts_with = "blocks")]
+        step: Option<usize>,
+        /// Start at this block instead of the beginning of the range.
+        ///
+        /// Without this, a run over the same range as an earlier run that was interrupted
+        /// continues after the last block that run completed.
+        #[structopt(long)]
+        resume_from: Option<i32>,
+        /// How many blocks to fetch from the provider at the same time
+        #[structopt(long, default_value = "10")]
+        concurrency: usize,
+        /// Only report which blocks diverge, and fail if any do.
+        ///
+        /// Nothing is deleted and no diffs are printed; the list of diverging blocks is printed
+        /// at the end.
+        #[structopt(long)]
+        verify: bool,
+        /// Don't ask for confirmation before deleting the first diverging block
+        #[structopt(long = "yes", short = "y", visible_alias = "skip-confirmation")]
+        skip_confirmation: bool,
+        /// Keep going when a block can't be checked or fixed, and list such blocks at the end.
+        ///
+        /// Blocks that are not in the cache are skipped, and listed separately.
+        #[structopt(long)]
+        continue_on_error: bool,
+        /// Only check blocks up to the chain head at the start of the run.
+        ///
+        /// Without this, a range without `--to` also covers the blocks that the chain head
+        /// moves past during the run. On a live chain, such a run can go on for as long as the
+        /// chain head keeps moving faster than the run checks blocks.
+        #[structopt(long)]
+        no_follow_head: bool,
+        /// Only check that each cached block's parent hash is the hash of the cached block
+        /// before it, and report every block where that is not the case.
+        ///
+        /// This uses only the block cache and catches blocks that are fine on their own but
+        /// don't link up, e.g., after a reorg that was only partially applied.
+        #[structopt(long, conflicts_with_all = &["blocks", "step", "resume-from", "verify"])]
+        check_chain: bool,
+        /// Only report how many blocks of the range are in the cache, how many are missing, and
+        /// how many of the cached blocks diverge from the provider.
+        ///
+        /// Nothing is deleted, and diverging blocks don't make the run fail.
+        #[structopt(long, conflicts_with_all = &["check-chain", "resume-from", "verify"])]
+        stats: bool,
     },
 }
 
@@ -667,29 +902,58 @@ impl Context {
     }
 
     fn chain_store(self, chain_name: &str) -> anyhow::Result<Arc<ChainStore>> {
-        use graph::components::store::BlockStore;
-        self.store()
-            .block_store()
-            .chain_store(&chain_name)
-            .ok_or_else(|| anyhow::anyhow!("Could not find a network named '{}'", chain_name))
+        commands::check_blocks::resolve_chain_store(&self.store().block_store(), chain_name)
     }
 
-    async fn chain_store_and_adapter(
+    /// The chain store for `chain_name` and a provider to check the blocks in it against. We
+    /// use a JRPC provider if the chain has one, and fall back to Firehose otherwise
+    async fn chain_store_and_provider(
         self,
         chain_name: &str,
-    ) -> anyhow::Result<(Arc<ChainStore>, Arc<EthereumAdapter>)> {
-        let ethereum_networks = self.ethereum_networks().await?;
-        let chain_store = self.chain_store(chain_name)?;
-        let ethereum_adapter = ethereum_networks
-            .networks
+    ) -> anyhow::Result<(Arc<ChainStore>, Box<dyn BlockProvider>)> {
+        let has_web3 = self
+            .config
+            .chains
+            .chains
             .get(chain_name)
-            .map(|adapters| adapters.cheapest())
-            .flatten()
-            .ok_or(anyhow::anyhow!(
-                "Failed to obtain an Ethereum adapter for chain '{}'",
-                chain_name
-            ))?;
-        Ok((chain_store, ethereum_adapter))
+            .map_or(false, |chain| {
+                chain
+                    .providers
+                    .iter()
+                    .any(|provider| matches!(provider.details, ProviderDetails::Web3(_)))
+            });
+        let block_provider: Box<dyn BlockProvider> = if has_web3 {
+            let ethereum_networks = self.ethereum_networks().await?;
+            let ethereum_adapter = ethereum_networks
+                .networks
+                .get(chain_name)
+                .map(|adapters| adapters.cheapest())
+                .flatten()
+                .ok_or(anyhow::anyhow!(
+                    "Failed to obtain an Ethereum adapter for chain '{}'",
+                    chain_name
+                ))?;
+            Box::new(JsonRpcProvider::new(ethereum_adapter))
+        } else {
+            let firehose_networks = create_firehose_networks(
+                self.logger.clone(),
+                self.metrics_registry(),
+                &self.config,
+            )
+            .await?;
+            let endpoint = firehose_networks
+                .get(&BlockchainKind::Ethereum)
+                .and_then(|networks| networks.networks.get(chain_name))
+                .and_then(|endpoints| endpoints.random())
+                .cloned()
+                .ok_or(anyhow::anyhow!(
+                    "Failed to obtain a JRPC or Firehose provider for chain '{}'",
+                    chain_name
+                ))?;
+            Box::new(FirehoseProvider::new(endpoint))
+        };
+        let chain_store = self.chain_store(chain_name)?;
+        Ok((chain_store, block_provider))
     }
 }
 
@@ -796,6 +1060,8 @@ async fn main() -> anyhow::Result<()> {
                     let older = older.map(|older| chrono::Duration::minutes(older as i64));
                     commands::unused_deployments::remove(store, count, deployment, older)
                 }
+                ListOrphans => commands::unused_deployments::list_orphans(store),
+                RemoveOrphans { yes } => commands::unused_deployments::remove_orphans(store, yes),
             }
         }
         Config(cmd) => {
@@ -908,6 +1174,15 @@ async fn main() -> anyhow::Result<()> {
             query,
             vars,
         } => commands::query::run(ctx.graphql_runner(), target, query, vars).await,
+        ComparePoi {
+            deployment,
+            reference_url,
+            from,
+            to,
+        } => {
+            let (store, primary) = ctx.store_and_primary();
+            commands::poi::compare(store, primary, deployment, reference_url, from, to).await
+        }
         Chain(cmd) => {
             use ChainCommand::*;
             match cmd {
@@ -927,28 +1202,188 @@ async fn main() -> anyhow::Result<()> {
                     let (block_store, primary) = ctx.block_store_and_primary_pool();
                     commands::chain::remove(primary, block_store, name)
                 }
-                CheckBlocks { method, chain_name } => {
-                    use commands::check_blocks::{by_hash, by_number, by_range};
+                CheckBlocks {
+                    method,
+                    chain_name,
+                    dry_run,
+                    reinsert,
+                    force,
+                    with_receipts,
+                    ignore_fields,
+                    no_color,
+                    full_context,
+                    provider_attempts,
+                    provider_retry_delay,
+                    report_file,
+                } => {
+                    use commands::check_blocks::{
+                        by_hash, by_number, by_range, by_timestamp, CheckOptions, FixOptions,
+                        RangeOptions,
+                    };
                     use CheckBlockMethod::*;
                     let logger = ctx.logger.clone();
-                    let (chain_store, ethereum_adapter) =
-                        ctx.chain_store_and_adapter(&chain_name).await?;
+                    let (chain_store, block_provider) =
+                        ctx.chain_store_and_provider(&chain_name).await?;
+                    let fix_options = FixOptions {
+                        dry_run,
+                        reinsert,
+                        force,
+                    };
+                    // Diffs are printed to stderr
+                    let check_options = CheckOptions {
+                        with_receipts,
+                        ignore_fields: ignore_fields
+                            .into_iter()
+                            .filter(|field| !field.is_empty())
+                            .collect(),
+                        colorize: !no_color && atty::is(atty::Stream::Stderr),
+                        full_context,
+                        provider_attempts,
+                        provider_retry_delay,
+                    };
                     match method {
-                        ByHash { hash } => {
-                            by_hash(&hash, chain_store, &ethereum_adapter, &logger).await
+                        ByHash { hash, ancestors } => {
+                            by_hash(
+                                &hash,
+                                ancestors,
+                                chain_store,
+                                block_provider.as_ref(),
+                                fix_options,
+                                &check_options,
+                                report_file,
+                                &logger,
+                            )
+                            .await
                         }
-                        ByNumber { number } => {
-                            by_number(number, chain_store, &ethereum_adapter, &logger).await
+                        ByNumber { number, ancestors } => {
+                            by_number(
+                                number,
+                                ancestors,
+                                chain_store,
+                                block_provider.as_ref(),
+                                fix_options,
+                                &check_options,
+                                report_file,
+                                &logger,
+                            )
+                            .await
                         }
-                        ByRange { from, to } => {
-                            by_range(chain_store, &ethereum_adapter, from, to, &logger).await
+                        ByTimestamp { timestamp } => {
+                            by_timestamp(
+                                timestamp,
+                                chain_store,
+                                block_provider.as_ref(),
+                                fix_options,
+                                &check_options,
+                                report_file,
+                                &logger,
+                            )
+                            .await
                         }
+                        ByRange {
+                            from,
+                            to,
+                            blocks,
+                            step,
+                            resume_from,
+                            concurrency,
+                            verify,
+                            skip_confirmation,
+                            continue_on_error,
+                            no_follow_head,
+                            check_chain,
+                            stats,
+                        } => {
+                            let options = RangeOptions {
+                                blocks,
+                                step,
+                                resume_from,
+                                concurrency,
+                                verify,
+                                skip_confirmation,
+                                continue_on_error,
+                                follow_head: !no_follow_head,
+                                check_chain,
+                                stats,
+                            };
+                            by_range(
+                                chain_store,
+                                block_provider.as_ref(),
+                                from,
+                                to,
+                                options,
+                                fix_options,
+                                &check_options,
+                                report_file,
+                                &logger,
+                            )
+                            .await
+                        }
+                    }
+                }
+                ExportBlock {
+                    chain_name,
+                    hash,
+                    source,
+                    out,
+                    provider_attempts,
+                    provider_retry_delay,
+                } => {
+                    use commands::check_blocks::{export_block, CheckOptions};
+                    let logger = ctx.logger.clone();
+                    let (chain_store, block_provider) =
+                        ctx.chain_store_and_provider(&chain_name).await?;
+                    let check_options = CheckOptions {
+                        with_receipts: false,
+                        ignore_fields: vec![],
+                        colorize: false,
+                        full_context: false,
+                        provider_attempts,
+                        provider_retry_delay,
+                    };
+                    export_block(
+                        &hash,
+                        source,
+                        &out,
+                        chain_store,
+                        block_provider.as_ref(),
+                        &check_options,
+                        &logger,
+                    )
+                    .await
+                }
+                Truncate {
+                    chain_name,
+                    from,
+                    to,
+                    force,
+                    json,
+                } => {
+                    use commands::check_blocks::{purge_range, truncate};
+                    let chain_store = ctx.chain_store(&chain_name)?;
+                    if from.is_none() && to.is_none() {
+                        truncate(chain_store, force, json)
+                    } else {
+                        purge_range(chain_store, from, to, force)
                     }
                 }
-                Truncate { chain_name, force } => {
-                    use commands::check_blocks::truncate;
+                PurgeRange {
+                    chain_name,
+                    from,
+                    to,
+                    force,
+                } => {
+                    use commands::check_blocks::purge_range;
+                    let chain_store = ctx.chain_store(&chain_name)?;
+                    purge_range(chain_store, from, to, force)
+                }
+                RepairChainHead {
+                    chain_name,
+                    dry_run,
+                } => {
+                    use commands::check_blocks::repair_chain_head;
                     let chain_store = ctx.chain_store(&chain_name)?;
-                    truncate(chain_store, force)
+                    repair_chain_head(chain_store, dry_run).await
                 }
             }
         }
@@ -1009,3 +1444,7 @@ async fn main() -> anyhow::Result<()> {
 fn parse_duration_in_secs(s: &str) -> Result<Duration, ParseIntError> {
     Ok(Duration::from_secs(s.parse()?))
 }
+
+fn parse_duration_in_millis(s: &str) -> Result<Duration, ParseIntError> {
+    Ok(Duration::from_millis(s.parse()?))
+}
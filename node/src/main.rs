@@ -345,7 +345,9 @@ async fn main() {
             blockchain_map.clone(),
             graphql_runner.clone(),
             network_store.clone(),
+            subscription_manager.clone(),
             link_resolver.clone(),
+            metrics_registry.clone(),
         );
 
         if !opt.disable_block_ingestor {
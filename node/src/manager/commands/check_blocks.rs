@@ -1,93 +1,1256 @@
 use graph::{
     anyhow::{bail, ensure},
-    components::store::ChainStore as ChainStoreTrait,
+    blockchain::BlockPtr,
+    cheap_clone::CheapClone,
+    components::store::{BlockStore as _, ChainStore as ChainStoreTrait},
     prelude::{
         anyhow::{self, anyhow, Context},
+        serde_json::{self, Value},
         web3::types::H256,
     },
     slog::Logger,
 };
-use graph_chain_ethereum::{EthereumAdapter, EthereumAdapterTrait};
-use graph_store_postgres::ChainStore;
-use std::sync::Arc;
+use graph_store_postgres::{BlockCacheLock, BlockStore, ChainStore};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+pub use provider::{BlockProvider, FirehoseProvider, JsonRpcProvider};
+pub use ranges::BlockList;
+
+/// What to do with cached blocks that diverge from the provider
+#[derive(Clone, Copy)]
+pub struct FixOptions {
+    /// Only report diverging blocks, never delete them from the cache
+    pub dry_run: bool,
+    /// Write the block from the provider to the cache after deleting the cached block
+    pub reinsert: bool,
+    /// Change the cache even if another run holds the lock for the chain
+    pub force: bool,
+}
+
+/// What to compare besides the block itself, and how to fetch blocks from the provider
+#[derive(Clone)]
+pub struct CheckOptions {
+    /// Also compare the transaction receipts of the block
+    pub with_receipts: bool,
+    /// Fields of blocks that are left out when comparing them because they can legitimately
+    /// change after a block was cached, e.g. `totalDifficulty`
+    pub ignore_fields: Vec<String>,
+    /// How many times to try fetching a block from the provider before giving up
+    pub provider_attempts: usize,
+    /// How long to wait before fetching a block again after the first failed attempt; the
+    /// delay doubles with every further attempt
+    pub provider_retry_delay: Duration,
+    /// Color diffs with ANSI escape codes
+    pub colorize: bool,
+    /// Also show the top-level fields that are the same in diffs, not just the ones that differ
+    pub full_context: bool,
+}
+
+/// How a cached block differs from the same block from the provider, with a rendered diff for
+/// each part of the block that differs
+struct Divergence {
+    body: Option<String>,
+    receipts: Option<String>,
+}
+
+impl Divergence {
+    fn is_empty(&self) -> bool {
+        self.body.is_none() && self.receipts.is_none()
+    }
+}
+
+/// Finds the chain store for the chain named `chain_name` in `block_store`, so that the commands
+/// in this module can be pointed at any chain of a node by its name from the configuration.
+/// Errors with the names of the known chains if there is no such chain.
+pub fn resolve_chain_store(
+    block_store: &BlockStore,
+    chain_name: &str,
+) -> anyhow::Result<Arc<ChainStore>> {
+    match block_store.chain_store(chain_name) {
+        Some(chain_store) => Ok(chain_store),
+        None => {
+            let known = block_store.chain_names()?;
+            if known.is_empty() {
+                bail!("Could not find a chain named '{chain_name}', there are no chains")
+            }
+            bail!(
+                "Could not find a chain named '{chain_name}', the known chains are: {}",
+                known.join(", ")
+            )
+        }
+    }
+}
+
+/// Takes the lock that keeps two runs from changing the block cache of the same chain at the
+/// same time. Dry runs don't change anything and therefore don't need it.
+fn lock_block_cache(
+    chain_store: &ChainStore,
+    fix_options: FixOptions,
+) -> anyhow::Result<Option<BlockCacheLock>> {
+    if fix_options.dry_run || fix_options.force {
+        return Ok(None);
+    }
+    match chain_store.lock_block_cache()? {
+        Some(lock) => Ok(Some(lock)),
+        None => bail!(
+            "Another run is changing the block cache of chain {} right now. \
+             Use --force to change it anyway",
+            chain_store.chain
+        ),
+    }
+}
 
 pub async fn by_hash(
     hash: &str,
+    ancestors: usize,
     chain_store: Arc<ChainStore>,
-    ethereum_adapter: &EthereumAdapter,
+    block_provider: &dyn BlockProvider,
+    fix_options: FixOptions,
+    check_options: &CheckOptions,
+    report_file: Option<PathBuf>,
     logger: &Logger,
 ) -> anyhow::Result<()> {
     let block_hash = helpers::parse_block_hash(hash)?;
-    run(&block_hash, &chain_store, ethereum_adapter, logger).await
+    run(
+        &block_hash,
+        ancestors,
+        &chain_store,
+        block_provider,
+        fix_options,
+        check_options,
+        report_file,
+        logger,
+    )
+    .await
 }
 
 pub async fn by_number(
     number: i32,
+    ancestors: usize,
+    chain_store: Arc<ChainStore>,
+    block_provider: &dyn BlockProvider,
+    fix_options: FixOptions,
+    check_options: &CheckOptions,
+    report_file: Option<PathBuf>,
+    logger: &Logger,
+) -> anyhow::Result<()> {
+    let block_hash = steps::resolve_block_hash_from_block_number(number, &chain_store)?;
+    run(
+        &block_hash,
+        ancestors,
+        &chain_store,
+        block_provider,
+        fix_options,
+        check_options,
+        report_file,
+        logger,
+    )
+    .await
+}
+
+pub async fn by_timestamp(
+    timestamp: u64,
     chain_store: Arc<ChainStore>,
-    ethereum_adapter: &EthereumAdapter,
+    block_provider: &dyn BlockProvider,
+    fix_options: FixOptions,
+    check_options: &CheckOptions,
+    report_file: Option<PathBuf>,
     logger: &Logger,
 ) -> anyhow::Result<()> {
+    let number = steps::resolve_block_number_from_timestamp(timestamp, &chain_store)?;
+    println!("Block {number} is the first cached block with a timestamp at or after {timestamp}");
     let block_hash = steps::resolve_block_hash_from_block_number(number, &chain_store)?;
-    run(&block_hash, &chain_store, ethereum_adapter, logger).await
+    run(
+        &block_hash,
+        0,
+        &chain_store,
+        block_provider,
+        fix_options,
+        check_options,
+        report_file,
+        logger,
+    )
+    .await
+}
+
+/// Which versions of a block `export_block` writes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockSource {
+    Cache,
+    Provider,
+    Both,
+}
+
+impl FromStr for BlockSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "cache" => Ok(BlockSource::Cache),
+            "provider" => Ok(BlockSource::Provider),
+            "both" => Ok(BlockSource::Both),
+            _ => bail!(
+                "Unknown block source `{}`, expected `cache`, `provider` or `both`",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for BlockSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockSource::Cache => write!(f, "cache"),
+            BlockSource::Provider => write!(f, "provider"),
+            BlockSource::Both => write!(f, "both"),
+        }
+    }
+}
+
+/// Writes the cached block with `hash`, the same block from the provider, or both, to
+/// `<out>/<hash>.cache.json` and `<out>/<hash>.provider.json`, so that they can be inspected
+/// offline, e.g., after a check reported that they diverge. The block must be in the cache
+/// either way since some providers can only fetch blocks by number.
+pub async fn export_block(
+    hash: &str,
+    source: BlockSource,
+    out: &Path,
+    chain_store: Arc<ChainStore>,
+    block_provider: &dyn BlockProvider,
+    check_options: &CheckOptions,
+    logger: &Logger,
+) -> anyhow::Result<()> {
+    let block_hash = helpers::parse_block_hash(hash)?;
+    let cached_block = steps::fetch_single_cached_block(block_hash, &chain_store)?;
+    let number = helpers::block_number(&cached_block)?;
+    fs::create_dir_all(out)
+        .with_context(|| format!("Failed to create directory {}", out.display()))?;
+
+    if source != BlockSource::Provider {
+        helpers::write_block(out, &block_hash, BlockSource::Cache, &cached_block)?;
+    }
+    if source != BlockSource::Cache {
+        let provider_block = steps::fetch_single_provider_block(
+            &block_hash,
+            number,
+            block_provider,
+            check_options,
+            logger,
+        )
+        .await?;
+        helpers::write_block(out, &block_hash, BlockSource::Provider, &provider_block)?;
+    }
+    Ok(())
+}
+
+/// Options that control how `by_range` goes through a range of blocks
+pub struct RangeOptions {
+    /// Check exactly these blocks instead of a range
+    pub blocks: Option<BlockList>,
+    /// Only check every `step`th block of the range
+    pub step: Option<usize>,
+    /// Start at this block instead of the beginning of the range
+    pub resume_from: Option<i32>,
+    /// How many blocks to fetch from the provider at the same time
+    pub concurrency: usize,
+    /// Only report which blocks diverge, without printing diffs or deleting anything
+    pub verify: bool,
+    /// Don't ask before deleting the first diverging block
+    pub skip_confirmation: bool,
+    /// Report errors for individual blocks and move on to the next block instead of stopping
+    pub continue_on_error: bool,
+    /// For ranges without an upper bound, also check the blocks that the chain head moves past
+    /// while we go through the range
+    pub follow_head: bool,
+    /// Only check that every cached block in the range is the parent of the cached block after
+    /// it, without asking the provider about anything
+    pub check_chain: bool,
+    /// Only count how many blocks are in the cache and how many of those diverge, without
+    /// changing anything
+    pub stats: bool,
+}
+
+/// How many blocks we handle before we look at the chain head again when we follow it
+const FOLLOW_HEAD_INTERVAL: usize = 1000;
+
+/// Keeps track of the chain head for a range without an upper bound
+struct HeadFollower {
+    lower_bound: i32,
+    step: usize,
+    head: i32,
+}
+
+impl HeadFollower {
+    /// Returns the blocks of the range that the chain head moved past since we last looked at
+    /// it
+    fn new_blocks(&mut self, chain_store: &ChainStore) -> anyhow::Result<Vec<i32>> {
+        let head = steps::find_chain_head(chain_store)?;
+        Ok(self.advance(head))
+    }
+
+    /// Returns the blocks of the range up to `head` that come after the previous head
+    fn advance(&mut self, head: i32) -> Vec<i32> {
+        if head <= self.head {
+            return vec![];
+        }
+        let previous = self.head;
+        self.head = head;
+        // The first block of the range after `previous`, without going through the range from
+        // its start
+        let step = self.step as i32;
+        let first = if previous < self.lower_bound {
+            self.lower_bound
+        } else {
+            self.lower_bound + (previous + 1 - self.lower_bound + step - 1) / step * step
+        };
+        (first..=head).step_by(self.step).collect()
+    }
 }
 
 pub async fn by_range(
     chain_store: Arc<ChainStore>,
-    ethereum_adapter: &EthereumAdapter,
+    block_provider: &dyn BlockProvider,
+    range_from: Option<i32>,
+    range_to: Option<i32>,
+    options: RangeOptions,
+    fix_options: FixOptions,
+    check_options: &CheckOptions,
+    report_file: Option<PathBuf>,
+    logger: &Logger,
+) -> anyhow::Result<()> {
+    if options.check_chain {
+        ensure!(
+            options.blocks.is_none() && options.step.is_none(),
+            "Checking the chain needs consecutive blocks and can't be combined with a list of \
+             blocks or a step"
+        );
+        return check_chain(
+            &chain_store,
+            range_from,
+            range_to,
+            options.continue_on_error,
+        );
+    }
+    if options.stats {
+        return cache_stats(
+            &chain_store,
+            block_provider,
+            range_from,
+            range_to,
+            options,
+            check_options,
+            logger,
+        )
+        .await;
+    }
+    let mut deleted = Vec::new();
+    let result = check_range(
+        &chain_store,
+        block_provider,
+        range_from,
+        range_to,
+        options,
+        fix_options,
+        check_options,
+        &mut deleted,
+        logger,
+    )
+    .await;
+    // Blocks that were deleted before an error stopped the run belong in the report, too
+    if let Some(report_file) = report_file {
+        report::write(&report_file, &deleted)?;
+    }
+    result
+}
+
+/// Checks and fixes the blocks of a range, and adds each block that gets deleted from the cache
+/// to `deleted`
+async fn check_range(
+    chain_store: &ChainStore,
+    block_provider: &dyn BlockProvider,
     range_from: Option<i32>,
     range_to: Option<i32>,
+    options: RangeOptions,
+    fix_options: FixOptions,
+    check_options: &CheckOptions,
+    deleted: &mut Vec<(i32, H256)>,
     logger: &Logger,
 ) -> anyhow::Result<()> {
-    // Resolve a range of block numbers into a collection of blocks hashes
-    let range = ranges::Range::new(range_from, range_to)?;
+    let RangeOptions {
+        blocks,
+        step,
+        resume_from,
+        concurrency,
+        verify,
+        skip_confirmation,
+        continue_on_error,
+        follow_head,
+        check_chain: _,
+        stats: _,
+    } = options;
+    ensure!(concurrency > 0, "Concurrency must be at least 1");
+    // Verification never changes the cache
+    let _lock = if verify {
+        None
+    } else {
+        lock_block_cache(chain_store, fix_options)?
+    };
+
+    // An explicit list of blocks is short enough to simply be checked again if a run gets
+    // interrupted, and we only keep track of our progress through ranges
+    let (mut block_numbers, progress, mut follower) = match blocks {
+        Some(blocks) => (blocks.into_numbers(), None, None),
+        None => {
+            // Resolve a range of block numbers into a collection of blocks hashes
+            let range = ranges::Range::new(range_from, range_to, step)?;
+            let max = match range.upper_bound {
+                // When we have an open upper bound, we use the chain head's block number
+                None => steps::find_chain_head(chain_store)?,
+                Some(x) => x,
+            };
+
+            let state_file = tracks_progress(verify, fix_options)
+                .then(|| resume::StateFile::for_chain(&chain_store.chain));
+            let start = start_block(&range, max, resume_from, state_file.as_ref())?;
+            // Only blocks that are a whole number of steps from the start of the range are
+            // checked, even when we resume in the middle of it
+            let block_numbers = (range.lower_bound..=max)
+                .step_by(range.step)
+                .skip_while(|block_number| *block_number < start)
+                .collect::<Vec<_>>();
+            let follower = (follow_head && range.upper_bound.is_none()).then(|| HeadFollower {
+                lower_bound: range.lower_bound,
+                step: range.step,
+                head: max,
+            });
+            let progress = state_file.map(|state_file| (range, state_file));
+            (block_numbers, progress, follower)
+        }
+    };
+
+    // Blocks are fetched from the provider a chunk at a time, which saves a
+    // round trip per block. The blocks in a chunk are then compared and
+    // handled in order and one at a time, so that deletions never race with
+    // each other and we always know how far we got
+    let started = Instant::now();
+    let mut progress_line = progress_line::ProgressLine::new(started);
+    let mut summary = Summary::default();
+    // Deleting blocks can't be undone, and we ask once before the first deletion
+    let mut confirmed = fix_options.dry_run || skip_confirmation;
+    let mut handled = 0;
+    let mut handled_at_head_check = 0;
+    let mut next = 0;
+    loop {
+        // Look at the chain head when we run out of blocks, and every so often before that so
+        // that new blocks are checked while they are still close to the head
+        if let Some(follower) = &mut follower {
+            if next == block_numbers.len()
+                || handled - handled_at_head_check >= FOLLOW_HEAD_INTERVAL
+            {
+                handled_at_head_check = handled;
+                let new_blocks = follower.new_blocks(chain_store)?;
+                if let Some(&last) = new_blocks.last() {
+                    println!(
+                        "The chain head advanced to block {}, checking blocks up to {last}",
+                        follower.head
+                    );
+                }
+                block_numbers.extend(new_blocks);
+            }
+        }
+        if next == block_numbers.len() {
+            break;
+        }
+        let end = (next + concurrency).min(block_numbers.len());
+        let block_hashes = block_numbers[next..end]
+            .iter()
+            .map(|&block_number| {
+                let block_hash =
+                    steps::find_block_hash_from_block_number(block_number, chain_store);
+                (block_number, block_hash)
+            })
+            .collect::<Vec<_>>();
+        next = end;
+        let hashes: Vec<(i32, H256)> = block_hashes
+            .iter()
+            .filter_map(|(number, hash)| match hash {
+                Ok(Some(hash)) => Some((*number, *hash)),
+                _ => None,
+            })
+            .collect();
+        let mut provider_blocks =
+            steps::fetch_provider_blocks(&hashes, block_provider, check_options, logger).await;
+
+        for (block_number, block_hash) in block_hashes {
+            handled += 1;
+            progress_line.update(handled, block_numbers.len(), block_number);
+            let block_hash = match summary.skip_missing(block_number, block_hash, continue_on_error)
+            {
+                Some(block_hash) => block_hash,
+                None => continue,
+            };
+            let compared = block_hash.and_then(|block_hash| {
+                let cached_block = steps::fetch_single_cached_block(block_hash, chain_store)?;
+                let provider_block = provider_blocks
+                    .remove(&block_hash)
+                    .unwrap_or_else(|| Err(anyhow!("provider found no block {block_hash}")))?;
+                let body = steps::diff_block_pair(&cached_block, &provider_block, check_options);
+                Ok((block_hash, provider_block, body))
+            });
+            let (block_hash, provider_block, body) =
+                match summary.tolerate(block_number, compared, continue_on_error)? {
+                    Some(compared) => compared,
+                    None => continue,
+                };
+            let receipts = if check_options.with_receipts {
+                let receipts = steps::diff_receipts(
+                    &block_hash,
+                    &provider_block,
+                    chain_store,
+                    block_provider,
+                    check_options,
+                    logger,
+                )
+                .await;
+                match summary.tolerate(block_number, receipts, continue_on_error)? {
+                    Some(receipts) => receipts,
+                    None => continue,
+                }
+            } else {
+                None
+            };
+            let divergence = Divergence { body, receipts };
+            summary.checked += 1;
+            if !divergence.is_empty() {
+                summary.diverging.push(block_number);
+            }
+            if verify {
+                continue;
+            }
+            if !divergence.is_empty() && !confirmed {
+                let unchecked = block_numbers.len() - handled;
+                let message = format!(
+                    "Block {block_number} diverges and will be deleted from the cache, as will \
+                     any of the {unchecked} blocks after it that diverge."
+                );
+                if !helpers::prompt_for_confirmation(&message)? {
+                    println!("Aborting.");
+                    return Ok(());
+                }
+                confirmed = true;
+            }
+            let fixed = fix(
+                &block_hash,
+                &divergence,
+                &provider_block,
+                chain_store,
+                fix_options,
+            )
+            .await;
+            match summary.tolerate(block_number, fixed, continue_on_error)? {
+                Some(true) => {
+                    summary.deleted += 1;
+                    deleted.push((block_number, block_hash));
+                }
+                Some(false) => {}
+                None => continue,
+            }
+            if let Some((range, state_file)) = &progress {
+                state_file.save(range, block_number)?;
+            }
+        }
+    }
+    progress_line.finish(handled, block_numbers.len());
+    if let Some((_, state_file)) = &progress {
+        state_file.remove()?;
+    }
+    if fix_options.dry_run {
+        println!("Dry run: no blocks were deleted from cache.");
+    }
+    summary.print(started.elapsed());
+    // Fail when blocks diverge, even if they were fixed, so that scheduled
+    // runs make it obvious that the cache had bad blocks
+    ensure!(
+        summary.diverging.is_empty(),
+        "Found {} diverging blocks",
+        summary.diverging.len()
+    );
+    ensure!(
+        summary.failed.is_empty(),
+        "Failed to check {} blocks",
+        summary.failed.len()
+    );
+    Ok(())
+}
+
+/// Whether a `by_range` run keeps track of how far it got so that it can be resumed. Only runs
+/// that change the cache do; verifying and dry runs never change anything and always cover the
+/// whole range, and they must not touch the progress of an interrupted run that does.
+fn tracks_progress(verify: bool, fix_options: FixOptions) -> bool {
+    !verify && !fix_options.dry_run
+}
+
+/// The first block of `range` that a run checks: `resume_from` if we were told explicitly where
+/// to start, or else the block after the one where an earlier, interrupted run over the same
+/// range left off according to `state_file`
+fn start_block(
+    range: &ranges::Range,
+    max: i32,
+    resume_from: Option<i32>,
+    state_file: Option<&resume::StateFile>,
+) -> anyhow::Result<i32> {
+    if let Some(block_number) = resume_from {
+        ensure!(
+            (range.lower_bound..=max).contains(&block_number),
+            "Can't resume from block {}, it is outside of the range [{}, {}]",
+            block_number,
+            range.lower_bound,
+            max
+        );
+        return Ok(block_number);
+    }
+    match state_file
+        .map(|state_file| state_file.load(range))
+        .transpose()?
+    {
+        Some(Some(last_completed)) => {
+            println!("Resuming after block {last_completed}, where an earlier run stopped");
+            Ok(last_completed + 1)
+        }
+        Some(None) | None => Ok(range.lower_bound),
+    }
+}
+
+/// Checks that the `parentHash` of every cached block in the range is the hash of the cached
+/// block before it, and reports every block where that linkage is broken, e.g., because a reorg
+/// was only partially applied to the cache. Body diffs against the provider can't catch that
+/// when each block on its own is fine.
+fn check_chain(
+    chain_store: &ChainStore,
+    range_from: Option<i32>,
+    range_to: Option<i32>,
+    continue_on_error: bool,
+) -> anyhow::Result<()> {
+    let range = ranges::Range::new(range_from, range_to, None)?;
     let max = match range.upper_bound {
-        // When we have an open upper bound, we use the chain head's block number
-        None => steps::find_chain_head(&chain_store)?,
+        None => steps::find_chain_head(chain_store)?,
         Some(x) => x,
     };
-    // FIXME: This performs poorly.
-    // TODO: This could be turned into async code
+
+    let started = Instant::now();
+    let mut summary = Summary::default();
+    let mut breaks = Vec::new();
+    // The hash of the cached block before the current one, unless we couldn't find it
+    let mut parent: Option<H256> = None;
     for block_number in range.lower_bound..=max {
-        println!("Fixing block [{block_number}/{max}]");
-        let block_hash = steps::resolve_block_hash_from_block_number(block_number, &chain_store)?;
-        run(&block_hash, &chain_store, ethereum_adapter, logger).await?
+        let block_hash = steps::find_block_hash_from_block_number(block_number, chain_store);
+        let block_hash = match summary.skip_missing(block_number, block_hash, continue_on_error) {
+            Some(block_hash) => block_hash,
+            None => {
+                parent = None;
+                continue;
+            }
+        };
+        let linkage = block_hash.and_then(|block_hash| {
+            let cached_block = steps::fetch_single_cached_block(block_hash, chain_store)?;
+            Ok((block_hash, helpers::parent_hash(&cached_block)?))
+        });
+        let (block_hash, parent_hash) =
+            match summary.tolerate(block_number, linkage, continue_on_error)? {
+                Some(linkage) => linkage,
+                None => {
+                    parent = None;
+                    continue;
+                }
+            };
+        summary.checked += 1;
+        if let Some(expected) = parent {
+            if parent_hash != expected {
+                println!(
+                    "Block {block_number} ({block_hash}) has parent hash {parent_hash}, but the \
+                     cached block {} is {expected}",
+                    block_number - 1
+                );
+                breaks.push(block_number);
+            }
+        }
+        parent = Some(block_hash);
+    }
+
+    println!(
+        "Checked the linkage of {} blocks in {:?}",
+        summary.checked,
+        started.elapsed()
+    );
+    println!(
+        "{} blocks don't link to their parent: {breaks:?}",
+        breaks.len()
+    );
+    if !summary.skipped.is_empty() {
+        println!(
+            "{} blocks are not in the cache and were skipped: {:?}",
+            summary.skipped.len(),
+            summary.skipped
+        );
+    }
+    if !summary.failed.is_empty() {
+        println!(
+            "{} blocks failed: {:?}",
+            summary.failed.len(),
+            summary.failed
+        );
     }
+    ensure!(
+        breaks.is_empty(),
+        "Found {} blocks that don't link to their parent",
+        breaks.len()
+    );
+    ensure!(
+        summary.failed.is_empty(),
+        "Failed to check {} blocks",
+        summary.failed.len()
+    );
     Ok(())
 }
 
-pub fn truncate(chain_store: Arc<ChainStore>, skip_confirmation: bool) -> anyhow::Result<()> {
-    if !skip_confirmation && !helpers::prompt_for_confirmation()? {
-        println!("Aborting.");
-        return Ok(());
+/// Counts how many blocks of the range are in the cache, and how many of those diverge from the
+/// provider, to tell whether the cache gets populated properly. Nothing in the cache is changed,
+/// and diverging blocks don't make this fail.
+async fn cache_stats(
+    chain_store: &ChainStore,
+    block_provider: &dyn BlockProvider,
+    range_from: Option<i32>,
+    range_to: Option<i32>,
+    options: RangeOptions,
+    check_options: &CheckOptions,
+    logger: &Logger,
+) -> anyhow::Result<()> {
+    let RangeOptions {
+        blocks,
+        step,
+        concurrency,
+        continue_on_error,
+        ..
+    } = options;
+    ensure!(concurrency > 0, "Concurrency must be at least 1");
+    let block_numbers = match blocks {
+        Some(blocks) => blocks.into_numbers(),
+        None => {
+            let range = ranges::Range::new(range_from, range_to, step)?;
+            let max = match range.upper_bound {
+                None => steps::find_chain_head(chain_store)?,
+                Some(x) => x,
+            };
+            (range.lower_bound..=max)
+                .step_by(range.step)
+                .collect::<Vec<_>>()
+        }
+    };
+
+    let started = Instant::now();
+    let mut progress_line = progress_line::ProgressLine::new(started);
+    // `checked` counts the blocks that are in the cache, and `skipped` the ones that are not
+    let mut summary = Summary::default();
+    let mut handled = 0;
+    for chunk in block_numbers.chunks(concurrency) {
+        let mut cached_blocks = Vec::new();
+        for &block_number in chunk {
+            // A block whose hash we know but whose data is gone is missing, too
+            let cached = steps::find_block_hash_from_block_number(block_number, chain_store)
+                .and_then(|block_hash| match block_hash {
+                    Some(block_hash) => Ok(chain_store
+                        .blocks(&[block_hash.into()])?
+                        .into_iter()
+                        .next()
+                        .map(|block| (block_hash, block))),
+                    None => Ok(None),
+                });
+            match summary.tolerate(block_number, cached, continue_on_error)? {
+                Some(Some((block_hash, block))) => {
+                    cached_blocks.push((block_number, block_hash, block))
+                }
+                Some(None) => summary.skipped.push(block_number),
+                None => {}
+            }
+        }
+        summary.checked += cached_blocks.len();
+
+        let hashes: Vec<(i32, H256)> = cached_blocks
+            .iter()
+            .map(|(block_number, block_hash, _)| (*block_number, *block_hash))
+            .collect();
+        let mut provider_blocks =
+            steps::fetch_provider_blocks(&hashes, block_provider, check_options, logger).await;
+        for (block_number, block_hash, cached_block) in cached_blocks {
+            let provider_block = provider_blocks
+                .remove(&block_hash)
+                .unwrap_or_else(|| Err(anyhow!("provider found no block {block_hash}")));
+            if let Some(provider_block) =
+                summary.tolerate(block_number, provider_block, continue_on_error)?
+            {
+                if steps::diff_block_pair(&cached_block, &provider_block, check_options).is_some() {
+                    summary.diverging.push(block_number);
+                }
+            }
+        }
+
+        handled += chunk.len();
+        if let Some(&block_number) = chunk.last() {
+            progress_line.update(handled, block_numbers.len(), block_number);
+        }
+    }
+    progress_line.finish(handled, block_numbers.len());
+
+    let total = block_numbers.len();
+    let present = summary.checked;
+    let diverged = summary.diverging.len();
+    let share = if present == 0 {
+        0.0
+    } else {
+        100.0 * diverged as f64 / present as f64
+    };
+    println!(
+        "Block cache of chain {} ({:?}):",
+        chain_store.chain,
+        started.elapsed()
+    );
+    println!("  present:  {present}/{total}");
+    println!("  missing:  {}", summary.skipped.len());
+    println!("  diverged: {diverged} ({share:.1}% of present blocks)");
+    if !summary.failed.is_empty() {
+        println!(
+            "{} blocks failed: {:?}",
+            summary.failed.len(),
+            summary.failed
+        );
+    }
+    ensure!(
+        summary.failed.is_empty(),
+        "Failed to check {} blocks",
+        summary.failed.len()
+    );
+    Ok(())
+}
+
+/// What a `by_range` run found and did
+#[derive(Default)]
+struct Summary {
+    checked: usize,
+    diverging: Vec<i32>,
+    deleted: usize,
+    /// The blocks that could not be checked or fixed because of an error
+    failed: Vec<i32>,
+    /// The blocks that are not in the cache and were therefore not checked
+    skipped: Vec<i32>,
+}
+
+impl Summary {
+    /// Passes errors for `block_number` on, unless we `continue_on_error`; the error is then
+    /// reported, the block recorded as failed, and `None` returned.
+    fn tolerate<T>(
+        &mut self,
+        block_number: i32,
+        result: anyhow::Result<T>,
+        continue_on_error: bool,
+    ) -> anyhow::Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if continue_on_error => {
+                eprintln!("Failed to check block {block_number}: {e:#}");
+                self.failed.push(block_number);
+                Ok(None)
+            }
+            Err(e) => Err(e.context(format!("Failed to check block {block_number}"))),
+        }
+    }
+
+    /// Turns a block that is not in the cache into an error, unless we `continue_on_error`; gaps
+    /// in the cache are common enough that the block is then only reported and recorded as
+    /// skipped, and `None` returned.
+    fn skip_missing(
+        &mut self,
+        block_number: i32,
+        block_hash: anyhow::Result<Option<H256>>,
+        continue_on_error: bool,
+    ) -> Option<anyhow::Result<H256>> {
+        match block_hash {
+            Ok(Some(block_hash)) => Some(Ok(block_hash)),
+            Ok(None) if continue_on_error => {
+                eprintln!("Block {block_number} is not in the cache, skipping it");
+                self.skipped.push(block_number);
+                None
+            }
+            Ok(None) => Some(Err(anyhow!(
+                "Failed to locate block number {block_number} in store"
+            ))),
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn print(&self, elapsed: Duration) {
+        let Summary {
+            checked,
+            diverging,
+            deleted,
+            failed,
+            skipped,
+        } = self;
+        println!("Checked {checked} blocks in {elapsed:?}");
+        println!("{} blocks diverge: {diverging:?}", diverging.len());
+        println!("{deleted} blocks were deleted from the cache");
+        if !skipped.is_empty() {
+            println!(
+                "{} blocks are not in the cache and were skipped: {skipped:?}",
+                skipped.len()
+            );
+        }
+        if !failed.is_empty() {
+            println!("{} blocks failed: {failed:?}", failed.len());
+        }
+    }
+}
+
+/// Deletes all cached blocks. With `json`, the outcome is printed as a JSON object
+/// `{"chain": .., "truncated": true, "rows_deleted": ..}` for use in scripts.
+pub fn truncate(
+    chain_store: Arc<ChainStore>,
+    skip_confirmation: bool,
+    json: bool,
+) -> anyhow::Result<()> {
+    let rows_deleted = match delete_cached_blocks(&chain_store, None, skip_confirmation)? {
+        Some(rows_deleted) => rows_deleted,
+        None => return Ok(()),
+    };
+    if json {
+        let summary = serde_json::json!({
+            "chain": chain_store.chain,
+            "truncated": true,
+            "rows_deleted": rows_deleted,
+        });
+        println!("{summary}");
+    } else {
+        println!(
+            "Deleted {rows_deleted} cached blocks for {}",
+            chain_store.chain
+        );
     }
+    Ok(())
+}
 
+/// Points the chain head at the highest cached block whose parent is also cached, e.g., after
+/// deleting blocks near the head left it pointing at a block that is gone. Ingestion then picks
+/// up from there. With `dry_run`, only reports which block that is.
+pub async fn repair_chain_head(chain_store: Arc<ChainStore>, dry_run: bool) -> anyhow::Result<()> {
+    let _lock = lock_block_cache(
+        &chain_store,
+        FixOptions {
+            dry_run,
+            reinsert: false,
+            force: false,
+        },
+    )?;
+    let show = |ptr: Option<&BlockPtr>| match ptr {
+        Some(ptr) => format!("block {} ({})", ptr.number, ptr.hash_hex()),
+        None => "none".to_string(),
+    };
+
+    let before = chain_store.cheap_clone().chain_head_ptr().await?;
+    let after = steps::find_linked_head(&chain_store)?;
+    println!(
+        "Chain head of {}: {}",
+        chain_store.chain,
+        show(before.as_ref())
+    );
+    if before == after {
+        println!("The chain head is the highest well-linked cached block, nothing to repair.");
+        return Ok(());
+    }
+    let after = match after {
+        Some(after) => after,
+        None => bail!(
+            "The block cache of {} has no well-linked block to point the chain head at",
+            chain_store.chain
+        ),
+    };
+    if dry_run {
+        println!(
+            "Dry run: the chain head would be set to {}",
+            show(Some(&after))
+        );
+        return Ok(());
+    }
     chain_store
-        .truncate_block_cache()
-        .with_context(|| format!("Failed to truncate block cache for {}", chain_store.chain))
+        .set_chain_head_ptr(&after)
+        .with_context(|| format!("Failed to set the chain head of {}", chain_store.chain))?;
+    println!("Chain head set to {}", show(Some(&after)));
+    Ok(())
+}
+
+/// The longest we wait between two attempts to fetch a block from the provider
+const PROVIDER_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// How many block numbers `purge_range` resolves and deletes at a time
+const PURGE_BATCH_SIZE: i32 = 1000;
+
+/// Deletes all cached blocks with numbers from `min` to `max` without fetching anything from a
+/// provider. An open upper bound extends the range to the chain head; the genesis block is
+/// never deleted.
+pub fn purge_range(
+    chain_store: Arc<ChainStore>,
+    min: Option<i32>,
+    max: Option<i32>,
+    skip_confirmation: bool,
+) -> anyhow::Result<()> {
+    let range = ranges::Range::new(min, max, None)?;
+    if let Some(deleted) = delete_cached_blocks(&chain_store, Some(range), skip_confirmation)? {
+        println!("Deleted {} cached blocks", deleted);
+    }
+    Ok(())
+}
+
+/// Deletes the cached blocks in `range`, or all of them without a range, and returns how many
+/// were deleted; `None` if the user aborted. This is where `truncate` and `purge_range` take
+/// the lock on the block cache, before asking for confirmation so that nobody confirms a
+/// deletion that can't happen. The blocks of a range are looked up in the cache and deleted
+/// `PURGE_BATCH_SIZE` block numbers at a time.
+fn delete_cached_blocks(
+    chain_store: &ChainStore,
+    range: Option<ranges::Range>,
+    skip_confirmation: bool,
+) -> anyhow::Result<Option<usize>> {
+    let _lock = lock_block_cache(
+        chain_store,
+        FixOptions {
+            dry_run: false,
+            reinsert: false,
+            force: false,
+        },
+    )?;
+    let range = match range {
+        Some(range) => {
+            let max = match range.upper_bound {
+                None => steps::find_chain_head(chain_store)?,
+                Some(x) => x,
+            };
+            Some((range.lower_bound, max))
+        }
+        None => None,
+    };
+    let message = match range {
+        Some((min, max)) => format!(
+            "This will delete all cached blocks from {} to {}.",
+            min, max
+        ),
+        None => "This will delete all cached blocks.".to_string(),
+    };
+    if !skip_confirmation && !helpers::prompt_for_confirmation(&message)? {
+        println!("Aborting.");
+        return Ok(None);
+    }
+
+    let (min, max) = match range {
+        Some(range) => range,
+        None => {
+            let deleted = chain_store.truncate_block_cache().with_context(|| {
+                format!("Failed to truncate block cache for {}", chain_store.chain)
+            })?;
+            return Ok(Some(deleted));
+        }
+    };
+    let mut deleted = 0;
+    let mut start = min;
+    while start <= max {
+        let end = max.min(start.saturating_add(PURGE_BATCH_SIZE - 1));
+        let mut hashes = Vec::new();
+        for number in start..=end {
+            for hash in chain_store.block_hashes_by_block_number(number)? {
+                hashes.push(H256::from_slice(hash.as_slice()));
+            }
+        }
+        let hashes: Vec<&H256> = hashes.iter().collect();
+        deleted += chain_store
+            .delete_blocks(&hashes)
+            .with_context(|| format!("Failed to delete cached blocks from {} to {}", start, end))?;
+        println!("Purged blocks {} to {}", start, end);
+        match end.checked_add(1) {
+            Some(next) => start = next,
+            None => break,
+        }
+    }
+    Ok(Some(deleted))
 }
 
+/// Checks and fixes the block with `block_hash`, and then up to `ancestors` of its ancestors.
+/// The ancestors are found through the parent hashes of the cached blocks, so that a chain of
+/// bad blocks that a reorg left behind gets fixed as a whole.
 async fn run(
     block_hash: &H256,
+    ancestors: usize,
     chain_store: &ChainStore,
-    ethereum_adapter: &EthereumAdapter,
+    block_provider: &dyn BlockProvider,
+    fix_options: FixOptions,
+    check_options: &CheckOptions,
+    report_file: Option<PathBuf>,
     logger: &Logger,
 ) -> anyhow::Result<()> {
-    let cached_block = steps::fetch_single_cached_block(*block_hash, &chain_store)?;
-    let provider_block =
-        steps::fetch_single_provider_block(&block_hash, ethereum_adapter, logger).await?;
-    let diff = steps::diff_block_pair(&cached_block, &provider_block);
-    steps::report_difference(diff.as_deref(), &block_hash);
-    if diff.is_some() {
-        steps::delete_block(&block_hash, &chain_store)?;
+    let _lock = lock_block_cache(chain_store, fix_options)?;
+    let mut deleted = Vec::new();
+    let result = check_ancestors(
+        block_hash,
+        ancestors,
+        chain_store,
+        block_provider,
+        fix_options,
+        check_options,
+        &mut deleted,
+        logger,
+    )
+    .await;
+    if let Some(report_file) = report_file {
+        report::write(&report_file, &deleted)?;
+    }
+    result
+}
+
+/// The loop behind `run`, which adds each block that gets deleted from the cache to `deleted`
+async fn check_ancestors(
+    block_hash: &H256,
+    ancestors: usize,
+    chain_store: &ChainStore,
+    block_provider: &dyn BlockProvider,
+    fix_options: FixOptions,
+    check_options: &CheckOptions,
+    deleted: &mut Vec<(i32, H256)>,
+    logger: &Logger,
+) -> anyhow::Result<()> {
+    let mut block_hash = *block_hash;
+    for depth in 0..=ancestors {
+        // Remember the parent before the block possibly gets deleted
+        let cached_block = steps::fetch_single_cached_block(block_hash, chain_store)?;
+        let number = helpers::block_number(&cached_block)?;
+        if depth > 0 {
+            println!("Checking ancestor {depth}: block {number} ({block_hash})");
+        }
+        let (divergence, provider_block) = check(
+            &block_hash,
+            chain_store,
+            block_provider,
+            check_options,
+            logger,
+        )
+        .await?;
+        let fixed = fix(
+            &block_hash,
+            &divergence,
+            &provider_block,
+            chain_store,
+            fix_options,
+        )
+        .await?;
+        if fixed {
+            deleted.push((number, block_hash));
+        }
+
+        if depth == ancestors {
+            break;
+        }
+        if number == 0 {
+            println!("Reached the genesis block, there are no more ancestors to check.");
+            break;
+        }
+        let parent_hash = helpers::parent_hash(&cached_block)?;
+        if chain_store.blocks(&[parent_hash.into()])?.is_empty() {
+            println!(
+                "The parent {parent_hash} of block {number} is not in the cache, \
+                 there are no more ancestors to check."
+            );
+            break;
+        }
+        block_hash = parent_hash;
     }
     Ok(())
 }
 
+/// Reports the difference for a block and deletes the block from the cache if it diverges from
+/// the provider, unless this is a dry run. If asked to, the block from the provider then takes
+/// the place of the deleted block. Returns whether the block was deleted.
+async fn fix(
+    block_hash: &H256,
+    divergence: &Divergence,
+    provider_block: &Value,
+    chain_store: &ChainStore,
+    fix_options: FixOptions,
+) -> anyhow::Result<bool> {
+    steps::report_difference(divergence, block_hash);
+    if divergence.is_empty() {
+        return Ok(false);
+    }
+    if fix_options.dry_run {
+        println!("Dry run: block {block_hash} was not deleted from cache.");
+        return Ok(false);
+    }
+    steps::delete_block(block_hash, chain_store)?;
+    if fix_options.reinsert {
+        steps::reinsert_block(block_hash, provider_block, chain_store).await?;
+    }
+    Ok(true)
+}
+
+/// Compares the cached block with the same block from the provider and returns how they
+/// differ together with the block from the provider.
+async fn check(
+    block_hash: &H256,
+    chain_store: &ChainStore,
+    block_provider: &dyn BlockProvider,
+    check_options: &CheckOptions,
+    logger: &Logger,
+) -> anyhow::Result<(Divergence, Value)> {
+    let cached_block = steps::fetch_single_cached_block(*block_hash, &chain_store)?;
+    let number = helpers::block_number(&cached_block)?;
+    let provider_block = steps::fetch_single_provider_block(
+        &block_hash,
+        number,
+        block_provider,
+        check_options,
+        logger,
+    )
+    .await?;
+    let body = steps::diff_block_pair(&cached_block, &provider_block, check_options);
+    let receipts = if check_options.with_receipts {
+        steps::diff_receipts(
+            block_hash,
+            &provider_block,
+            chain_store,
+            block_provider,
+            check_options,
+            logger,
+        )
+        .await?
+    } else {
+        None
+    };
+    Ok((Divergence { body, receipts }, provider_block))
+}
+
 mod steps {
     use super::*;
-    use futures::compat::Future01CompatExt;
-    use graph::prelude::serde_json::{self, Value};
+    use futures::future::join_all;
+    use graph::prelude::{
+        debug,
+        serde_json::{self, Value},
+        LightEthereumBlock,
+    };
+    use graph::util::backoff::ExponentialBackoff;
+    use graph_chain_ethereum::chain::BlockFinality;
     use json_structural_diff::{colorize as diff_to_string, JsonDiff};
+    use std::borrow::Cow;
 
     /// Queries the [`ChainStore`] about the block hash for the given block number.
     ///
@@ -96,10 +1259,98 @@ mod steps {
         number: i32,
         chain_store: &ChainStore,
     ) -> anyhow::Result<H256> {
+        find_block_hash_from_block_number(number, chain_store)?
+            .ok_or_else(|| anyhow!("Failed to locate block number {} in store", number))
+    }
+
+    /// Like [`resolve_block_hash_from_block_number`], but returns `None` if the store has no
+    /// block with the given number.
+    ///
+    /// Errors if the store has several blocks with that number.
+    pub(super) fn find_block_hash_from_block_number(
+        number: i32,
+        chain_store: &ChainStore,
+    ) -> anyhow::Result<Option<H256>> {
         let block_hashes = chain_store.block_hashes_by_block_number(number)?;
+        if block_hashes.is_empty() {
+            return Ok(None);
+        }
         let hash = helpers::get_single_item("block hash", block_hashes)
             .with_context(|| format!("Failed to locate block number {} in store", number))?;
-        Ok(H256(hash.as_slice().try_into()?))
+        Ok(Some(H256(hash.as_slice().try_into()?)))
+    }
+
+    /// Finds the first cached block whose timestamp is equal to or after `timestamp` with a
+    /// binary search over the cached blocks. The cache can have gaps, and blocks that are not
+    /// in it are never looked at.
+    ///
+    /// Errors if `timestamp` is before the first or after the last cached block.
+    pub(super) fn resolve_block_number_from_timestamp(
+        timestamp: u64,
+        chain_store: &ChainStore,
+    ) -> anyhow::Result<i32> {
+        let block_timestamp = |number: i32| -> anyhow::Result<u64> {
+            let block_hash = resolve_block_hash_from_block_number(number, chain_store)?;
+            let block = fetch_single_cached_block(block_hash, chain_store)?;
+            helpers::block_timestamp(&block)
+                .with_context(|| format!("Failed to read the timestamp of block {number}"))
+        };
+
+        let (first, last) = match (
+            chain_store.lowest_cached_block_number_from(0)?,
+            chain_store.highest_cached_block_number()?,
+        ) {
+            (Some(first), Some(last)) => (first, last),
+            _ => bail!("The block cache of {} is empty", chain_store.chain),
+        };
+        ensure!(
+            timestamp >= block_timestamp(first)?,
+            "Timestamp {} predates the first cached block {}",
+            timestamp,
+            first
+        );
+        ensure!(
+            timestamp <= block_timestamp(last)?,
+            "Timestamp {} is after the last cached block {}",
+            timestamp,
+            last
+        );
+
+        first_block_at_or_after(timestamp, first, last, |from, before| {
+            match chain_store.lowest_cached_block_number_from(from)? {
+                Some(number) if number < before => Ok(Some((number, block_timestamp(number)?))),
+                _ => Ok(None),
+            }
+        })
+    }
+
+    /// The binary search behind `resolve_block_number_from_timestamp`, for the first block from
+    /// `low` to `high` with a timestamp at or after `timestamp`, where block `high` has such a
+    /// timestamp. Not every block has to exist; `first_from(from, before)` returns the first
+    /// block from `from` up to but excluding `before` together with its timestamp, if there is
+    /// one.
+    pub(super) fn first_block_at_or_after(
+        timestamp: u64,
+        mut low: i32,
+        high: i32,
+        mut first_from: impl FnMut(i32, i32) -> anyhow::Result<Option<(i32, u64)>>,
+    ) -> anyhow::Result<i32> {
+        // `found` is the first block we know of with a timestamp at or after `timestamp`. Blocks
+        // before `low` have an earlier timestamp, and there are no blocks from `high` up to
+        // `found`
+        let (mut found, mut high) = (high, high);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match first_from(mid, high)? {
+                Some((number, block_timestamp)) if block_timestamp < timestamp => low = number + 1,
+                Some((number, _)) => {
+                    found = number;
+                    high = number;
+                }
+                None => high = mid,
+            }
+        }
+        Ok(found)
     }
 
     /// Queries the [`ChainStore`] for a cached block given a block hash.
@@ -117,54 +1368,190 @@ mod steps {
             .with_context(|| format!("Failed to locate block {} in store.", block_hash))
     }
 
-    /// Fetches a block from a JRPC endpoint.
+    /// Fetches block `number`, which should have `block_hash`, from the provider. Requests that
+    /// fail, e.g., because of a network hiccup, are retried with an exponential backoff, as
+    /// `check_options` says.
     ///
-    /// Errors on a non-unary result.
+    /// A provider that fetches blocks by number returns the block on the canonical chain, which
+    /// has a different hash than a cached block that is not on it; such a block diverges like
+    /// any other. Errors if the provider's block has a different number; retrying wouldn't
+    /// change that.
     pub(super) async fn fetch_single_provider_block(
         block_hash: &H256,
-        ethereum_adapter: &EthereumAdapter,
+        number: i32,
+        block_provider: &dyn BlockProvider,
+        check_options: &CheckOptions,
         logger: &Logger,
     ) -> anyhow::Result<Value> {
-        let provider_block = ethereum_adapter
-            .block_by_hash(&logger, *block_hash)
-            .compat()
-            .await
-            .with_context(|| format!("failed to fetch block {block_hash}"))?
-            .ok_or_else(|| anyhow!("JRPC provider found no block {block_hash}"))?;
+        let mut backoff =
+            ExponentialBackoff::new(check_options.provider_retry_delay, PROVIDER_RETRY_MAX_DELAY);
+        let provider_block = loop {
+            match block_provider.block(block_hash, number, logger).await {
+                Ok(block) => break block,
+                Err(e) if backoff.attempt + 1 < check_options.provider_attempts as u64 => {
+                    debug!(
+                        logger,
+                        "Trying to fetch block {} again after it failed (attempt #{})",
+                        block_hash,
+                        backoff.attempt + 1;
+                        "error" => format!("{e:#}")
+                    );
+                    backoff.sleep_async().await;
+                }
+                Err(e) => {
+                    return Err(e.context(format!("failed to fetch block {block_hash}")));
+                }
+            }
+        };
         ensure!(
-            provider_block.hash == Some(*block_hash),
-            "Provider responded with a different block hash"
+            provider_block.number.map(|number| number.as_u64()) == Some(number as u64),
+            "Provider responded with block {:?} instead of block {}",
+            provider_block.number,
+            number
         );
         serde_json::to_value(provider_block)
             .context("failed to parse provider block as a JSON value")
     }
 
-    /// Compares two [`serde_json::Value`] values.
+    /// Fetches a batch of blocks from the provider, issuing the requests for them
+    /// concurrently.
+    ///
+    /// Every block comes with its own result, so that one block that can't be fetched doesn't
+    /// keep us from looking at the others.
+    pub(super) async fn fetch_provider_blocks(
+        block_hashes: &[(i32, H256)],
+        block_provider: &dyn BlockProvider,
+        check_options: &CheckOptions,
+        logger: &Logger,
+    ) -> HashMap<H256, anyhow::Result<Value>> {
+        join_all(block_hashes.iter().map(|(number, block_hash)| async move {
+            let provider_block = fetch_single_provider_block(
+                block_hash,
+                *number,
+                block_provider,
+                check_options,
+                logger,
+            )
+            .await;
+            (*block_hash, provider_block)
+        }))
+        .await
+        .into_iter()
+        .collect()
+    }
+
+    /// Compares two [`serde_json::Value`] values, leaving out the top-level fields that
+    /// `check_options` says to ignore.
     ///
-    /// If they are different, returns a user-friendly string ready to be displayed.
-    pub(super) fn diff_block_pair(a: &Value, b: &Value) -> Option<String> {
+    /// If they are different, returns a user-friendly string ready to be displayed, with color
+    /// and context as `check_options` asks for.
+    pub(super) fn diff_block_pair(
+        a: &Value,
+        b: &Value,
+        check_options: &CheckOptions,
+    ) -> Option<String> {
+        let a = without_ignored_fields(a, &check_options.ignore_fields);
+        let b = without_ignored_fields(b, &check_options.ignore_fields);
+        let (a, b) = (a.as_ref(), b.as_ref());
         if a == b {
             None
         } else {
-            match JsonDiff::diff(a, &b, false).diff {
+            // The third argument of `JsonDiff::diff` makes it compare keys only, which would
+            // hide changed values
+            match JsonDiff::diff(a, b, false).diff {
                 // The diff could potentially be a `Value::Null`, which is equivalent to not being
                 // different at all.
                 None | Some(Value::Null) => None,
-                Some(diff) => {
+                Some(mut diff) => {
+                    if check_options.full_context {
+                        add_unchanged_fields(&mut diff, a, b);
+                    }
                     // Convert the JSON diff to a pretty-formatted text that will be displayed to
                     // the user
-                    Some(diff_to_string(&diff, false))
+                    Some(diff_to_string(&diff, check_options.colorize))
+                }
+            }
+        }
+    }
+
+    /// Adds the top-level fields that `a` and `b` have in common to `diff`, so that they are
+    /// rendered as unchanged context around the fields that differ.
+    fn add_unchanged_fields(diff: &mut Value, a: &Value, b: &Value) {
+        if let (Value::Object(diff), Value::Object(a), Value::Object(b)) = (diff, a, b) {
+            for (key, value) in b {
+                if a.get(key) == Some(value) {
+                    diff.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    /// Removes `ignore_fields` from `value` if it is an object; anything else is returned as is.
+    fn without_ignored_fields<'a>(value: &'a Value, ignore_fields: &[String]) -> Cow<'a, Value> {
+        match value {
+            Value::Object(object)
+                if ignore_fields.iter().any(|field| object.contains_key(field)) =>
+            {
+                let mut object = object.clone();
+                for field in ignore_fields {
+                    object.remove(field);
                 }
+                Cow::Owned(Value::Object(object))
             }
+            _ => Cow::Borrowed(value),
+        }
+    }
+
+    /// Compares the transaction receipts that are cached for a block with the receipts the
+    /// provider has for it.
+    ///
+    /// Blocks that were cached without their receipts have nothing to compare, and their
+    /// receipts are never reported as diverging.
+    pub(super) async fn diff_receipts(
+        block_hash: &H256,
+        provider_block: &Value,
+        chain_store: &ChainStore,
+        block_provider: &dyn BlockProvider,
+        check_options: &CheckOptions,
+        logger: &Logger,
+    ) -> anyhow::Result<Option<String>> {
+        let mut cached_receipts = chain_store
+            .transaction_receipts_in_block(block_hash)
+            .await?;
+        let block: LightEthereumBlock = serde_json::from_value(provider_block.clone())
+            .context("failed to parse provider block")?;
+        if cached_receipts.is_empty() && !block.transactions.is_empty() {
+            println!("Block {block_hash} has no cached transaction receipts to compare.");
+            return Ok(None);
         }
+        let mut provider_receipts = block_provider
+            .receipts(block, logger)
+            .await
+            .with_context(|| format!("failed to fetch transaction receipts for {block_hash}"))?;
+        cached_receipts.sort_by_key(|receipt| receipt.transaction_index);
+        provider_receipts.sort_by_key(|receipt| receipt.transaction_index);
+        if cached_receipts == provider_receipts {
+            return Ok(None);
+        }
+        Ok(diff_block_pair(
+            &serde_json::to_value(cached_receipts)?,
+            &serde_json::to_value(provider_receipts)?,
+            check_options,
+        ))
     }
 
-    /// Prints the difference between two [`serde_json::Value`] values to the user.
-    pub(super) fn report_difference(difference: Option<&str>, hash: &H256) {
-        if let Some(diff) = difference {
+    /// Prints the differences between a cached block and the block from the provider to the
+    /// user, keeping differences in the block itself apart from those in its receipts.
+    pub(super) fn report_difference(divergence: &Divergence, hash: &H256) {
+        if let Some(diff) = &divergence.body {
             eprintln!("block {hash} diverges from cache:");
             eprintln!("{diff}");
-        } else {
+        }
+        if let Some(diff) = &divergence.receipts {
+            eprintln!("transaction receipts of block {hash} diverge from cache:");
+            eprintln!("{diff}");
+        }
+        if divergence.is_empty() {
             println!("Cached block is equal to the same block from provider.")
         }
     }
@@ -177,6 +1564,60 @@ mod steps {
         Ok(())
     }
 
+    /// Writes a block that was fetched from the provider in place of the deleted block with
+    /// `block_hash` to the block cache. With a provider that fetches blocks by number, that is
+    /// the block on the canonical chain, which can have a different hash.
+    pub(super) async fn reinsert_block(
+        block_hash: &H256,
+        provider_block: &Value,
+        chain_store: &ChainStore,
+    ) -> anyhow::Result<()> {
+        let block: LightEthereumBlock = serde_json::from_value(provider_block.clone())
+            .context("failed to parse provider block")?;
+        let hash = block
+            .hash
+            .ok_or_else(|| anyhow!("Provider block for {block_hash} has no hash"))?;
+        println!("Reinserting block {hash:?} into cache.");
+        chain_store
+            .upsert_block(Arc::new(BlockFinality::Final(Arc::new(block))))
+            .await?;
+        println!("Done.");
+        Ok(())
+    }
+
+    /// Finds the highest cached block that is the genesis block or whose parent is cached with
+    /// the number right below it. Of several such blocks with the same number, the one with the
+    /// lowest hash wins so that repeated runs agree.
+    pub(super) fn find_linked_head(chain_store: &ChainStore) -> anyhow::Result<Option<BlockPtr>> {
+        let highest = match chain_store.highest_cached_block_number()? {
+            Some(highest) => highest,
+            None => return Ok(None),
+        };
+        for number in (0..=highest).rev() {
+            let mut hashes = chain_store.block_hashes_by_block_number(number)?;
+            hashes.sort_by(|a, b| a.as_slice().cmp(b.as_slice()));
+            for hash in hashes {
+                let ptr = BlockPtr::new(hash.clone(), number);
+                if number == 0 {
+                    return Ok(Some(ptr));
+                }
+                let block =
+                    fetch_single_cached_block(H256::from_slice(hash.as_slice()), chain_store)?;
+                let parent_hash = helpers::parent_hash(&block)?;
+                match chain_store.block_number(&parent_hash.into())? {
+                    Some((_, parent_number)) if parent_number == number - 1 => {
+                        return Ok(Some(ptr))
+                    }
+                    _ => println!(
+                        "Block {number} ({}) is not linked to a cached parent, skipping it",
+                        ptr.hash_hex()
+                    ),
+                }
+            }
+        }
+        Ok(None)
+    }
+
     /// Queries the [`ChainStore`] about the chain head.
     pub(super) fn find_chain_head(chain_store: &ChainStore) -> anyhow::Result<i32> {
         let chain_head: Option<i32> = chain_store.chain_head_block(&chain_store.chain)?;
@@ -184,11 +1625,161 @@ mod steps {
     }
 }
 
+mod provider {
+    use super::*;
+    use futures::compat::Future01CompatExt;
+    use graph::{
+        firehose::FirehoseEndpoint,
+        prelude::{
+            async_trait, transaction_receipt::LightTransactionReceipt, EthereumBlockWithCalls,
+            LightEthereumBlock,
+        },
+    };
+    use graph_chain_ethereum::{codec, EthereumAdapter, EthereumAdapterTrait};
+    use std::convert::TryInto;
+
+    /// Where the blocks that the cache is checked against come from. Everything after fetching
+    /// a block is the same no matter which provider it came from.
+    #[async_trait]
+    pub trait BlockProvider: Send + Sync {
+        /// Fetches the block with `block_hash`, which is block `number` in the cache
+        async fn block(
+            &self,
+            block_hash: &H256,
+            number: i32,
+            logger: &Logger,
+        ) -> anyhow::Result<LightEthereumBlock>;
+
+        /// Fetches the receipts of the transactions in `block`
+        async fn receipts(
+            &self,
+            block: LightEthereumBlock,
+            logger: &Logger,
+        ) -> anyhow::Result<Vec<LightTransactionReceipt>>;
+    }
+
+    /// Fetches blocks from a JRPC endpoint
+    pub struct JsonRpcProvider(Arc<EthereumAdapter>);
+
+    impl JsonRpcProvider {
+        pub fn new(ethereum_adapter: Arc<EthereumAdapter>) -> Self {
+            JsonRpcProvider(ethereum_adapter)
+        }
+    }
+
+    #[async_trait]
+    impl BlockProvider for JsonRpcProvider {
+        async fn block(
+            &self,
+            block_hash: &H256,
+            _number: i32,
+            logger: &Logger,
+        ) -> anyhow::Result<LightEthereumBlock> {
+            self.0
+                .block_by_hash(logger, *block_hash)
+                .compat()
+                .await?
+                .ok_or_else(|| anyhow!("JRPC provider found no block {block_hash}"))
+        }
+
+        async fn receipts(
+            &self,
+            block: LightEthereumBlock,
+            logger: &Logger,
+        ) -> anyhow::Result<Vec<LightTransactionReceipt>> {
+            Ok(self
+                .0
+                .load_full_block(logger, block)
+                .await?
+                .transaction_receipts
+                .iter()
+                .map(|receipt| LightTransactionReceipt::from(receipt.as_ref().clone()))
+                .collect())
+        }
+    }
+
+    /// Fetches blocks from a Firehose endpoint. Firehose only hands out blocks by number, and
+    /// we get the block that is on the canonical chain at that height; a cached block that
+    /// isn't on the canonical chain therefore diverges since the hashes differ.
+    ///
+    /// Firehose doesn't have every field that a JRPC provider has, e.g. the seal fields, and
+    /// blocks that were cached from JRPC show up as diverging in those fields.
+    pub struct FirehoseProvider(Arc<FirehoseEndpoint>);
+
+    impl FirehoseProvider {
+        pub fn new(endpoint: Arc<FirehoseEndpoint>) -> Self {
+            FirehoseProvider(endpoint)
+        }
+
+        async fn full_block(
+            &self,
+            number: i32,
+            logger: &Logger,
+        ) -> anyhow::Result<EthereumBlockWithCalls> {
+            let block = self
+                .0
+                .block_for_number::<codec::Block>(logger, number)
+                .await?;
+            (&block).try_into()
+        }
+    }
+
+    #[async_trait]
+    impl BlockProvider for FirehoseProvider {
+        async fn block(
+            &self,
+            _block_hash: &H256,
+            number: i32,
+            logger: &Logger,
+        ) -> anyhow::Result<LightEthereumBlock> {
+            let block = self.full_block(number, logger).await?;
+            Ok(block.ethereum_block.block.as_ref().clone())
+        }
+
+        async fn receipts(
+            &self,
+            block: LightEthereumBlock,
+            logger: &Logger,
+        ) -> anyhow::Result<Vec<LightTransactionReceipt>> {
+            let number = block
+                .number
+                .ok_or_else(|| anyhow!("block {:?} has no number", block.hash))?
+                .as_u32() as i32;
+            Ok(self
+                .full_block(number, logger)
+                .await?
+                .ethereum_block
+                .transaction_receipts
+                .iter()
+                .map(|receipt| LightTransactionReceipt::from(receipt.as_ref().clone()))
+                .collect())
+        }
+    }
+}
+
 mod helpers {
     use super::*;
     use graph::prelude::hex;
     use std::io::{self, Write};
 
+    /// Writes `block`, which came from `source`, as pretty-printed JSON to a file in `dir` that
+    /// is named after the block's hash and its source.
+    pub(super) fn write_block(
+        dir: &Path,
+        block_hash: &H256,
+        source: BlockSource,
+        block: &Value,
+    ) -> anyhow::Result<()> {
+        let path = dir.join(format!("{block_hash:x}.{source}.json"));
+        let json = serde_json::to_string_pretty(block)?;
+        fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!(
+            "Wrote block {block_hash:?} from the {source} to {}",
+            path.display()
+        );
+        Ok(())
+    }
+
     /// Tries to parse a [`H256`] from a hex string.
     pub(super) fn parse_block_hash(hash: &str) -> anyhow::Result<H256> {
         let hash = hash.trim_start_matches("0x");
@@ -196,9 +1787,38 @@ mod helpers {
         Ok(H256::from_slice(&hash))
     }
 
-    /// Asks users if they are certain about truncating the whole block cache.
-    pub(super) fn prompt_for_confirmation() -> anyhow::Result<bool> {
-        print!("This will delete all cached blocks.\nProceed? [y/N] ");
+    /// Reads the number of a block as it is stored in the block cache.
+    pub(super) fn block_number(block: &Value) -> anyhow::Result<i32> {
+        let number = block
+            .get("number")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Block has no number"))?;
+        i32::from_str_radix(number.trim_start_matches("0x"), 16)
+            .with_context(|| format!("Invalid block number {number}"))
+    }
+
+    /// Reads the hash of the parent of a block as it is stored in the block cache.
+    pub(super) fn parent_hash(block: &Value) -> anyhow::Result<H256> {
+        let hash = block
+            .get("parentHash")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Block has no parent hash"))?;
+        parse_block_hash(hash)
+    }
+
+    /// Reads the timestamp of a block as it is stored in the block cache.
+    pub(super) fn block_timestamp(block: &Value) -> anyhow::Result<u64> {
+        let timestamp = block
+            .get("timestamp")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Block has no timestamp"))?;
+        u64::from_str_radix(timestamp.trim_start_matches("0x"), 16)
+            .with_context(|| format!("Invalid block timestamp {timestamp}"))
+    }
+
+    /// Tells users what is about to happen and asks them if they are certain about it.
+    pub(super) fn prompt_for_confirmation(message: &str) -> anyhow::Result<bool> {
+        print!("{message}\nProceed? [y/N] ");
         io::stdout().flush()?;
 
         let mut answer = String::new();
@@ -225,17 +1845,230 @@ mod helpers {
     }
 }
 
+/// A single line on stderr that shows how far a `by_range` run has gotten, so that stdout only
+/// has the results of the run
+mod progress_line {
+    use std::{
+        io::{self, Write},
+        time::{Duration, Instant},
+    };
+
+    /// How often the line gets updated on a terminal
+    const TERMINAL_INTERVAL: Duration = Duration::from_millis(250);
+    /// How often a new line gets written when stderr goes somewhere else, e.g. a log file
+    const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+    pub(super) struct ProgressLine {
+        started: Instant,
+        last_update: Option<Instant>,
+        terminal: bool,
+    }
+
+    impl ProgressLine {
+        pub(super) fn new(started: Instant) -> Self {
+            Self {
+                started,
+                last_update: None,
+                terminal: atty::is(atty::Stream::Stderr),
+            }
+        }
+
+        /// Reports that `handled` of `total` blocks have been handled, the last one being
+        /// `block_number`. Updates are throttled, and most calls don't write anything.
+        pub(super) fn update(&mut self, handled: usize, total: usize, block_number: i32) {
+            let now = Instant::now();
+            let interval = if self.terminal {
+                TERMINAL_INTERVAL
+            } else {
+                LOG_INTERVAL
+            };
+            if matches!(self.last_update, Some(last) if now.duration_since(last) < interval) {
+                return;
+            }
+            self.last_update = Some(now);
+            let line = format!(
+                "Block {block_number}: {}",
+                self.describe(handled, total, now)
+            );
+            self.write(&line);
+        }
+
+        /// Writes the final state of the line
+        pub(super) fn finish(&mut self, handled: usize, total: usize) {
+            let line = format!("Done: {}", self.describe(handled, total, Instant::now()));
+            if self.terminal {
+                eprintln!("\x1b[K{line}");
+            } else {
+                eprintln!("{line}");
+            }
+        }
+
+        fn describe(&self, handled: usize, total: usize, now: Instant) -> String {
+            let elapsed = now.duration_since(self.started).as_secs_f64();
+            let rate = if elapsed > 0.0 {
+                handled as f64 / elapsed
+            } else {
+                0.0
+            };
+            let eta = if rate > 0.0 {
+                let remaining = total.saturating_sub(handled) as f64 / rate;
+                format!("{:?}", Duration::from_secs(remaining.ceil() as u64))
+            } else {
+                "unknown".to_string()
+            };
+            format!("{handled}/{total} blocks, {rate:.1} blocks/s, ETA {eta}")
+        }
+
+        fn write(&self, line: &str) {
+            if self.terminal {
+                // Replace the previous update, and leave the cursor at the start of the line so
+                // that any other output overwrites the update instead of being appended to it
+                eprint!("\x1b[K{line}\r");
+                io::stderr().flush().ok();
+            } else {
+                eprintln!("{line}");
+            }
+        }
+    }
+}
+
+/// The record of which blocks a run deleted from the cache
+mod report {
+    use graph::prelude::{
+        anyhow::{self, Context},
+        serde_json::{self, json},
+        web3::types::H256,
+    };
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+        path::Path,
+    };
+
+    /// Writes the deleted blocks to `path` as newline-delimited JSON with one
+    /// `{"number": .., "hash": ..}` object per block, in the order in which they were deleted.
+    /// The file is written even if no blocks were deleted, so that it always describes the
+    /// latest run.
+    pub(super) fn write(path: &Path, deleted: &[(i32, H256)]) -> anyhow::Result<()> {
+        let context = || format!("Failed to write the report to {}", path.display());
+        let mut writer = BufWriter::new(File::create(path).with_context(context)?);
+        for (number, hash) in deleted {
+            let line = json!({ "number": number, "hash": hash });
+            serde_json::to_writer(&mut writer, &line).with_context(context)?;
+            writer.write_all(b"\n").with_context(context)?;
+        }
+        writer.flush().with_context(context)?;
+        println!(
+            "Wrote {} deleted blocks to {}",
+            deleted.len(),
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+/// Bookkeeping that lets an interrupted `by_range` run resume where it stopped
+mod resume {
+    use super::ranges::Range;
+    use graph::prelude::{
+        anyhow::{self, Context},
+        serde_json,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::{fs, path::PathBuf};
+
+    #[derive(Serialize, Deserialize)]
+    struct State {
+        lower_bound: i32,
+        upper_bound: Option<i32>,
+        #[serde(default = "default_step")]
+        step: usize,
+        last_completed: i32,
+    }
+
+    fn default_step() -> usize {
+        1
+    }
+
+    /// A small file in the working directory that records the last block a `by_range` run over a
+    /// chain has completed.
+    pub(super) struct StateFile {
+        path: PathBuf,
+    }
+
+    impl StateFile {
+        pub(super) fn for_chain(chain: &str) -> Self {
+            Self {
+                path: PathBuf::from(format!(".check-blocks-{chain}.json")),
+            }
+        }
+
+        #[cfg(test)]
+        pub(super) fn at(path: PathBuf) -> Self {
+            Self { path }
+        }
+
+        /// Returns the last block that was completed by an earlier run over the same range, if
+        /// there was one.
+        pub(super) fn load(&self, range: &Range) -> anyhow::Result<Option<i32>> {
+            if !self.path.exists() {
+                return Ok(None);
+            }
+            let contents = fs::read_to_string(&self.path)
+                .with_context(|| format!("Failed to read {}", self.path.display()))?;
+            let state: State = serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", self.path.display()))?;
+            let same_range = state.lower_bound == range.lower_bound
+                && state.upper_bound == range.upper_bound
+                && state.step == range.step;
+            Ok(same_range.then(|| state.last_completed))
+        }
+
+        pub(super) fn save(&self, range: &Range, last_completed: i32) -> anyhow::Result<()> {
+            let state = State {
+                lower_bound: range.lower_bound,
+                upper_bound: range.upper_bound,
+                step: range.step,
+                last_completed,
+            };
+            fs::write(&self.path, serde_json::to_string(&state)?)
+                .with_context(|| format!("Failed to write {}", self.path.display()))
+        }
+
+        /// Forgets about the progress of the run, once it is finished.
+        pub(super) fn remove(&self) -> anyhow::Result<()> {
+            if self.path.exists() {
+                fs::remove_file(&self.path)
+                    .with_context(|| format!("Failed to remove {}", self.path.display()))?;
+            }
+            Ok(())
+        }
+    }
+}
+
 /// Custom range type
 mod ranges {
-    use graph::prelude::anyhow::{self, bail};
+    use graph::prelude::anyhow::{self, bail, Context};
+    use std::str::FromStr;
 
     pub(super) struct Range {
         pub(super) lower_bound: i32,
         pub(super) upper_bound: Option<i32>,
+        /// The distance between the blocks in the range that get checked
+        pub(super) step: usize,
     }
 
     impl Range {
-        pub fn new(lower_bound: Option<i32>, upper_bound: Option<i32>) -> anyhow::Result<Self> {
+        pub fn new(
+            lower_bound: Option<i32>,
+            upper_bound: Option<i32>,
+            step: Option<usize>,
+        ) -> anyhow::Result<Self> {
+            let step = match step {
+                Some(0) => bail!("Step must be a positive number"),
+                Some(step) => step,
+                None => 1,
+            };
             let (lower_bound, upper_bound) = match (lower_bound, upper_bound) {
                 // Invalid cases:
                 (None, None) => {
@@ -263,7 +2096,200 @@ mod ranges {
             Ok(Self {
                 lower_bound,
                 upper_bound,
+                step,
             })
         }
     }
+
+    /// An explicit set of block numbers, given either as a single number or as a
+    /// comma-separated list like `100,205,4001`
+    #[derive(Clone, Debug)]
+    pub struct BlockList(Vec<i32>);
+
+    impl BlockList {
+        /// The block numbers in ascending order and without duplicates
+        pub(super) fn into_numbers(self) -> Vec<i32> {
+            self.0
+        }
+    }
+
+    impl FromStr for BlockList {
+        type Err = anyhow::Error;
+
+        fn from_str(s: &str) -> anyhow::Result<Self> {
+            let mut numbers = s
+                .split(',')
+                .map(|number| {
+                    let number = number.trim();
+                    number
+                        .parse::<i32>()
+                        .with_context(|| format!("Malformed block number: `{}`", number))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            for &number in &numbers {
+                match number {
+                    0 => bail!("Genesis block can't be removed"),
+                    x if x < 0 => bail!("Negative block number used: {}", x),
+                    _ => {}
+                }
+            }
+            numbers.sort_unstable();
+            numbers.dedup();
+            Ok(Self(numbers))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A state file in the temporary directory that is unique to the test `name`
+    fn state_file(name: &str) -> resume::StateFile {
+        let path =
+            std::env::temp_dir().join(format!("check-blocks-{}-{name}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        resume::StateFile::at(path)
+    }
+
+    #[test]
+    fn dry_run_does_not_resume_or_save_progress() {
+        let range = ranges::Range::new(Some(1), Some(100), None).unwrap();
+        let state_file = state_file("dry-run");
+        state_file.save(&range, 50).unwrap();
+
+        let dry_run = FixOptions {
+            dry_run: true,
+            reinsert: false,
+            force: false,
+        };
+        assert!(!tracks_progress(false, dry_run));
+        assert!(!tracks_progress(true, dry_run));
+        let tracked = tracks_progress(false, dry_run).then(|| &state_file);
+        assert_eq!(1, start_block(&range, 100, None, tracked).unwrap());
+        // An explicit start still applies
+        assert_eq!(20, start_block(&range, 100, Some(20), tracked).unwrap());
+
+        // The progress of the interrupted run is still there for a run that fixes blocks
+        let fix = FixOptions {
+            dry_run: false,
+            ..dry_run
+        };
+        let tracked = tracks_progress(false, fix).then(|| &state_file);
+        assert_eq!(51, start_block(&range, 100, None, tracked).unwrap());
+        state_file.remove().unwrap();
+    }
+
+    #[test]
+    fn state_file_only_resumes_the_same_range() {
+        let range = ranges::Range::new(Some(10), None, Some(5)).unwrap();
+        let state_file = state_file("same-range");
+        assert_eq!(None, state_file.load(&range).unwrap());
+
+        state_file.save(&range, 25).unwrap();
+        assert_eq!(Some(25), state_file.load(&range).unwrap());
+        for (lower, upper, step) in [(Some(11), None, Some(5)), (Some(10), Some(100), Some(5))] {
+            let other = ranges::Range::new(lower, upper, step).unwrap();
+            assert_eq!(None, state_file.load(&other).unwrap());
+        }
+        let other_step = ranges::Range::new(Some(10), None, None).unwrap();
+        assert_eq!(None, state_file.load(&other_step).unwrap());
+
+        state_file.remove().unwrap();
+        assert_eq!(None, state_file.load(&range).unwrap());
+    }
+
+    #[test]
+    fn head_follower_returns_new_blocks_of_the_range() {
+        let mut follower = HeadFollower {
+            lower_bound: 10,
+            step: 1,
+            head: 20,
+        };
+        assert_eq!(vec![21, 22, 23], follower.advance(23));
+        // The head did not move, or moved back
+        assert!(follower.advance(23).is_empty());
+        assert!(follower.advance(15).is_empty());
+        assert_eq!(23, follower.head);
+
+        // Only blocks that are a whole number of steps from the lower bound
+        let mut follower = HeadFollower {
+            lower_bound: 10,
+            step: 5,
+            head: 22,
+        };
+        assert_eq!(vec![25, 30], follower.advance(33));
+        assert!(follower.advance(34).is_empty());
+        assert_eq!(vec![35], follower.advance(35));
+        assert_eq!(vec![40], follower.advance(40));
+
+        // A head below the range only checks blocks from its start
+        let mut follower = HeadFollower {
+            lower_bound: 10,
+            step: 3,
+            head: 5,
+        };
+        assert_eq!(vec![10, 13], follower.advance(14));
+    }
+
+    #[test]
+    fn range_with_step() {
+        let range = ranges::Range::new(None, Some(100), Some(10)).unwrap();
+        assert_eq!(
+            (1, Some(100), 10),
+            (range.lower_bound, range.upper_bound, range.step)
+        );
+        let range = ranges::Range::new(Some(5), None, None).unwrap();
+        assert_eq!(
+            (5, None, 1),
+            (range.lower_bound, range.upper_bound, range.step)
+        );
+
+        assert!(ranges::Range::new(Some(5), Some(10), Some(0)).is_err());
+        assert!(ranges::Range::new(None, None, Some(2)).is_err());
+        assert!(ranges::Range::new(Some(0), Some(10), None).is_err());
+        assert!(ranges::Range::new(Some(10), Some(5), None).is_err());
+        assert!(ranges::Range::new(Some(-1), None, None).is_err());
+    }
+
+    #[test]
+    fn block_list_from_str() {
+        let numbers = |s: &str| BlockList::from_str(s).map(BlockList::into_numbers);
+        assert_eq!(vec![7], numbers("7").unwrap());
+        assert_eq!(vec![100, 205, 4001], numbers("4001, 100,205").unwrap());
+        // Duplicates are checked once
+        assert_eq!(vec![3, 5], numbers("5,3,5,3").unwrap());
+
+        for bad in ["", "1,,2", "1;2", "0x10", "1,0", "-4"] {
+            assert!(numbers(bad).is_err(), "`{}` is not a valid block list", bad);
+        }
+    }
+
+    #[test]
+    fn timestamp_search_skips_gaps() {
+        // Blocks 0 to 99 with 10s between them, of which only some are cached
+        let cached: Vec<i32> = (0..100)
+            .filter(|number| *number < 5 || (40..43).contains(number) || *number >= 97)
+            .collect();
+        let search = |timestamp: u64| {
+            steps::first_block_at_or_after(timestamp, 0, 99, |from, before| {
+                Ok(cached
+                    .iter()
+                    .find(|number| (from..before).contains(*number))
+                    .map(|number| (*number, 1000 + 10 * *number as u64)))
+            })
+            .unwrap()
+        };
+
+        assert_eq!(0, search(1000));
+        assert_eq!(3, search(1025));
+        assert_eq!(4, search(1040));
+        // Timestamps of blocks in a gap find the first cached block after the gap
+        assert_eq!(40, search(1045));
+        assert_eq!(40, search(1400));
+        assert_eq!(42, search(1420));
+        assert_eq!(97, search(1421));
+        assert_eq!(97, search(1900));
+        assert_eq!(99, search(1990));
+    }
 }
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use graph::anyhow::bail;
+use graph::components::store::StatusStore;
+use graph::prelude::{anyhow, hex, reqwest, serde_json, BlockNumber, DeploymentHash};
+use graph_store_postgres::{connection_pool::ConnectionPool, Store};
+
+use crate::manager::deployment::DeploymentSearch;
+
+/// The public proofs of indexing of one deployment, locally and on a
+/// reference indexer, remembered so that no block is queried twice
+struct Pois {
+    store: Arc<Store>,
+    client: reqwest::Client,
+    reference_url: String,
+    deployment: DeploymentHash,
+    local: HashMap<BlockNumber, Option<String>>,
+    reference: HashMap<BlockNumber, Option<String>>,
+}
+
+impl Pois {
+    async fn local(&mut self, block: BlockNumber) -> Result<Option<String>, anyhow::Error> {
+        if let Some(poi) = self.local.get(&block) {
+            return Ok(poi.clone());
+        }
+        let poi = self
+            .store
+            .get_public_proof_of_indexing(&self.deployment, block)
+            .await?
+            .map(|(_, poi)| format!("0x{}", hex::encode(&poi)));
+        self.local.insert(block, poi.clone());
+        Ok(poi)
+    }
+
+    async fn reference(&mut self, block: BlockNumber) -> Result<Option<String>, anyhow::Error> {
+        if let Some(poi) = self.reference.get(&block) {
+            return Ok(poi.clone());
+        }
+        let query = format!(
+            r#"{{ publicProofsOfIndexing(requests: [{{ deployment: "{}", blockNumber: "{}" }}]) {{ proofOfIndexing }} }}"#,
+            self.deployment, block
+        );
+        let response: serde_json::Value = self
+            .client
+            .post(&self.reference_url)
+            .json(&serde_json::json!({ "query": query }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if let Some(errors) = response.get("errors") {
+            bail!(
+                "the reference indexer failed to return the POI for block {}: {}",
+                block,
+                errors
+            );
+        }
+        let poi = response
+            .pointer("/data/publicProofsOfIndexing/0/proofOfIndexing")
+            .and_then(|poi| poi.as_str())
+            .map(str::to_string);
+        self.reference.insert(block, poi.clone());
+        Ok(poi)
+    }
+
+    async fn agree(&mut self, block: BlockNumber) -> Result<bool, anyhow::Error> {
+        Ok(self.local(block).await? == self.reference(block).await?)
+    }
+}
+
+/// Find the first block in `from..=to` for which the public proof of
+/// indexing of `search` differs between this installation and the index
+/// node at `reference_url`. Since a proof of indexing covers all blocks up
+/// to the one it is for, proofs stay different once they diverge, and we
+/// can bisect the range to find the first divergence with few queries
+pub async fn compare(
+    store: Arc<Store>,
+    primary: ConnectionPool,
+    search: DeploymentSearch,
+    reference_url: String,
+    from: BlockNumber,
+    to: BlockNumber,
+) -> Result<(), anyhow::Error> {
+    if from > to {
+        bail!("the start block {} is after the end block {}", from, to);
+    }
+    let locator = search.locate_unique(&primary)?;
+
+    let mut pois = Pois {
+        store,
+        client: reqwest::Client::new(),
+        reference_url,
+        deployment: locator.hash.clone(),
+        local: HashMap::new(),
+        reference: HashMap::new(),
+    };
+
+    if pois.agree(to).await? {
+        println!(
+            "The proofs of indexing for {} agree for all blocks from {} to {}",
+            locator, from, to
+        );
+        return Ok(());
+    }
+
+    // Invariant: the proofs for `to` differ, and they agree for all blocks
+    // before `from`
+    let (mut from, mut to) = (from, to);
+    while from < to {
+        let mid = from + (to - from) / 2;
+        if pois.agree(mid).await? {
+            from = mid + 1;
+        } else {
+            to = mid;
+        }
+    }
+
+    let show = |poi: Option<String>| poi.unwrap_or_else(|| "none".to_string());
+    println!(
+        "The proofs of indexing for {} first differ at block {}",
+        locator, to
+    );
+    println!("  local:     {}", show(pois.local(to).await?));
+    println!("  reference: {}", show(pois.reference(to).await?));
+    Ok(())
+}
@@ -117,7 +117,7 @@ pub fn run(
     println!("\nRewinding deployments");
     for deployment in &deployments {
         let loc = deployment.locator();
-        subgraph_store.rewind(loc.hash.clone(), block_ptr_to.clone())?;
+        subgraph_store.rewind(&loc, block_ptr_to.clone())?;
         println!("  ... rewound {}", loc);
     }
 
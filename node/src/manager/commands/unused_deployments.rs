@@ -1,6 +1,7 @@
 use std::{sync::Arc, time::Instant};
 
 use graph::prelude::{anyhow::Error, chrono};
+use graph_store_postgres::command_support::catalog::Site;
 use graph_store_postgres::{unused, SubgraphStore, UnusedDeployment};
 
 use crate::manager::display::List;
@@ -137,3 +138,71 @@ pub fn remove(
     }
     Ok(())
 }
+
+fn orphan_list(orphans: &[Site]) -> List {
+    let mut list = List::new(vec!["id", "deployment", "shard", "namespace", "network"]);
+    for site in orphans {
+        list.append(vec![
+            site.id.to_string(),
+            site.deployment.to_string(),
+            site.shard.to_string(),
+            site.namespace.to_string(),
+            site.network.clone(),
+        ]);
+    }
+    list
+}
+
+/// List deployments that no subgraph version refers to. Unlike unused
+/// deployments, these were never the version of any subgraph, e.g.,
+/// because deploying them failed halfway
+pub fn list_orphans(store: Arc<SubgraphStore>) -> Result<(), Error> {
+    let orphans = store.orphaned_deployments()?;
+
+    if orphans.is_empty() {
+        println!("no orphaned deployments");
+    } else {
+        orphan_list(&orphans).render();
+    }
+
+    Ok(())
+}
+
+/// Remove the deployments that `list_orphans` lists. Without `yes`, only
+/// show which deployments would be removed
+pub fn remove_orphans(store: Arc<SubgraphStore>, yes: bool) -> Result<(), Error> {
+    let orphans = store.orphaned_deployments()?;
+
+    if orphans.is_empty() {
+        println!("Nothing to remove.");
+        return Ok(());
+    }
+
+    orphan_list(&orphans).render();
+    if !yes {
+        println!(
+            "\nThese {} deployments would be removed. Run again with --yes to remove them",
+            orphans.len()
+        );
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for site in &orphans {
+        let start = Instant::now();
+        match store.remove_deployment(site.id) {
+            Ok(()) => println!(
+                "removed {} from {} in {:.1}s",
+                site.namespace,
+                site.shard,
+                start.elapsed().as_millis() as f64 / 1000.0
+            ),
+            Err(e) => {
+                failed += 1;
+                println!("removing {} failed: {}", site.namespace, e)
+            }
+        }
+    }
+    println!("Removed {} orphaned deployments", orphans.len() - failed);
+    Ok(())
+}
@@ -1,9 +1,12 @@
 mod auth;
 mod explorer;
+mod metrics;
 mod resolver;
 mod schema;
 mod server;
 mod service;
+mod status_watch;
+mod sync_rate;
 
 pub use self::auth::PoiProtection;
 pub use self::server::IndexNodeServer;
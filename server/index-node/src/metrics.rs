@@ -0,0 +1,51 @@
+use std::fmt;
+use std::time::Duration;
+
+use graph::prelude::*;
+
+/// Metrics about the top-level fields that the index node resolves, so
+/// that operators can see which queries, e.g., proofs of indexing, are
+/// expensive and alert on failures
+pub struct IndexNodeMetrics {
+    field_execution_time: Box<HistogramVec>,
+    field_results: Box<CounterVec>,
+}
+
+impl fmt::Debug for IndexNodeMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "IndexNodeMetrics {{ }}")
+    }
+}
+
+impl IndexNodeMetrics {
+    pub fn new(registry: Arc<dyn MetricsRegistry>) -> Self {
+        let field_execution_time = registry
+            .new_histogram_vec(
+                "index_node_field_execution_time",
+                "Execution time for resolving top-level index node fields",
+                vec![String::from("field")],
+                vec![0.01, 0.1, 0.5, 1.0, 10.0, 100.0],
+            )
+            .expect("failed to create `index_node_field_execution_time` histogram");
+        let field_results = registry
+            .new_counter_vec(
+                "index_node_field_results",
+                "Number of top-level index node fields resolved, by outcome",
+                vec![String::from("field"), String::from("status")],
+            )
+            .expect("failed to create `index_node_field_results` counter");
+
+        Self {
+            field_execution_time,
+            field_results,
+        }
+    }
+
+    pub fn observe_field<T, E>(&self, field: &str, duration: Duration, result: &Result<T, E>) {
+        let status = if result.is_ok() { "success" } else { "failed" };
+        self.field_execution_time
+            .with_label_values(&[field])
+            .observe(duration.as_secs_f64());
+        self.field_results.with_label_values(&[field, status]).inc();
+    }
+}
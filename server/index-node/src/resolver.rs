@@ -1,19 +1,29 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::convert::TryInto;
+use std::sync::Mutex;
+use std::time::Instant;
 
 use either::Either;
+use lru_time_cache::LruCache;
+use sha2::{Digest, Sha256};
 use web3::types::Address;
 
-use graph::blockchain::{Blockchain, BlockchainKind, BlockchainMap};
-use graph::components::store::{BlockStore, EntityType, Store};
+use graph::blockchain::{
+    Blockchain, BlockchainKind, BlockchainMap, DataSource as _, DataSourceTemplate as _,
+};
+use graph::components::store::{BlockStore, DeploymentLocator, EntityType, Store};
 use graph::data::graphql::{object, IntoValue, ObjectOrInterface, ValueMap};
 use graph::data::subgraph::features::detect_features;
+use graph::data::subgraph::schema::{SubgraphError, SubgraphHealth};
 use graph::data::subgraph::status;
+use graph::data::subgraph::UnresolvedSchema;
 use graph::data::value::Object;
 use graph::prelude::*;
 use graph_graphql::prelude::{a, ExecutionContext, Resolver};
 
 use crate::auth::PoiProtection;
+use crate::metrics::IndexNodeMetrics;
+use crate::sync_rate::SyncRates;
 
 #[derive(Clone, Debug)]
 struct PublicProofOfIndexingRequest {
@@ -57,6 +67,14 @@ impl IntoValue for PublicProofOfIndexingResult {
     }
 }
 
+/// The results of `subgraphFeatures` queries, which we can cache since the features of a
+/// deployment never change.
+pub(crate) type SubgraphFeaturesCache = Arc<Mutex<LruCache<DeploymentHash, r::Value>>>;
+
+/// Recently measured deployment sizes, since measuring them is expensive and they don't change
+/// much from one minute to the next.
+pub(crate) type DeploymentSizeCache = Arc<Mutex<LruCache<DeploymentHash, status::DeploymentSize>>>;
+
 /// Resolver for the index node GraphQL API.
 pub struct IndexNodeResolver<S: Store> {
     logger: Logger,
@@ -64,6 +82,13 @@ pub struct IndexNodeResolver<S: Store> {
     store: Arc<S>,
     link_resolver: Arc<dyn LinkResolver>,
     bearer_token: Option<String>,
+    subgraph_features_cache: SubgraphFeaturesCache,
+    deployment_size_cache: DeploymentSizeCache,
+    sync_rates: Arc<SyncRates>,
+    metrics: Arc<IndexNodeMetrics>,
+    /// The deployment whose status changed, when the resolver is used for
+    /// an event of the `indexingStatusChanged` subscription
+    changed_deployment: Option<String>,
 }
 
 impl<S: Store> IndexNodeResolver<S> {
@@ -73,8 +98,15 @@ impl<S: Store> IndexNodeResolver<S> {
         link_resolver: Arc<dyn LinkResolver>,
         bearer_token: Option<String>,
         blockchain_map: Arc<BlockchainMap>,
+        subgraph_features_cache: SubgraphFeaturesCache,
+        deployment_size_cache: DeploymentSizeCache,
+        sync_rates: Arc<SyncRates>,
+        metrics: Arc<IndexNodeMetrics>,
     ) -> Self {
-        let logger = logger.new(o!("component" => "IndexNodeResolver"));
+        // A resolver is created for every request, so tagging its logger
+        // with a random id lets us correlate all log lines of one query
+        let query_id = format!("{:016x}", rand::random::<u64>());
+        let logger = logger.new(o!("component" => "IndexNodeResolver", "query_id" => query_id));
 
         Self {
             logger,
@@ -82,6 +114,19 @@ impl<S: Store> IndexNodeResolver<S> {
             store,
             link_resolver,
             bearer_token,
+            subgraph_features_cache,
+            deployment_size_cache,
+            sync_rates,
+            metrics,
+            changed_deployment: None,
+        }
+    }
+
+    /// Use this resolver to report that the status of `deployment` changed
+    pub fn for_status_change(self, deployment: String) -> Self {
+        Self {
+            changed_deployment: Some(deployment),
+            ..self
         }
     }
 
@@ -100,10 +145,247 @@ impl<S: Store> IndexNodeResolver<S> {
             })
             .unwrap_or_else(|| Vec::new());
 
-        let infos = self
+        let health = field.get_optional::<Vec<SubgraphHealth>>("health").unwrap();
+        // The whole response is built in memory before it is serialized. The store orders and
+        // pages the statuses, and we only fill in the expensive parts of the ones on the page
+        let max_first = ENV_VARS.indexing_statuses_max_first;
+        let first = match field.get_optional::<i32>("first").unwrap() {
+            Some(first) if first.max(0) as usize > max_first => {
+                return Err(QueryExecutionError::ValidationError(
+                    Some(field.position),
+                    format!(
+                        "`indexingStatuses` asks for {} statuses but at most {} are allowed",
+                        first, max_first
+                    ),
+                ));
+            }
+            Some(first) => Some(first.max(0) as usize),
+            None => None,
+        };
+        let order = match field.get_optional::<String>("orderBy").unwrap().as_deref() {
+            Some("LAG_DESC") => status::Order::LagDesc,
+            Some("LAG_ASC") => status::Order::LagAsc,
+            Some("LATEST_BLOCK") => status::Order::LatestBlock,
+            Some(order_by) => unreachable!("invalid orderBy `{}`", order_by),
+            None => status::Order::Created,
+        };
+        let page = status::Page {
+            network: field.get_optional::<String>("network").unwrap(),
+            node: field.get_optional::<String>("node").unwrap(),
+            order,
+            first,
+            skip: field
+                .get_optional::<i32>("skip")
+                .unwrap()
+                .unwrap_or(0)
+                .max(0) as usize,
+        };
+        let at_block = field.get_optional::<BlockNumber>("atBlock").unwrap();
+        let mut infos = match at_block {
+            Some(block) => self.statuses_at_block(deployments, health, page, block)?,
+            None => {
+                let filter = match health {
+                    Some(health) => status::Filter::DeploymentsWithHealth(deployments, health),
+                    None => status::Filter::Deployments(deployments),
+                };
+                self.store.status_page(filter, page)?
+            }
+        };
+        self.fill_requested(field, &mut infos, at_block)?;
+        Ok(infos.into_value())
+    }
+
+    /// The page of statuses as of `block`. The store filters, orders and pages by current
+    /// health and latest blocks, which are not the ones as of `block`, and we therefore have
+    /// to load the statuses of all matching deployments and do all of that here
+    fn statuses_at_block(
+        &self,
+        deployments: Vec<String>,
+        health: Option<Vec<SubgraphHealth>>,
+        page: status::Page,
+        block: BlockNumber,
+    ) -> Result<Vec<status::Info>, QueryExecutionError> {
+        let mut infos = self
             .store
             .status(status::Filter::Deployments(deployments))?;
-        Ok(infos.into_value())
+        if let Some(network) = &page.network {
+            infos.retain(|info| info.chains.iter().any(|chain| &chain.network == network));
+        }
+        if let Some(node) = &page.node {
+            infos.retain(|info| info.node.as_ref() == Some(node));
+        }
+        for info in infos.iter_mut() {
+            self.rewind_to_block(info, block)?;
+        }
+        if let Some(health) = health {
+            infos.retain(|info| health.contains(&info.health));
+        }
+
+        infos.sort_by_key(|info| {
+            let chain = info.chains.first();
+            let latest = chain
+                .and_then(|chain| chain.latest_block.as_ref())
+                .map(|block| block.number());
+            let head = chain
+                .and_then(|chain| chain.chain_head_block.as_ref())
+                .map(|block| block.number());
+            page.order.key(info.id, latest, head)
+        });
+        Ok(infos
+            .into_iter()
+            .skip(page.skip)
+            .take(page.first.unwrap_or(usize::MAX))
+            .collect())
+    }
+
+    /// Changes `info` so that its health, errors and latest block are what they were when the
+    /// deployment had processed `block`. Everything else in `info` stays current.
+    fn rewind_to_block(
+        &self,
+        info: &mut status::Info,
+        block: BlockNumber,
+    ) -> Result<(), QueryExecutionError> {
+        let id = DeploymentHash::new(info.subgraph.clone())
+            .map_err(QueryExecutionError::SubgraphDeploymentIdError)?;
+        let before = |error: &SubgraphError| {
+            error
+                .block_ptr
+                .as_ref()
+                .map_or(false, |ptr| ptr.number <= block)
+        };
+
+        let transition = self.store.health_at_block(&id, block)?;
+        info.health = transition
+            .as_ref()
+            .map_or(SubgraphHealth::Healthy, |transition| transition.health);
+        info.fatal_error = match (info.health, info.fatal_error.take()) {
+            (SubgraphHealth::Failed, Some(error)) if before(&error) => Some(error),
+            // The deployment failed at `block` but has since been restarted;
+            // all we still know about the error is its message
+            (SubgraphHealth::Failed, _) => transition.and_then(|transition| {
+                transition.error.map(|message| SubgraphError {
+                    subgraph_id: id.clone(),
+                    message,
+                    block_ptr: None,
+                    handler: None,
+                    deterministic: false,
+                })
+            }),
+            _ => None,
+        };
+        info.non_fatal_errors.retain(before);
+        info.sync_rate = None;
+
+        for chain in info.chains.iter_mut() {
+            let ahead = chain
+                .latest_block
+                .as_ref()
+                .map_or(false, |latest| latest.number() > block);
+            if ahead {
+                chain.latest_block = self
+                    .block_hash_on_network(&chain.network, block)
+                    .ok()
+                    .map(|hash| status::EthereumBlock::new(hash, block));
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the statuses that the optional arguments of `field` exclude, i.e., those of
+    /// deployments that don't index the `network` or that aren't assigned to the `node`.
+    fn retain_requested(field: &a::Field, infos: &mut Vec<status::Info>) {
+        if let Some(network) = field.get_optional::<String>("network").unwrap() {
+            infos.retain(|info| info.chains.iter().any(|chain| chain.network == network));
+        }
+        if let Some(node) = field.get_optional::<String>("node").unwrap() {
+            infos.retain(|info| info.node.as_ref() == Some(&node));
+        }
+    }
+
+    /// Fills in the parts of `infos` that the store does not provide: the sync rate, which
+    /// we track here, and the parts that the client has to ask for explicitly: the number
+    /// of entities of each type, which is expensive to count, and a page of the non-fatal
+    /// errors, when `first` or `skip` are passed to `nonFatalErrors`. If the statuses were
+    /// rewound to `at_block`, there is no sync rate, and the page of errors is taken from
+    /// the errors before that block.
+    fn fill_requested(
+        &self,
+        field: &a::Field,
+        infos: &mut [status::Info],
+        at_block: Option<BlockNumber>,
+    ) -> Result<(), QueryExecutionError> {
+        for info in infos.iter_mut().filter(|_| at_block.is_none()) {
+            let latest_block = info
+                .chains
+                .first()
+                .and_then(|chain| chain.latest_block.as_ref());
+            if let Some(block) = latest_block {
+                info.sync_rate = self.sync_rates.record(&info.subgraph, block.number());
+            }
+        }
+
+        let selected = |name: &str| {
+            field
+                .selection_set
+                .fields()
+                .flat_map(|(_, fields)| fields)
+                .find(|field| field.name == name)
+        };
+        let count_entities = selected("entitiesByType").is_some();
+        let measure_size = selected("deploymentSize").is_some();
+        let errors_page = selected("nonFatalErrors").and_then(|errors| {
+            let first = errors.get_optional::<i32>("first").unwrap();
+            let skip = errors.get_optional::<i32>("skip").unwrap();
+            (first.is_some() || skip.is_some()).then(|| {
+                (
+                    first.unwrap_or(1000).max(0) as usize,
+                    skip.unwrap_or(0).max(0) as usize,
+                )
+            })
+        });
+        if !count_entities && !measure_size && errors_page.is_none() {
+            return Ok(());
+        }
+
+        for info in infos {
+            let id = DeploymentHash::new(info.subgraph.clone())
+                .map_err(QueryExecutionError::SubgraphDeploymentIdError)?;
+            if count_entities {
+                info.entities_by_type = Some(self.store.entity_counts(&id)?);
+            }
+            if measure_size {
+                info.deployment_size = Some(self.deployment_size(&id)?);
+            }
+            match (errors_page, at_block) {
+                (Some((first, skip)), None) => {
+                    info.non_fatal_errors = self.store.non_fatal_errors(&id, first, skip)?;
+                }
+                (Some((first, skip)), Some(_)) => {
+                    info.non_fatal_errors = std::mem::take(&mut info.non_fatal_errors)
+                        .into_iter()
+                        .skip(skip)
+                        .take(first)
+                        .collect();
+                }
+                (None, _) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn deployment_size(
+        &self,
+        id: &DeploymentHash,
+    ) -> Result<status::DeploymentSize, QueryExecutionError> {
+        if let Some(size) = self.deployment_size_cache.lock().unwrap().get(id) {
+            return Ok(*size);
+        }
+        let size = self.store.deployment_size(id)?;
+        self.deployment_size_cache
+            .lock()
+            .unwrap()
+            .insert(id.clone(), size);
+        Ok(size)
     }
 
     fn resolve_indexing_statuses_for_subgraph_name(
@@ -123,9 +405,43 @@ impl<S: Store> IndexNodeResolver<S> {
             "name" => &subgraph_name
         );
 
-        let infos = self
+        let mut infos = self
             .store
             .status(status::Filter::SubgraphName(subgraph_name))?;
+        Self::retain_requested(field, &mut infos);
+        self.fill_requested(field, &mut infos, None)?;
+
+        Ok(infos.into_value())
+    }
+
+    fn resolve_indexing_statuses_for_subgraph_names(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let subgraph_names = field
+            .get_required::<Vec<String>>("subgraphNames")
+            .expect("subgraphNames not provided");
+
+        debug!(
+            self.logger,
+            "Resolve indexing statuses for subgraph names";
+            "names" => subgraph_names.join(", ")
+        );
+
+        // Statuses are reported in the order of the names, and sorted by
+        // deployment for each name so that the result is stable. A
+        // deployment that is used by several of the names is only reported
+        // the first time we see it
+        let mut seen = HashSet::new();
+        let mut infos = Vec::new();
+        for subgraph_name in subgraph_names {
+            let mut name_infos = self
+                .store
+                .status(status::Filter::SubgraphName(subgraph_name))?;
+            name_infos.sort_by_key(|info| info.id.0);
+            infos.extend(name_infos.into_iter().filter(|info| seen.insert(info.id)));
+        }
+        self.fill_requested(field, &mut infos, None)?;
 
         Ok(infos.into_value())
     }
@@ -284,7 +600,74 @@ impl<S: Store> IndexNodeResolver<S> {
         ))
     }
 
+    /// The indexer address that `field` asks to sign the proof of indexing with
+    fn signing_indexer(&self, field: &a::Field) -> Option<Address> {
+        let indexer = field
+            .get_optional::<Address>("indexer")
+            .expect("Invalid indexer");
+
+        let poi_protection = PoiProtection::from_env(&ENV_VARS);
+        if !poi_protection.validate_access_token(self.bearer_token.as_deref()) {
+            // Let's sign the POI with a zero'd address when the access token is
+            // invalid.
+            return Some(Address::zero());
+        }
+        indexer
+    }
+
     fn resolve_proof_of_indexing(&self, field: &a::Field) -> Result<r::Value, QueryExecutionError> {
+        let indexer = self.signing_indexer(field);
+        self.proof_of_indexing(field, indexer).map(|(_, poi)| poi)
+    }
+
+    /// Like `proofOfIndexing`, but also reports the block that the proof of indexing is for.
+    /// Without `blockHash`, that is the block that the block cache has for `blockNumber`, and
+    /// clients can check that it is the block they meant
+    fn resolve_proof_of_indexing_with_block(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let deployment_id = field
+            .get_required::<DeploymentHash>("subgraph")
+            .expect("Valid subgraphId required");
+        let indexer = self.signing_indexer(field);
+        let (block, poi) = self.proof_of_indexing(field, indexer)?;
+
+        Ok(object! {
+            __typename: "ProofOfIndexingResult",
+            deployment: deployment_id.to_string(),
+            block: object! {
+                number: block.number,
+                hash: block.hash_hex(),
+            },
+            proofOfIndexing: poi,
+        })
+    }
+
+    /// The proof of indexing without any indexer address mixed in. Whoever has it can compute
+    /// the proof of indexing of any indexer, and unlike `proofOfIndexing`, we refuse to
+    /// compute it when the access token is invalid rather than sign with a zero'd address.
+    fn resolve_public_proof_of_indexing(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let poi_protection = PoiProtection::from_env(&ENV_VARS);
+        if !poi_protection.validate_access_token(self.bearer_token.as_deref()) {
+            return Err(QueryExecutionError::AccessTokenRequired(
+                "publicProofOfIndexing".to_string(),
+            ));
+        }
+
+        self.proof_of_indexing(field, None).map(|(_, poi)| poi)
+    }
+
+    /// Computes the proof of indexing that `field` asks for, signed by `indexer`, and returns it
+    /// together with the block it is for
+    fn proof_of_indexing(
+        &self,
+        field: &a::Field,
+        indexer: Option<Address>,
+    ) -> Result<(BlockPtr, r::Value), QueryExecutionError> {
         let deployment_id = field
             .get_required::<DeploymentHash>("subgraph")
             .expect("Valid subgraphId required");
@@ -295,23 +678,16 @@ impl<S: Store> IndexNodeResolver<S> {
             .try_into()
             .unwrap();
 
-        let block_hash = field
-            .get_required::<BlockHash>("blockHash")
-            .expect("Valid blockHash required");
+        let block_hash = match field
+            .get_optional::<BlockHash>("blockHash")
+            .expect("Invalid blockHash")
+        {
+            Some(block_hash) => block_hash,
+            None => self.block_hash_for_deployment(&deployment_id, block_number)?,
+        };
 
         let block = BlockPtr::new(block_hash, block_number);
 
-        let mut indexer = field
-            .get_optional::<Address>("indexer")
-            .expect("Invalid indexer");
-
-        let poi_protection = PoiProtection::from_env(&ENV_VARS);
-        if !poi_protection.validate_access_token(self.bearer_token.as_deref()) {
-            // Let's sign the POI with a zero'd address when the access token is
-            // invalid.
-            indexer = Some(Address::zero());
-        }
-
         let poi_fut = self
             .store
             .get_proof_of_indexing(&deployment_id, &indexer, block.clone());
@@ -326,11 +702,228 @@ impl<S: Store> IndexNodeResolver<S> {
                     "block" => format!("{}", block),
                     "error" => format!("{:?}", e)
                 );
-                r::Value::Null
+                // Report the failure to the client; a `null` would be
+                // indistinguishable from there being no proof of indexing
+                return Err(QueryExecutionError::from(e));
             }
         };
 
-        Ok(poi)
+        Ok((block, poi))
+    }
+
+    /// Looks up the hash of the block with `number` in the block cache of the network that
+    /// `deployment` indexes. Fails if the block isn't cached, or if the cache has several blocks
+    /// with that number because of a fork, since we then can't tell which one is meant
+    fn block_hash_for_deployment(
+        &self,
+        deployment: &DeploymentHash,
+        number: BlockNumber,
+    ) -> Result<BlockHash, QueryExecutionError> {
+        let network = self
+            .store
+            .status(status::Filter::Deployments(vec![deployment.to_string()]))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| QueryExecutionError::DeploymentNotFound(deployment.to_string()))?
+            .chains
+            .into_iter()
+            .next()
+            .map(|chain| chain.network)
+            .ok_or_else(|| {
+                QueryExecutionError::BlockHashNotFound(
+                    number,
+                    format!("deployment {} has no network", deployment),
+                )
+            })?;
+        self.block_hash_on_network(&network, number)
+    }
+
+    /// Looks up the hash of the block with `number` on `network` in the block cache
+    fn block_hash_on_network(
+        &self,
+        network: &str,
+        number: BlockNumber,
+    ) -> Result<BlockHash, QueryExecutionError> {
+        let not_found = |reason: String| QueryExecutionError::BlockHashNotFound(number, reason);
+
+        let chain_store = self
+            .store
+            .block_store()
+            .chain_store(&network)
+            .ok_or_else(|| not_found(format!("network {} is not supported", network)))?;
+
+        let mut hashes = chain_store
+            .block_hashes_by_block_number(number)
+            .map_err(|e| not_found(e.to_string()))?;
+        match hashes.len() {
+            0 => Err(not_found(format!(
+                "the block is not in the block cache for {}",
+                network
+            ))),
+            1 => Ok(hashes.pop().unwrap()),
+            n => Err(not_found(format!(
+                "there are {} blocks with that number on {}, pass `blockHash` to pick one",
+                n, network
+            ))),
+        }
+    }
+
+    fn resolve_proof_of_indexing_for_blocks(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let deployment_id = field
+            .get_required::<DeploymentHash>("subgraph")
+            .expect("Valid subgraphId required");
+
+        let blocks = field
+            .get_required::<Vec<BlockPtr>>("blocks")
+            .expect("Valid blocks required, validation should have caught this");
+
+        let max_blocks = ENV_VARS.poi_range_max_blocks;
+        if blocks.len() > max_blocks {
+            return Err(QueryExecutionError::ValidationError(
+                Some(field.position),
+                format!(
+                    "`proofOfIndexingForBlocks` asks for {} blocks but at most {} are allowed",
+                    blocks.len(),
+                    max_blocks
+                ),
+            ));
+        }
+
+        let indexer = self.signing_indexer(field);
+
+        // `join_all` preserves the order of the futures, so the results line
+        // up with the requested blocks
+        let pois =
+            futures::executor::block_on(futures::future::join_all(blocks.iter().map(|block| {
+                self.store
+                    .get_proof_of_indexing(&deployment_id, &indexer, block.clone())
+            })));
+        let pois = blocks
+            .iter()
+            .zip(pois)
+            .map(|(block, poi)| {
+                poi.map_err(|e| {
+                    error!(
+                        self.logger,
+                        "Failed to query proof of indexing";
+                        "subgraph" => &deployment_id,
+                        "block" => format!("{}", block),
+                        "error" => format!("{:?}", e)
+                    );
+                    // Same as for `proofOfIndexing`, a `null` would look like
+                    // there is no proof of indexing for the block
+                    QueryExecutionError::from(e)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(r::Value::List(
+            blocks
+                .into_iter()
+                .zip(pois)
+                .map(|(block, poi)| {
+                    object! {
+                        __typename: "ProofOfIndexingResult",
+                        deployment: deployment_id.to_string(),
+                        block: object! {
+                            number: block.number,
+                            hash: block.hash_hex(),
+                        },
+                        proofOfIndexing: poi.map(|poi| format!("0x{}", hex::encode(&poi))),
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    fn resolve_proof_of_indexing_range(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let deployment_id = field
+            .get_required::<DeploymentHash>("subgraph")
+            .expect("Valid subgraphId required");
+        let from_block = field
+            .get_required::<BlockNumber>("fromBlock")
+            .expect("Valid fromBlock required");
+        let to_block = field
+            .get_required::<BlockNumber>("toBlock")
+            .expect("Valid toBlock required");
+        let indexer = self.signing_indexer(field);
+
+        if from_block < 0 || to_block < from_block {
+            return Err(QueryExecutionError::ValidationError(
+                Some(field.position),
+                format!(
+                    "invalid block range [{}, {}] for `proofOfIndexingRange`",
+                    from_block, to_block
+                ),
+            ));
+        }
+        let max_blocks = ENV_VARS.poi_range_max_blocks;
+        let count = (to_block - from_block) as usize + 1;
+        if count > max_blocks {
+            return Err(QueryExecutionError::ValidationError(
+                Some(field.position),
+                format!(
+                    "`proofOfIndexingRange` covers {} blocks but at most {} are allowed",
+                    count, max_blocks
+                ),
+            ));
+        }
+
+        // Look the network up once instead of for every block in the range
+        let network = self
+            .store
+            .status(status::Filter::Deployments(vec![deployment_id.to_string()]))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| QueryExecutionError::DeploymentNotFound(deployment_id.to_string()))?
+            .chains
+            .into_iter()
+            .next()
+            .map(|chain| chain.network)
+            .ok_or_else(|| {
+                QueryExecutionError::BlockHashNotFound(
+                    from_block,
+                    format!("deployment {} has no network", deployment_id),
+                )
+            })?;
+        let blocks = (from_block..=to_block)
+            .map(|number| {
+                self.block_hash_on_network(&network, number)
+                    .map(|hash| BlockPtr::new(hash, number))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Computing all proofs in one go lets the store share the work that
+        // doesn't depend on the block between them
+        let pois = futures::executor::block_on(self.store.get_proofs_of_indexing(
+            &deployment_id,
+            &indexer,
+            blocks.clone(),
+        ))?;
+
+        Ok(r::Value::List(
+            blocks
+                .into_iter()
+                .zip(pois)
+                .map(|(block, poi)| {
+                    object! {
+                        __typename: "ProofOfIndexingResult",
+                        deployment: deployment_id.to_string(),
+                        block: object! {
+                            number: block.number,
+                            hash: block.hash_hex(),
+                        },
+                        proofOfIndexing: poi.map(|poi| format!("0x{}", hex::encode(&poi))),
+                    }
+                })
+                .collect(),
+        ))
     }
 
     fn resolve_public_proofs_of_indexing(
@@ -405,10 +998,12 @@ impl<S: Store> IndexNodeResolver<S> {
             "current_version" => current_version,
         );
 
-        let infos = self.store.status(status::Filter::SubgraphVersion(
+        let mut infos = self.store.status(status::Filter::SubgraphVersion(
             subgraph_name,
             current_version,
         ))?;
+        infos.truncate(1);
+        self.fill_requested(field, &mut infos, None)?;
 
         Ok(infos
             .into_iter()
@@ -417,43 +1012,491 @@ impl<S: Store> IndexNodeResolver<S> {
             .unwrap_or(r::Value::Null))
     }
 
+    fn resolve_indexing_status_for_deployment(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let deployment = field.get_required::<String>("deployment").unwrap();
+        self.indexing_status_for_deployment(field, deployment)
+    }
+
+    fn resolve_indexing_status_changed(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        match &self.changed_deployment {
+            Some(deployment) => self.indexing_status_for_deployment(field, deployment.clone()),
+            None => Ok(r::Value::Null),
+        }
+    }
+
+    fn indexing_status_for_deployment(
+        &self,
+        field: &a::Field,
+        deployment: String,
+    ) -> Result<r::Value, QueryExecutionError> {
+        debug!(
+            self.logger,
+            "Resolve indexing status for deployment";
+            "deployment" => &deployment,
+        );
+
+        let mut infos = self
+            .store
+            .status(status::Filter::Deployments(vec![deployment]))?;
+        infos.truncate(1);
+        self.fill_requested(field, &mut infos, None)?;
+
+        Ok(infos
+            .into_iter()
+            .next()
+            .map(|info| info.into_value())
+            .unwrap_or(r::Value::Null))
+    }
+
+    fn resolve_node_health(&self) -> r::Value {
+        // A store we can not reach is exactly what the caller wants to know
+        // about; report it rather than failing the query
+        match self.store.node_health() {
+            Ok(health) => health,
+            Err(e) => {
+                error!(self.logger, "Failed to determine node health"; "error" => e.to_string());
+                status::NodeHealth::unreachable()
+            }
+        }
+        .into_value()
+    }
+
+    fn resolve_pending_assignments(&self) -> Result<r::Value, QueryExecutionError> {
+        Ok(self.store.pending_assignments()?.into_value())
+    }
+
+    fn resolve_chains(&self) -> Result<r::Value, QueryExecutionError> {
+        let block_store = self.store.block_store();
+        let mut networks = BTreeMap::new();
+        for network in self.blockchain_map.networks() {
+            let chain_store = match block_store.chain_store(network) {
+                Some(chain_store) => chain_store,
+                None => {
+                    warn!(self.logger, "No chain store for configured network"; "network" => network);
+                    continue;
+                }
+            };
+            let chain_head_block = futures::executor::block_on(chain_store.chain_head_ptr())
+                .map_err(StoreError::from)?
+                .map(status::EthereumBlock::from);
+            networks.insert(
+                network.to_string(),
+                status::NetworkInfo::new(network.to_string(), chain_head_block),
+            );
+        }
+
+        for info in self.store.status(status::Filter::Deployments(vec![]))? {
+            for chain in &info.chains {
+                if let Some(network) = networks.get_mut(&chain.network) {
+                    network.add_deployment(chain);
+                }
+            }
+        }
+
+        Ok(r::Value::List(
+            networks
+                .into_values()
+                .map(|network| network.into_value())
+                .collect(),
+        ))
+    }
+
+    /// Mutations change what the node indexes, and, unlike proofs of
+    /// indexing, which are merely signed with a zero address without a valid
+    /// access token, they are never open to everybody
+    fn check_mutation_access(&self, field: &a::Field) -> Result<(), QueryExecutionError> {
+        let poi_protection = PoiProtection::from_env(&ENV_VARS);
+        if !poi_protection.is_active()
+            || !poi_protection.validate_access_token(self.bearer_token.as_deref())
+        {
+            return Err(QueryExecutionError::AccessTokenRequired(
+                field.name.to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn active_locator(
+        &self,
+        deployment: &DeploymentHash,
+    ) -> Result<DeploymentLocator, QueryExecutionError> {
+        self.store
+            .subgraph_store()
+            .active_locator(deployment)?
+            .ok_or_else(|| QueryExecutionError::DeploymentNotFound(deployment.to_string()))
+    }
+
+    /// Pauses or resumes the deployment in the `deployment` argument,
+    /// depending on `pause`, and returns its assignment afterwards
+    fn resolve_pause_deployment(
+        &self,
+        field: &a::Field,
+        pause: bool,
+    ) -> Result<r::Value, QueryExecutionError> {
+        self.check_mutation_access(field)?;
+
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let deployment = field.get_required::<DeploymentHash>("deployment").unwrap();
+
+        let subgraph_store = self.store.subgraph_store();
+        let locator = self.active_locator(&deployment)?;
+        if pause {
+            subgraph_store.pause_subgraph(&locator)?;
+        } else {
+            subgraph_store.resume_subgraph(&locator)?;
+        }
+        info!(
+            self.logger,
+            "{} deployment", if pause { "Paused" } else { "Resumed" };
+            "deployment" => &deployment
+        );
+
+        let (node, paused) = subgraph_store
+            .assignment_status(&locator)?
+            .ok_or_else(|| QueryExecutionError::DeploymentNotFound(deployment.to_string()))?;
+        Ok(object! {
+            __typename: "DeploymentAssignment",
+            deployment: deployment.to_string(),
+            node: node.to_string(),
+            paused: paused,
+        })
+    }
+
+    /// Rewinds the paused deployment in the `deployment` argument to the
+    /// block given by `blockNumber` and `blockHash`, which must be an
+    /// ancestor of the deployment's current block
+    fn resolve_rewind_deployment(&self, field: &a::Field) -> Result<r::Value, QueryExecutionError> {
+        self.check_mutation_access(field)?;
+
+        // We can safely unwrap because the arguments are non-nullable and have been validated.
+        let deployment = field.get_required::<DeploymentHash>("deployment").unwrap();
+        let number = field.get_required::<BlockNumber>("blockNumber").unwrap();
+        let hash = field.get_required::<BlockHash>("blockHash").unwrap();
+        let block_ptr_to = BlockPtr::new(hash, number);
+
+        let invalid = |reason: String| {
+            QueryExecutionError::ValidationError(
+                Some(field.position),
+                format!(
+                    "can not rewind {} to block {}: {}",
+                    deployment, block_ptr_to, reason
+                ),
+            )
+        };
+
+        let subgraph_store = self.store.subgraph_store();
+        let locator = self.active_locator(&deployment)?;
+
+        // The deployment would otherwise keep indexing while we remove its
+        // data from under it
+        if let Some((_, false)) = subgraph_store.assignment_status(&locator)? {
+            return Err(invalid(
+                "the deployment is indexing and must be paused first".to_string(),
+            ));
+        }
+
+        let chain = self
+            .store
+            .status(status::Filter::Deployments(vec![deployment.to_string()]))?
+            .into_iter()
+            .next()
+            .and_then(|info| info.chains.into_iter().next())
+            .ok_or_else(|| QueryExecutionError::DeploymentNotFound(deployment.to_string()))?;
+        let latest = chain
+            .latest_block
+            .map(|block| block.to_ptr())
+            .ok_or_else(|| invalid("the deployment has not processed any blocks".to_string()))?;
+        if block_ptr_to.number >= latest.number {
+            return Err(invalid(format!(
+                "the deployment is at block {} and can only be rewound to an earlier block",
+                latest.number
+            )));
+        }
+
+        // Make sure we don't rewind to a block on a different fork; the
+        // ancestor of the deployment's block at the target block number must
+        // be the target block
+        let chain_store = self
+            .store
+            .block_store()
+            .chain_store(&chain.network)
+            .ok_or_else(|| invalid(format!("network {} is not supported", chain.network)))?;
+        let ancestor = futures::executor::block_on(
+            chain_store
+                .cheap_clone()
+                .ancestor_block(latest.clone(), latest.number - block_ptr_to.number),
+        )
+        .map_err(|e| invalid(e.to_string()))?;
+        let target = chain_store
+            .blocks(&[block_ptr_to.hash.clone()])
+            .map_err(|e| invalid(e.to_string()))?
+            .pop();
+        match (ancestor, target) {
+            (Some(ancestor), Some(target)) if ancestor == target => {}
+            (Some(_), Some(_)) => {
+                return Err(invalid(format!(
+                    "the block is not an ancestor of the deployment's block {}",
+                    latest
+                )))
+            }
+            _ => {
+                return Err(invalid(format!(
+                    "the block cache for {} is missing blocks needed to check that the \
+                     block is an ancestor of the deployment's block {}",
+                    chain.network, latest
+                )))
+            }
+        }
+
+        subgraph_store.rewind(&locator, block_ptr_to.clone())?;
+        info!(
+            self.logger,
+            "Rewound deployment";
+            "deployment" => &deployment,
+            "from" => latest.to_string(),
+            "to" => block_ptr_to.to_string()
+        );
+
+        Ok(object! {
+            __typename: "Block",
+            hash: block_ptr_to.hash_hex(),
+            number: block_ptr_to.number,
+        })
+    }
+
+    fn resolve_indexing_status_history(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let deployment = field.get_required::<DeploymentHash>("subgraph").unwrap();
+        let first = field.get_optional::<i32>("first").unwrap().unwrap_or(100);
+        let skip = field.get_optional::<i32>("skip").unwrap().unwrap_or(0);
+
+        debug!(
+            self.logger,
+            "Resolve indexing status history";
+            "deployment" => &deployment,
+        );
+
+        let history =
+            self.store
+                .health_history(&deployment, first.max(0) as usize, skip.max(0) as usize)?;
+
+        Ok(r::Value::List(
+            history
+                .into_iter()
+                .map(|transition| transition.into_value())
+                .collect(),
+        ))
+    }
+
+    fn resolve_failed_deployments(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let first = field.get_optional::<i32>("first").unwrap().unwrap_or(100);
+        let skip = field.get_optional::<i32>("skip").unwrap().unwrap_or(0);
+
+        debug!(self.logger, "Resolve failed deployments");
+
+        let mut infos = self.store.status(status::Filter::Failed)?;
+        // The store returns deployments grouped by shard; sort them so that pages are stable
+        infos.sort_by_key(|info| info.id.0);
+        Ok(r::Value::List(
+            infos
+                .into_iter()
+                .skip(skip.max(0) as usize)
+                .take(first.max(0) as usize)
+                .map(|info| status::FailedDeployment::from(info).into_value())
+                .collect(),
+        ))
+    }
+
+    fn resolve_subgraph_versions(&self, field: &a::Field) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let subgraph_name = field.get_required::<String>("subgraphName").unwrap();
+
+        debug!(
+            self.logger,
+            "Resolve subgraph versions";
+            "name" => &subgraph_name,
+        );
+
+        // A subgraph with an invalid name can't exist, and therefore has no versions
+        let versions = match SubgraphName::new(subgraph_name) {
+            Ok(name) => self.store.subgraph_store().subgraph_versions(&name)?,
+            Err(()) => vec![],
+        };
+
+        Ok(r::Value::List(
+            versions
+                .into_iter()
+                .map(|version| version.into_value())
+                .collect(),
+        ))
+    }
+
     async fn resolve_subgraph_features(
         &self,
         field: &a::Field,
     ) -> Result<r::Value, QueryExecutionError> {
         // We can safely unwrap because the argument is non-nullable and has been validated.
         let subgraph_id = field.get_required::<String>("subgraphId").unwrap();
-
-        // TODO:
-        //
-        // An interesting optimization would involve trying to get the subgraph manifest from the
-        // SubgraphStore before hitting IPFS, but we must fix a dependency cycle between the `graph`
-        // and `server` crates first.
-        //
-        // 1. implement a new method in subgraph store to retrieve the SubgraphManifest of a given deployment id
-        // 2. try to fetch this subgraph from our SubgraphStore before hitting IPFS
+        let data_sources = field
+            .get_optional::<Vec<String>>("dataSources")
+            .expect("Invalid dataSources");
 
         // Try to build a deployment hash with the input string
         let deployment_hash = DeploymentHash::new(subgraph_id).map_err(|invalid_qm_hash| {
             QueryExecutionError::SubgraphDeploymentIdError(invalid_qm_hash)
         })?;
 
+        self.subgraph_features(deployment_hash, data_sources).await
+    }
+
+    async fn resolve_subgraph_features_from_store(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let deployment = field.get_required::<String>("deployment").unwrap();
+        let deployment_hash = DeploymentHash::new(deployment).map_err(|invalid_qm_hash| {
+            QueryExecutionError::SubgraphDeploymentIdError(invalid_qm_hash)
+        })?;
+
+        let features = match self
+            .store
+            .subgraph_store()
+            .detected_features(&deployment_hash)?
+        {
+            Some(features) => r::Value::List(
+                features
+                    .iter()
+                    .map(ToString::to_string)
+                    .map(r::Value::String)
+                    .collect(),
+            ),
+            // The deployment is unknown or was created before its features were recorded, and
+            // we have to detect them from its manifest
+            None => match self
+                .subgraph_features(deployment_hash.clone(), None)
+                .await?
+            {
+                r::Value::Object(response) => response
+                    .get("features")
+                    .cloned()
+                    .unwrap_or(r::Value::List(vec![])),
+                _ => r::Value::List(vec![]),
+            },
+        };
+
+        Ok(object! {
+            __typename: "DeploymentFeatures",
+            deployment: deployment_hash.to_string(),
+            features: features,
+        })
+    }
+
+    /// Detects and validates the features of the deployment from its manifest, and reports them
+    /// together with other information from the manifest
+    async fn subgraph_features(
+        &self,
+        deployment_hash: DeploymentHash,
+        data_sources: Option<Vec<String>>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // Only the features of the whole subgraph are cached; requests for some of its data
+        // sources are rare enough that they always do the full work
+        let cache_key = data_sources.is_none().then(|| deployment_hash.clone());
+        if let Some(cache_key) = &cache_key {
+            if let Some(response) = self.subgraph_features_cache.lock().unwrap().get(cache_key) {
+                return Ok(response.clone());
+            }
+        }
+
+        // Either way, the manifest is resolved and validated the same way
+        let file_bytes = original_manifest(
+            self.store.subgraph_store().raw_manifest(&deployment_hash)?,
+            &self.link_resolver,
+            &self.logger,
+            &deployment_hash,
+        )
+        .await?;
+        let raw: serde_yaml::Mapping = serde_yaml::from_slice(&file_bytes)
+            .map_err(SubgraphManifestResolveError::ParseError)?;
+
+        let response = self
+            .manifest_features(deployment_hash, &file_bytes, raw, data_sources.as_deref())
+            .await?;
+
+        if let Some(cache_key) = cache_key {
+            self.subgraph_features_cache
+                .lock()
+                .unwrap()
+                .insert(cache_key, response.clone());
+        }
+
+        Ok(response)
+    }
+
+    /// Like `subgraphFeatures`, but for a manifest that is passed in directly instead of a
+    /// deployment, so that it can be checked before it is deployed anywhere. Files that the
+    /// manifest links to are still resolved through IPFS. The results are not cached since the
+    /// manifest is likely to change until it is deployed.
+    async fn resolve_subgraph_features_for_manifest(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        // We can safely unwrap because the argument is non-nullable and has been validated.
+        let manifest = field.get_required::<String>("manifestYaml").unwrap();
+
+        let raw: serde_yaml::Mapping =
+            serde_yaml::from_str(&manifest).map_err(SubgraphManifestResolveError::ParseError)?;
+
+        // The manifest has no deployment hash yet; resolving it needs one, and one derived
+        // from its contents can't be confused with a real IPFS hash
+        let hash = blake3::hash(manifest.as_bytes()).to_hex();
+        let deployment_hash = DeploymentHash::new(format!("manifest_{}", &hash[..32]))
+            .expect("the placeholder deployment hash is valid");
+
+        self.manifest_features(deployment_hash, manifest.as_bytes(), raw, None)
+            .await
+    }
+
+    /// Detects and validates the features of the `raw` manifest of `deployment_hash`, and
+    /// reports them together with other information from the manifest. `manifest_bytes` is the
+    /// manifest as it was fetched, and is only used to report its hash
+    async fn manifest_features(
+        &self,
+        deployment_hash: DeploymentHash,
+        manifest_bytes: &[u8],
+        raw: serde_yaml::Mapping,
+        data_sources: Option<&[String]>,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let manifest_hash = sha256_hex(manifest_bytes);
+        // Without a schema, resolving the manifest below fails anyway
+        let schema_link = raw
+            .get(&serde_yaml::Value::from("schema"))
+            .cloned()
+            .and_then(|schema| serde_yaml::from_value::<UnresolvedSchema>(schema).ok())
+            .map(|schema| schema.file);
+
         let ValidationPostProcessResult {
             features,
             errors,
             network,
+            spec_version,
+            api_version,
+            manifest_data_sources,
+            templates,
         } = {
-            let raw: serde_yaml::Mapping = {
-                let file_bytes = self
-                    .link_resolver
-                    .cat(&self.logger, &deployment_hash.to_ipfs_link())
-                    .await
-                    .map_err(SubgraphManifestResolveError::ResolveError)?;
-
-                serde_yaml::from_slice(&file_bytes)
-                    .map_err(SubgraphManifestResolveError::ParseError)?
-            };
-
             let kind = BlockchainKind::from_manifest(&raw)
                 .map_err(SubgraphManifestResolveError::ResolveError)?;
             match kind {
@@ -471,6 +1514,7 @@ impl<S: Store> IndexNodeResolver<S> {
                     validate_and_extract_features(
                         &self.store.subgraph_store(),
                         unvalidated_subgraph_manifest,
+                        data_sources,
                     )
                     .await?
                 }
@@ -489,6 +1533,7 @@ impl<S: Store> IndexNodeResolver<S> {
                     validate_and_extract_features(
                         &self.store.subgraph_store(),
                         unvalidated_subgraph_manifest,
+                        data_sources,
                     )
                     .await?
                 }
@@ -507,6 +1552,7 @@ impl<S: Store> IndexNodeResolver<S> {
                     validate_and_extract_features(
                         &self.store.subgraph_store(),
                         unvalidated_subgraph_manifest,
+                        data_sources,
                     )
                     .await?
                 }
@@ -525,39 +1571,98 @@ impl<S: Store> IndexNodeResolver<S> {
                     validate_and_extract_features(
                         &self.store.subgraph_store(),
                         unvalidated_subgraph_manifest,
+                        data_sources,
                     )
                     .await?
                 }
             }
         };
 
+        // Resolving the manifest fetched the schema, and the link resolver has it cached
+        let schema_hash = match schema_link {
+            Some(link) => {
+                let schema_bytes = self
+                    .link_resolver
+                    .cat(&self.logger, &link)
+                    .await
+                    .map_err(SubgraphManifestResolveError::ResolveError)?;
+                r::Value::String(sha256_hex(&schema_bytes))
+            }
+            None => r::Value::Null,
+        };
+
         // We then bulid a GraphqQL `Object` value that contains the feature detection and
         // validation results and send it back as a response.
         let response = [
             ("features".to_string(), features),
             ("errors".to_string(), errors),
             ("network".to_string(), network),
+            ("specVersion".to_string(), spec_version),
+            ("apiVersion".to_string(), api_version),
+            ("dataSources".to_string(), manifest_data_sources),
+            ("templates".to_string(), templates),
+            ("manifestHash".to_string(), r::Value::String(manifest_hash)),
+            ("schemaHash".to_string(), schema_hash),
         ];
-        let response = Object::from_iter(response);
+        Ok(r::Value::Object(Object::from_iter(response)))
+    }
+}
 
-        Ok(r::Value::Object(response))
+/// The manifest of `deployment_hash` exactly as it was deployed. Deployments that this node
+/// already knows about have it `stored`, and only unknown deployments need to go to IPFS for it.
+/// Since the store keeps the file that the registrar fetched from IPFS, both give the same bytes
+async fn original_manifest(
+    stored: Option<String>,
+    link_resolver: &Arc<dyn LinkResolver>,
+    logger: &Logger,
+    deployment_hash: &DeploymentHash,
+) -> Result<Vec<u8>, QueryExecutionError> {
+    match stored {
+        Some(raw_yaml) => Ok(raw_yaml.into_bytes()),
+        None => Ok(link_resolver
+            .cat(logger, &deployment_hash.to_ipfs_link())
+            .await
+            .map_err(SubgraphManifestResolveError::ResolveError)?),
     }
 }
 
+/// The SHA-256 hash of `bytes` as a hex string
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
 struct ValidationPostProcessResult {
     features: r::Value,
     errors: r::Value,
     network: r::Value,
+    spec_version: r::Value,
+    api_version: r::Value,
+    manifest_data_sources: r::Value,
+    templates: r::Value,
 }
 
 async fn validate_and_extract_features<C, SgStore>(
     subgraph_store: &Arc<SgStore>,
     unvalidated_subgraph_manifest: UnvalidatedSubgraphManifest<C>,
+    data_sources: Option<&[String]>,
 ) -> Result<ValidationPostProcessResult, QueryExecutionError>
 where
     C: Blockchain,
     SgStore: SubgraphStore,
 {
+    // Validation consumes the manifest, and the versions are reported even if it fails. When
+    // data sources use different mapping API versions, the manifest is invalid and we report
+    // the newest one
+    let spec_version = r::Value::String(unvalidated_subgraph_manifest.spec_version().to_string());
+    let api_version = unvalidated_subgraph_manifest
+        .api_versions()
+        .max()
+        .map_or(r::Value::Null, |version| {
+            r::Value::String(version.to_string())
+        });
+    let (manifest_data_sources, templates) =
+        data_sources_to_graphql(&unvalidated_subgraph_manifest, data_sources);
+
     // Validate the subgraph we've just obtained.
     //
     // Note that feature valiadation errors will be inside the error variant vector (because
@@ -600,7 +1705,19 @@ where
     //
     // For this step we must collect whichever results we have into GraphQL `Value` types.
     match subgraph_validation {
-        Either::Left(subgraph_manifest) => {
+        Either::Left(mut subgraph_manifest) => {
+            // Only look at the requested data sources and templates; features that are
+            // declared for the whole subgraph, like grafting, are still detected
+            if let Some(names) = data_sources {
+                let requested = |name: &str| names.iter().any(|n| n == name);
+                subgraph_manifest
+                    .data_sources
+                    .retain(|ds| requested(ds.name()));
+                subgraph_manifest
+                    .templates
+                    .retain(|template| requested(template.name()));
+            }
+
             let features = r::Value::List(
                 detect_features(&subgraph_manifest)
                     .map_err(|_| QueryExecutionError::InvalidSubgraphManifest)?
@@ -616,6 +1733,10 @@ where
                 features,
                 errors,
                 network,
+                spec_version,
+                api_version,
+                manifest_data_sources,
+                templates,
             })
         }
         Either::Right(errors) => {
@@ -632,11 +1753,52 @@ where
                 features,
                 errors,
                 network,
+                spec_version,
+                api_version,
+                manifest_data_sources,
+                templates,
             })
         }
     }
 }
 
+/// Lists the data sources and templates of a manifest with what operators need to know to
+/// index them, like their network and contract address. Like features, the list is limited to
+/// the requested data sources and templates
+fn data_sources_to_graphql<C: Blockchain>(
+    manifest: &UnvalidatedSubgraphManifest<C>,
+    requested: Option<&[String]>,
+) -> (r::Value, r::Value) {
+    let requested = |name: &str| requested.map_or(true, |names| names.iter().any(|n| n == name));
+    let data_sources = manifest
+        .data_sources()
+        .iter()
+        .filter(|ds| requested(ds.name()))
+        .map(|ds| {
+            object! {
+                name: ds.name(),
+                kind: ds.kind(),
+                network: ds.network(),
+                address: ds.address(),
+                startBlock: ds.start_block(),
+            }
+        })
+        .collect();
+    let templates = manifest
+        .templates()
+        .iter()
+        .filter(|template| requested(template.name()))
+        .map(|template| {
+            object! {
+                name: template.name(),
+                kind: template.kind(),
+                network: template.network(),
+            }
+        })
+        .collect();
+    (r::Value::List(data_sources), r::Value::List(templates))
+}
+
 fn entity_changes_to_graphql(entity_changes: Vec<EntityOperation>) -> r::Value {
     // Results are sorted first alphabetically by entity type, then by entity
     // ID, and then aphabetically by field name.
@@ -705,6 +1867,11 @@ impl<S: Store> Clone for IndexNodeResolver<S> {
             store: self.store.clone(),
             link_resolver: self.link_resolver.clone(),
             bearer_token: self.bearer_token.clone(),
+            subgraph_features_cache: self.subgraph_features_cache.clone(),
+            deployment_size_cache: self.deployment_size_cache.clone(),
+            sync_rates: self.sync_rates.clone(),
+            metrics: self.metrics.clone(),
+            changed_deployment: self.changed_deployment.clone(),
         }
     }
 }
@@ -714,7 +1881,12 @@ impl<S: Store> Resolver for IndexNodeResolver<S> {
     const CACHEABLE: bool = false;
 
     async fn query_permit(&self) -> Result<tokio::sync::OwnedSemaphorePermit, QueryExecutionError> {
-        self.store.query_permit().await.map_err(Into::into)
+        let timeout = ENV_VARS.index_node_query_permit_timeout;
+        trace!(self.logger, "Acquiring query permit");
+        tokio::time::timeout(timeout, self.store.query_permit())
+            .await
+            .map_err(|_| QueryExecutionError::QueryPermitTimeout(timeout))?
+            .map_err(Into::into)
     }
 
     fn prefetch(
@@ -733,20 +1905,27 @@ impl<S: Store> Resolver for IndexNodeResolver<S> {
         scalar_type: &s::ScalarType,
         value: Option<r::Value>,
     ) -> Result<r::Value, QueryExecutionError> {
-        match (
+        let start = Instant::now();
+        let result = match (
             parent_object_type.name.as_str(),
             field.name.as_str(),
             scalar_type.name.as_str(),
         ) {
             ("Query", "proofOfIndexing", "Bytes") => self.resolve_proof_of_indexing(field),
+            ("Query", "publicProofOfIndexing", "Bytes") => {
+                self.resolve_public_proof_of_indexing(field)
+            }
             ("Query", "blockData", "JSONObject") => self.resolve_block_data(field),
 
             // Fallback to the same as is in the default trait implementation. There
             // is no way to call back into the default implementation for the trait.
             // So, note that this is duplicated.
             // See also c2112309-44fd-4a84-92a0-5a651e6ed548
-            _ => Ok(value.unwrap_or(r::Value::Null)),
-        }
+            _ => return Ok(value.unwrap_or(r::Value::Null)),
+        };
+        self.metrics
+            .observe_field(&field.name, start.elapsed(), &result);
+        result
     }
 
     fn resolve_objects(
@@ -757,16 +1936,39 @@ impl<S: Store> Resolver for IndexNodeResolver<S> {
         object_type: ObjectOrInterface<'_>,
     ) -> Result<r::Value, QueryExecutionError> {
         // Resolves the `field.name` top-level field.
-        match (prefetched_objects, object_type.name(), field.name.as_str()) {
+        let start = Instant::now();
+        let result = match (prefetched_objects, object_type.name(), field.name.as_str()) {
             (None, "SubgraphIndexingStatus", "indexingStatuses") => {
                 self.resolve_indexing_statuses(field)
             }
             (None, "SubgraphIndexingStatus", "indexingStatusesForSubgraphName") => {
                 self.resolve_indexing_statuses_for_subgraph_name(field)
             }
+            (None, "SubgraphIndexingStatus", "indexingStatusesForSubgraphNames") => {
+                self.resolve_indexing_statuses_for_subgraph_names(field)
+            }
+            (None, "HealthTransition", "indexingStatusHistory") => {
+                self.resolve_indexing_status_history(field)
+            }
+            (None, "SubgraphVersion", "subgraphVersions") => self.resolve_subgraph_versions(field),
+            (None, "FailedDeployment", "failedDeployments") => {
+                self.resolve_failed_deployments(field)
+            }
             (None, "CachedEthereumCall", "cachedEthereumCalls") => {
                 self.resolve_cached_ethereum_calls(field)
             }
+            (None, "NetworkInfo", "chains") => self.resolve_chains(),
+            (None, "PendingAssignment", "pendingAssignments") => self.resolve_pending_assignments(),
+
+            // The top-level `proofOfIndexingForBlocks` field
+            (None, "ProofOfIndexingResult", "proofOfIndexingForBlocks") => {
+                self.resolve_proof_of_indexing_for_blocks(field)
+            }
+
+            // The top-level `proofOfIndexingRange` field
+            (None, "ProofOfIndexingResult", "proofOfIndexingRange") => {
+                self.resolve_proof_of_indexing_range(field)
+            }
 
             // The top-level `publicProofsOfIndexing` field
             (None, "PublicProofOfIndexingResult", "publicProofsOfIndexing") => {
@@ -774,8 +1976,11 @@ impl<S: Store> Resolver for IndexNodeResolver<S> {
             }
 
             // Resolve fields of `Object` values (e.g. the `chains` field of `ChainIndexingStatus`)
-            (value, _, _) => Ok(value.unwrap_or(r::Value::Null)),
-        }
+            (value, _, _) => return Ok(value.unwrap_or(r::Value::Null)),
+        };
+        self.metrics
+            .observe_field(&field.name, start.elapsed(), &result);
+        result
     }
 
     fn resolve_object(
@@ -786,18 +1991,105 @@ impl<S: Store> Resolver for IndexNodeResolver<S> {
         _object_type: ObjectOrInterface<'_>,
     ) -> Result<r::Value, QueryExecutionError> {
         // Resolves the `field.name` top-level field.
-        match (prefetched_object, field.name.as_str()) {
+        let start = Instant::now();
+        let result = match (prefetched_object, field.name.as_str()) {
             (None, "indexingStatusForCurrentVersion") => {
                 self.resolve_indexing_status_for_version(field, true)
             }
             (None, "indexingStatusForPendingVersion") => {
                 self.resolve_indexing_status_for_version(field, false)
             }
+            (None, "indexingStatusForDeployment") => {
+                self.resolve_indexing_status_for_deployment(field)
+            }
             (None, "subgraphFeatures") => graph::block_on(self.resolve_subgraph_features(field)),
+            (None, "subgraphFeaturesFromStore") => {
+                graph::block_on(self.resolve_subgraph_features_from_store(field))
+            }
+            (None, "subgraphFeaturesForManifest") => {
+                graph::block_on(self.resolve_subgraph_features_for_manifest(field))
+            }
             (None, "entityChangesInBlock") => self.resolve_entity_changes_in_block(field),
+            (None, "nodeHealth") => Ok(self.resolve_node_health()),
+            (None, "pauseDeployment") => self.resolve_pause_deployment(field, true),
+            (None, "resumeDeployment") => self.resolve_pause_deployment(field, false),
+            (None, "rewindDeployment") => self.resolve_rewind_deployment(field),
+            (None, "proofOfIndexingWithBlock") => self.resolve_proof_of_indexing_with_block(field),
+            (None, "indexingStatusChanged") => self.resolve_indexing_status_changed(field),
 
             // Resolve fields of `Object` values (e.g. the `latestBlock` field of `EthereumBlock`)
-            (value, _) => Ok(value.unwrap_or(r::Value::Null)),
+            (value, _) => return Ok(value.unwrap_or(r::Value::Null)),
+        };
+        self.metrics
+            .observe_field(&field.name, start.elapsed(), &result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::prelude::*;
+    use std::time::Duration;
+
+    use super::{original_manifest, sha256_hex};
+
+    /// A manifest with comments and flow mappings that a YAML round trip wouldn't keep
+    const MANIFEST: &str = "# Deployed as written, comments and all\n\
+                            specVersion: 0.0.4\n\
+                            schema: { file: { \"/\": \"/ipfs/QmSchema\" } }\n\
+                            dataSources: []\n";
+    const MANIFEST_SHA256: &str =
+        "ca2c823d0c01bd1de41ad7a1f22b2af0debff5897c400699b205585bd004df49";
+
+    /// Serves `MANIFEST` for every link, like IPFS for a deployment this node doesn't know
+    #[derive(Debug)]
+    struct ManifestResolver;
+
+    #[async_trait]
+    impl LinkResolver for ManifestResolver {
+        fn with_timeout(&self, _timeout: Duration) -> Box<dyn LinkResolver> {
+            Box::new(ManifestResolver)
+        }
+
+        fn with_retries(&self) -> Box<dyn LinkResolver> {
+            Box::new(ManifestResolver)
+        }
+
+        async fn cat(&self, _logger: &Logger, _link: &Link) -> Result<Vec<u8>, Error> {
+            Ok(MANIFEST.as_bytes().to_vec())
+        }
+
+        async fn get_block(&self, _logger: &Logger, _link: &Link) -> Result<Vec<u8>, Error> {
+            unimplemented!()
         }
+
+        async fn json_stream(
+            &self,
+            _logger: &Logger,
+            _link: &Link,
+        ) -> Result<JsonValueStream, Error> {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn manifest_hash_is_the_same_for_stored_and_ipfs_manifests() {
+        let logger = Logger::root(slog::Discard, o!());
+        let link_resolver: Arc<dyn LinkResolver> = Arc::new(ManifestResolver);
+        let hash = DeploymentHash::new("QmManifest").unwrap();
+
+        let stored = futures::executor::block_on(original_manifest(
+            Some(MANIFEST.to_string()),
+            &link_resolver,
+            &logger,
+            &hash,
+        ))
+        .unwrap();
+        let from_ipfs =
+            futures::executor::block_on(original_manifest(None, &link_resolver, &logger, &hash))
+                .unwrap();
+
+        assert_eq!(MANIFEST_SHA256, sha256_hex(&stored));
+        assert_eq!(MANIFEST_SHA256, sha256_hex(&from_ipfs));
     }
 }
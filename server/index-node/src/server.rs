@@ -5,10 +5,11 @@ use std::net::{Ipv4Addr, SocketAddrV4};
 
 use graph::{
     blockchain::BlockchainMap,
-    components::store::Store,
+    components::store::{Store, SubscriptionManager},
     prelude::{IndexNodeServer as IndexNodeServerTrait, *},
 };
 
+use crate::metrics::IndexNodeMetrics;
 use crate::service::IndexNodeService;
 use thiserror::Error;
 
@@ -31,7 +32,9 @@ pub struct IndexNodeServer<Q, S> {
     blockchain_map: Arc<BlockchainMap>,
     graphql_runner: Arc<Q>,
     store: Arc<S>,
+    subscription_manager: Arc<dyn SubscriptionManager>,
     link_resolver: Arc<dyn LinkResolver>,
+    metrics: Arc<IndexNodeMetrics>,
 }
 
 impl<Q, S> IndexNodeServer<Q, S> {
@@ -41,7 +44,9 @@ impl<Q, S> IndexNodeServer<Q, S> {
         blockchain_map: Arc<BlockchainMap>,
         graphql_runner: Arc<Q>,
         store: Arc<S>,
+        subscription_manager: Arc<dyn SubscriptionManager>,
         link_resolver: Arc<dyn LinkResolver>,
+        metrics_registry: Arc<dyn MetricsRegistry>,
     ) -> Self {
         let logger = logger_factory.component_logger(
             "IndexNodeServer",
@@ -51,13 +56,16 @@ impl<Q, S> IndexNodeServer<Q, S> {
                 }),
             }),
         );
+        let metrics = Arc::new(IndexNodeMetrics::new(metrics_registry));
 
         IndexNodeServer {
             logger,
             blockchain_map,
             graphql_runner,
             store,
+            subscription_manager,
             link_resolver,
+            metrics,
         }
     }
 }
@@ -92,7 +100,9 @@ where
             self.blockchain_map.clone(),
             graphql_runner.clone(),
             store.clone(),
+            self.subscription_manager.clone(),
             self.link_resolver.clone(),
+            self.metrics.clone(),
         );
         let new_service =
             make_service_fn(move |_| futures03::future::ok::<_, Error>(service.clone()));
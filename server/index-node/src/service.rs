@@ -1,39 +1,52 @@
 use graph::blockchain::BlockchainMap;
 use http::header::{
     self, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
-    CONTENT_TYPE, LOCATION,
+    CACHE_CONTROL, CONTENT_TYPE, LOCATION,
 };
 use hyper::body::Bytes;
 use hyper::service::Service;
 use hyper::{Body, Method, Request, Response, StatusCode};
+use lru_time_cache::LruCache;
 use serde_json;
+use std::sync::Mutex;
 use std::task::Context;
 use std::task::Poll;
 
-use graph::components::{server::query::GraphQLServerError, store::Store};
+use graph::components::server::query::GraphQLServerError;
+use graph::components::store::{Store, SubscriptionManager};
+use graph::data::graphql::ValueMap;
 use graph::data::query::QueryResults;
 use graph::prelude::*;
-use graph_graphql::prelude::{execute_query, Query as PreparedQuery, QueryExecutionOptions};
+use graph_graphql::prelude::{
+    execute_query, execute_subscription_once, Query as PreparedQuery, QueryExecutionOptions,
+};
 use graphql_parser;
 
 use crate::auth::bearer_token;
 
 use crate::explorer::Explorer;
-use crate::resolver::IndexNodeResolver;
+use crate::metrics::IndexNodeMetrics;
+use crate::resolver::{DeploymentSizeCache, IndexNodeResolver, SubgraphFeaturesCache};
 use crate::schema::SCHEMA;
+use crate::status_watch::status_changes;
+use crate::sync_rate::SyncRates;
 
 /// An asynchronous response to a GraphQL request.
 pub type IndexNodeServiceResponse = DynTryFuture<'static, Response<Body>, GraphQLServerError>;
 
 /// A Hyper Service that serves GraphQL over a POST / endpoint.
-#[derive(Debug)]
 pub struct IndexNodeService<Q, S> {
     logger: Logger,
     blockchain_map: Arc<BlockchainMap>,
     graphql_runner: Arc<Q>,
     store: Arc<S>,
+    subscription_manager: Arc<dyn SubscriptionManager>,
     explorer: Arc<Explorer<S>>,
     link_resolver: Arc<dyn LinkResolver>,
+    subgraph_features_cache: SubgraphFeaturesCache,
+    deployment_size_cache: DeploymentSizeCache,
+    sync_rates: Arc<SyncRates>,
+    metrics: Arc<IndexNodeMetrics>,
 }
 
 impl<Q, S> Clone for IndexNodeService<Q, S> {
@@ -43,8 +56,13 @@ impl<Q, S> Clone for IndexNodeService<Q, S> {
             blockchain_map: self.blockchain_map.clone(),
             graphql_runner: self.graphql_runner.clone(),
             store: self.store.clone(),
+            subscription_manager: self.subscription_manager.clone(),
             explorer: self.explorer.clone(),
             link_resolver: self.link_resolver.clone(),
+            subgraph_features_cache: self.subgraph_features_cache.clone(),
+            deployment_size_cache: self.deployment_size_cache.clone(),
+            sync_rates: self.sync_rates.clone(),
+            metrics: self.metrics.clone(),
         }
     }
 }
@@ -62,17 +80,33 @@ where
         blockchain_map: Arc<BlockchainMap>,
         graphql_runner: Arc<Q>,
         store: Arc<S>,
+        subscription_manager: Arc<dyn SubscriptionManager>,
         link_resolver: Arc<dyn LinkResolver>,
+        metrics: Arc<IndexNodeMetrics>,
     ) -> Self {
         let explorer = Arc::new(Explorer::new(store.clone()));
+        let subgraph_features_cache =
+            Arc::new(Mutex::new(LruCache::with_expiry_duration_and_capacity(
+                ENV_VARS.subgraph_features_cache_ttl,
+                ENV_VARS.subgraph_features_cache_size,
+            )));
+        let deployment_size_cache = Arc::new(Mutex::new(LruCache::with_expiry_duration(
+            ENV_VARS.deployment_size_cache_ttl,
+        )));
+        let sync_rates = Arc::new(SyncRates::new(ENV_VARS.sync_rate_window));
 
         IndexNodeService {
             logger,
             blockchain_map,
             graphql_runner,
             store,
+            subscription_manager,
             explorer,
             link_resolver,
+            subgraph_features_cache,
+            deployment_size_cache,
+            sync_rates,
+            metrics,
         }
     }
 
@@ -108,7 +142,6 @@ where
         request: Request<Body>,
     ) -> Result<Response<Body>, GraphQLServerError> {
         let (req_parts, req_body) = request.into_parts();
-        let store = self.store.clone();
 
         // Obtain the schema for the index node GraphQL API
         let schema = SCHEMA.clone();
@@ -125,26 +158,14 @@ where
             Err(e) => return Ok(QueryResults::from(QueryResult::from(e)).as_http_response()),
         };
 
-        let load_manager = self.graphql_runner.load_manager();
+        if query.is_subscription() {
+            return Ok(self.handle_graphql_subscription(query, validated.bearer_token));
+        }
 
         // Run the query using the index node resolver
         let query_clone = query.cheap_clone();
-        let logger = self.logger.cheap_clone();
         let result = {
-            let resolver = IndexNodeResolver::new(
-                &logger,
-                store,
-                self.link_resolver.clone(),
-                validated.bearer_token,
-                self.blockchain_map.clone(),
-            );
-            let options = QueryExecutionOptions {
-                resolver,
-                deadline: None,
-                max_first: std::u32::MAX,
-                max_skip: std::u32::MAX,
-                load_manager,
-            };
+            let options = self.execution_options(validated.bearer_token);
             let result = execute_query(query_clone.cheap_clone(), None, None, options).await;
             query_clone.log_execution(0);
             QueryResult::from(
@@ -156,6 +177,84 @@ where
         Ok(QueryResults::from(result).as_http_response())
     }
 
+    /// Serves a subscription as a stream of server-sent events, one for
+    /// every result. `indexingStatusChanged` is the only subscription, and
+    /// validation has made sure that the query selects just that field
+    fn handle_graphql_subscription(
+        &self,
+        query: Arc<PreparedQuery>,
+        bearer_token: Option<String>,
+    ) -> Response<Body> {
+        let deployments = query
+            .selection_set
+            .single_field()
+            .and_then(|field| field.get_optional::<Vec<String>>("subgraphs").unwrap())
+            .unwrap_or_default();
+
+        let changes = match status_changes(
+            &self.logger,
+            self.store.clone(),
+            self.subscription_manager.clone(),
+            deployments,
+        ) {
+            Ok(changes) => changes,
+            Err(e) => {
+                let e = QueryExecutionError::from(e);
+                return QueryResults::from(QueryResult::from(e)).as_http_response();
+            }
+        };
+
+        let service = self.cheap_clone();
+        let events = changes
+            .flat_map(futures03::stream::iter)
+            .then(move |deployment| {
+                let options = service.execution_options(bearer_token.clone());
+                let options = QueryExecutionOptions {
+                    resolver: options.resolver.for_status_change(deployment),
+                    ..options
+                };
+                execute_subscription_once(query.cheap_clone(), options)
+            })
+            .map(|result| {
+                let results = QueryResults::from(result);
+                serde_json::to_string(&results).map(|json| format!("data: {}\n\n", json))
+            });
+
+        Response::builder()
+            .status(200)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .header(CONTENT_TYPE, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache")
+            .body(Body::wrap_stream(events))
+            .unwrap()
+    }
+
+    /// The options to execute a query or subscription with the index node
+    /// resolver
+    fn execution_options(
+        &self,
+        bearer_token: Option<String>,
+    ) -> QueryExecutionOptions<IndexNodeResolver<S>> {
+        let resolver = IndexNodeResolver::new(
+            &self.logger,
+            self.store.clone(),
+            self.link_resolver.clone(),
+            bearer_token,
+            self.blockchain_map.clone(),
+            self.subgraph_features_cache.clone(),
+            self.deployment_size_cache.clone(),
+            self.sync_rates.clone(),
+            self.metrics.clone(),
+        );
+        QueryExecutionOptions {
+            resolver,
+            deadline: None,
+            max_first: std::u32::MAX,
+            max_skip: std::u32::MAX,
+            load_manager: self.graphql_runner.load_manager(),
+        }
+    }
+
     // Handles OPTIONS requests
     fn handle_graphql_options(_request: Request<Body>) -> Response<Body> {
         Response::builder()
@@ -0,0 +1,140 @@
+//! Watching deployments for changes in their indexing status, which is what
+//! the `indexingStatusChanged` subscription reports
+
+use std::collections::{BTreeSet, HashMap};
+
+use graph::components::store::{EntityType, Store, SubscriptionManager, UnitStream};
+use graph::data::graphql::ext::DocumentExt;
+use graph::data::subgraph::schema::SubgraphHealth;
+use graph::data::subgraph::status;
+use graph::prelude::futures03::stream::{self, BoxStream};
+use graph::prelude::*;
+
+/// The parts of an indexing status that we report changes of
+#[derive(Debug, PartialEq)]
+struct WatchedStatus {
+    health: SubgraphHealth,
+    latest_blocks: Vec<Option<BlockNumber>>,
+}
+
+impl From<&status::Info> for WatchedStatus {
+    fn from(info: &status::Info) -> Self {
+        WatchedStatus {
+            health: info.health,
+            latest_blocks: info
+                .chains
+                .iter()
+                .map(|chain| chain.latest_block.as_ref().map(|block| block.number()))
+                .collect(),
+        }
+    }
+}
+
+struct StatusWatch<S> {
+    logger: Logger,
+    store: Arc<S>,
+    /// The deployments to watch; all of them if this is empty
+    deployments: Vec<String>,
+    triggers: BoxStream<'static, ()>,
+    last: HashMap<String, WatchedStatus>,
+    started: bool,
+}
+
+impl<S: Store> StatusWatch<S> {
+    /// Wait for the next store notification or poll, and for whatever else
+    /// arrives during the debounce interval. Returns `false` if there will
+    /// be no more notifications
+    async fn wait(&mut self) -> bool {
+        if self.triggers.next().await.is_none() {
+            return false;
+        }
+        tokio::time::sleep(ENV_VARS.index_node_status_debounce).await;
+        while let Some(Some(())) = self.triggers.next().now_or_never() {}
+        true
+    }
+
+    /// Load the current statuses and return the deployments whose health
+    /// or latest block differs from what we saw the last time
+    async fn changes(&mut self) -> Result<Vec<String>, StoreError> {
+        let store = self.store.cheap_clone();
+        let filter = status::Filter::Deployments(self.deployments.clone());
+        let infos = graph::spawn_blocking_allow_panic(move || store.status(filter))
+            .await
+            .map_err(|e| StoreError::Unknown(anyhow!("loading statuses panicked: {}", e)))??;
+
+        let mut changed = Vec::new();
+        let mut current = HashMap::new();
+        for info in &infos {
+            let status = WatchedStatus::from(info);
+            if self.last.get(&info.subgraph) != Some(&status) {
+                changed.push(info.subgraph.clone());
+            }
+            current.insert(info.subgraph.clone(), status);
+        }
+        self.last = current;
+        Ok(changed)
+    }
+
+    /// The deployments that changed since the last time. The first call
+    /// returns all watched deployments without waiting
+    async fn next(&mut self) -> Option<Vec<String>> {
+        loop {
+            if self.started && !self.wait().await {
+                return None;
+            }
+            self.started = true;
+            match self.changes().await {
+                Ok(changed) if !changed.is_empty() => return Some(changed),
+                Ok(_) => {}
+                Err(e) => {
+                    warn!(self.logger, "Failed to load indexing statuses"; "error" => e.to_string())
+                }
+            }
+        }
+    }
+}
+
+/// Returns a stream of the hashes of the watched deployments whose health
+/// or latest block changed. An empty `deployments` watches all deployments.
+///
+/// We look at the statuses whenever the store sends a notification about a
+/// change to one of the deployments, and also poll them, since failures and
+/// blocks without any entity changes do not cause notifications
+pub fn status_changes<S: Store>(
+    logger: &Logger,
+    store: Arc<S>,
+    subscription_manager: Arc<dyn SubscriptionManager>,
+    deployments: Vec<String>,
+) -> Result<BoxStream<'static, Vec<String>>, StoreError> {
+    let logger = logger.new(o!("component" => "StatusWatch"));
+
+    let mut filters = BTreeSet::new();
+    filters.insert(SubscriptionFilter::Assignment);
+    for info in store.status(status::Filter::Deployments(deployments.clone()))? {
+        let hash = DeploymentHash::new(info.subgraph.clone())
+            .map_err(|e| StoreError::Unknown(anyhow!("invalid deployment hash `{}`", e)))?;
+        let schema = store.subgraph_store().input_schema(&hash)?;
+        for object_type in schema.document.get_object_type_definitions() {
+            let entity_type = EntityType::new(object_type.name.clone());
+            filters.insert(SubscriptionFilter::Entities(hash.clone(), entity_type));
+        }
+    }
+    let notifications: UnitStream = subscription_manager.subscribe_no_payload(filters);
+    let polls = stream::unfold((), |()| async {
+        tokio::time::sleep(ENV_VARS.index_node_status_poll_interval).await;
+        Some(((), ()))
+    });
+
+    let watch = StatusWatch {
+        logger,
+        store,
+        deployments,
+        triggers: stream::select(notifications, polls).boxed(),
+        last: HashMap::new(),
+        started: false,
+    };
+    Ok(stream::unfold(watch, |mut watch| async move {
+        watch.next().await.map(|changed| (changed, watch))
+    })
+    .boxed())
+}
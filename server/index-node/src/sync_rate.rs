@@ -0,0 +1,101 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use graph::prelude::BlockNumber;
+
+/// Don't take more than one sample per second for a deployment; samples
+/// that are closer together than that don't make the rate more accurate
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Tracks how fast deployments are syncing. Every time the index node
+/// reports the status of a deployment, it records the deployment's latest
+/// block, and computes the rate at which blocks were processed over the
+/// samples from the last `window`. The rate is therefore only known once
+/// the status of a deployment was queried at least twice
+#[derive(Debug)]
+pub(crate) struct SyncRates {
+    window: Duration,
+    samples: Mutex<HashMap<String, VecDeque<(Instant, BlockNumber)>>>,
+}
+
+impl SyncRates {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record that `deployment` is at `block` and return the number of
+    /// blocks it processed per second over the sampling window, or `None`
+    /// if there isn't enough history yet to compute a meaningful rate
+    pub fn record(&self, deployment: &str, block: BlockNumber) -> Option<f64> {
+        self.record_at(deployment, block, Instant::now())
+    }
+
+    fn record_at(&self, deployment: &str, block: BlockNumber, now: Instant) -> Option<f64> {
+        let mut samples = self.samples.lock().unwrap();
+        let samples = samples.entry(deployment.to_string()).or_default();
+
+        match samples.back() {
+            // The deployment was rewound, e.g. because of a reorg or by an
+            // operator; earlier samples say nothing about the current rate
+            Some((_, last_block)) if *last_block > block => samples.clear(),
+            Some((last_time, _)) if now.duration_since(*last_time) < MIN_SAMPLE_INTERVAL => {}
+            _ => samples.push_back((now, block)),
+        }
+        if samples.is_empty() {
+            samples.push_back((now, block));
+        }
+        while samples.len() > 2
+            && samples
+                .front()
+                .map_or(false, |(time, _)| now.duration_since(*time) > self.window)
+        {
+            samples.pop_front();
+        }
+
+        match (samples.front(), samples.back()) {
+            (Some((first_time, first_block)), Some((last_time, last_block)))
+                if last_time.duration_since(*first_time) >= MIN_SAMPLE_INTERVAL =>
+            {
+                let elapsed = last_time.duration_since(*first_time).as_secs_f64();
+                Some((last_block - first_block) as f64 / elapsed)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_rate_over_window() {
+        let rates = SyncRates::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(None, rates.record_at("a", 100, at(0)));
+        assert_eq!(Some(10.0), rates.record_at("a", 200, at(10)));
+        assert_eq!(Some(10.0), rates.record_at("a", 300, at(20)));
+        // Samples older than the window are forgotten
+        assert_eq!(Some(1.0), rates.record_at("a", 360, at(80)));
+        // Other deployments are tracked separately
+        assert_eq!(None, rates.record_at("b", 100, at(80)));
+    }
+
+    #[test]
+    fn rewind_resets_history() {
+        let rates = SyncRates::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        rates.record_at("a", 100, at(0));
+        assert_eq!(Some(10.0), rates.record_at("a", 200, at(10)));
+        assert_eq!(None, rates.record_at("a", 150, at(20)));
+        assert_eq!(Some(5.0), rates.record_at("a", 200, at(30)));
+    }
+}
@@ -0,0 +1,2 @@
+alter table subgraphs.subgraph_manifest
+      add column raw_yaml text;
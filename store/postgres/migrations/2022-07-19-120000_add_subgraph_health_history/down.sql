@@ -0,0 +1 @@
+drop table subgraphs.subgraph_health_history;
@@ -0,0 +1,11 @@
+create table subgraphs.subgraph_health_history (
+    vid          bigserial primary key,
+    id           int not null
+                 references subgraphs.subgraph_deployment(id) on delete cascade,
+    block_number int,
+    health       subgraphs.health not null,
+    error        text
+);
+
+create index subgraph_health_history_id
+    on subgraphs.subgraph_health_history(id, vid);
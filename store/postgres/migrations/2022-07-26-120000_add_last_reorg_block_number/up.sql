@@ -0,0 +1,2 @@
+alter table subgraphs.subgraph_deployment
+      add column last_reorg_block_number int;
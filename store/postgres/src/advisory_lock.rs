@@ -11,7 +11,11 @@
 //! We use the following 2x 32-bit locks
 //!   * 1, n: to lock copying of the deployment with id n in the destination
 //!           shard
+//!   * 2, n: to keep `graphman chain check-blocks` from changing the block
+//!           cache of the chain whose name hashes to n from several
+//!           processes at once
 
+use diesel::sql_types::{Bool, Text};
 use diesel::{sql_query, PgConnection, RunQueryDsl};
 use graph::prelude::StoreError;
 
@@ -38,6 +42,30 @@ pub(crate) fn lock_copying(conn: &PgConnection, dst: &Site) -> Result<(), StoreE
         .map_err(StoreError::from)
 }
 
+/// Try to get the lock for checking the block cache of `chain`. Returns
+/// `false` without blocking if somebody else holds the lock
+pub(crate) fn try_lock_check_blocks(conn: &PgConnection, chain: &str) -> Result<bool, StoreError> {
+    #[derive(QueryableByName)]
+    struct Locked {
+        #[sql_type = "Bool"]
+        locked: bool,
+    }
+
+    sql_query("select pg_try_advisory_lock(2, hashtext($1)) as locked")
+        .bind::<Text, _>(chain)
+        .get_result::<Locked>(conn)
+        .map(|res| res.locked)
+        .map_err(StoreError::from)
+}
+
+pub(crate) fn unlock_check_blocks(conn: &PgConnection, chain: &str) -> Result<(), StoreError> {
+    sql_query("select pg_advisory_unlock(2, hashtext($1))")
+        .bind::<Text, _>(chain)
+        .execute(conn)
+        .map(|_| ())
+        .map_err(StoreError::from)
+}
+
 pub(crate) fn unlock_copying(conn: &PgConnection, dst: &Site) -> Result<(), StoreError> {
     sql_query(&format!("select pg_advisory_unlock(1, {})", dst.id))
         .execute(conn)
@@ -451,6 +451,18 @@ impl BlockStore {
             })
     }
 
+    /// The names of all chains in the database, sorted by name
+    pub fn chain_names(&self) -> Result<Vec<String>, StoreError> {
+        let mut names: Vec<_> = self
+            .mirror
+            .read(|conn| primary::load_chains(conn))?
+            .into_iter()
+            .map(|chain| chain.name)
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
     pub fn drop_chain(&self, chain: &str) -> Result<(), StoreError> {
         let chain_store = self
             .store(chain)
@@ -469,7 +481,7 @@ impl BlockStore {
 
     fn truncate_block_caches(&self) -> Result<(), StoreError> {
         for store in self.stores.read().unwrap().values() {
-            store.truncate_block_cache()?
+            store.truncate_block_cache()?;
         }
         Ok(())
     }
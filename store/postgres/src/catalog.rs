@@ -1,4 +1,4 @@
-use diesel::sql_types::{Bool, Integer};
+use diesel::sql_types::{BigInt, Bool, Integer};
 use diesel::{connection::SimpleConnection, prelude::RunQueryDsl, select};
 use diesel::{insert_into, OptionalExtension};
 use diesel::{pg::PgConnection, sql_query};
@@ -12,7 +12,10 @@ use std::iter::FromIterator;
 use std::sync::Arc;
 
 use graph::prelude::anyhow::anyhow;
-use graph::{data::subgraph::schema::POI_TABLE, prelude::StoreError};
+use graph::{
+    data::subgraph::{schema::POI_TABLE, status::DeploymentSize},
+    prelude::StoreError,
+};
 
 use crate::connection_pool::ForeignServer;
 use crate::{
@@ -459,6 +462,40 @@ pub(crate) fn check_index_is_valid(
     Ok(matches!(result, Some(true)))
 }
 
+/// The space that the tables in `namespace` and their indexes take up. Together, the two add
+/// up to the `pg_total_relation_size` of all the tables
+pub(crate) fn deployment_size(
+    conn: &PgConnection,
+    namespace: &Namespace,
+) -> Result<DeploymentSize, StoreError> {
+    #[derive(QueryableByName)]
+    struct Size {
+        #[sql_type = "BigInt"]
+        entities_bytes: i64,
+        #[sql_type = "BigInt"]
+        index_bytes: i64,
+    }
+
+    let query = "
+        select
+            coalesce(sum(pg_table_size(c.oid)), 0)::int8 as entities_bytes,
+            coalesce(sum(pg_indexes_size(c.oid)), 0)::int8 as index_bytes
+        from
+            pg_class c
+            join pg_namespace n on c.relnamespace = n.oid
+        where
+            n.nspname = $1
+            and c.relkind = 'r'";
+    let size = sql_query(query)
+        .bind::<Text, _>(namespace.as_str())
+        .get_result::<Size>(conn)?;
+
+    Ok(DeploymentSize {
+        entities_bytes: size.entities_bytes as u64,
+        index_bytes: size.index_bytes as u64,
+    })
+}
+
 pub(crate) fn indexes_for_table(
     conn: &PgConnection,
     schema_name: &str,
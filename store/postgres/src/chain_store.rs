@@ -24,7 +24,7 @@ use graph::util::timed_cache::TimedCache;
 use graph::{constraint_violation, ensure};
 
 use crate::{
-    block_store::ChainStatus, chain_head_listener::ChainHeadUpdateSender,
+    advisory_lock, block_store::ChainStatus, chain_head_listener::ChainHeadUpdateSender,
     connection_pool::ConnectionPool,
 };
 
@@ -395,13 +395,105 @@ mod data {
             }
         }
 
-        pub(super) fn truncate_block_cache(&self, conn: &PgConnection) -> Result<(), StoreError> {
+        /// Delete all blocks from the cache and return how many were deleted
+        pub(super) fn truncate_block_cache(
+            &self,
+            conn: &PgConnection,
+        ) -> Result<usize, StoreError> {
+            #[derive(QueryableByName)]
+            struct Count {
+                #[sql_type = "BigInt"]
+                count: i64,
+            }
+
             let table_name = match &self {
                 Storage::Shared => ETHEREUM_BLOCKS_TABLE_NAME,
                 Storage::Private(Schema { blocks, .. }) => &blocks.qname,
             };
-            conn.batch_execute(&format!("truncate table {} restart identity", table_name))?;
-            Ok(())
+            // `truncate` doesn't report how many rows it removed, and we
+            // count them first with the table locked so that nothing can
+            // sneak in between counting and truncating
+            conn.transaction(|| {
+                conn.batch_execute(&format!(
+                    "lock table {} in access exclusive mode",
+                    table_name
+                ))?;
+                let count = sql_query(format!("select count(*) as count from {}", table_name))
+                    .get_result::<Count>(conn)?
+                    .count;
+                conn.batch_execute(&format!("truncate table {} restart identity", table_name))?;
+                Ok(count as usize)
+            })
+        }
+
+        /// The highest block number in the cache, whether or not that block
+        /// is on the main chain. `None` if the cache is empty
+        pub(super) fn highest_block_number(
+            &self,
+            conn: &PgConnection,
+            chain: &str,
+        ) -> Result<Option<BlockNumber>, StoreError> {
+            #[derive(QueryableByName)]
+            struct Max {
+                #[sql_type = "diesel::sql_types::Nullable<BigInt>"]
+                number: Option<i64>,
+            }
+
+            let max = match self {
+                Storage::Shared => sql_query(format!(
+                    "select max(number) as number from {} where network_name = $1",
+                    ETHEREUM_BLOCKS_TABLE_NAME
+                ))
+                .bind::<Text, _>(chain)
+                .get_result::<Max>(conn)?,
+                Storage::Private(Schema { blocks, .. }) => sql_query(format!(
+                    "select max(number) as number from {}",
+                    blocks.qname
+                ))
+                .get_result::<Max>(conn)?,
+            };
+            max.number
+                .map(|number| {
+                    BlockNumber::try_from(number)
+                        .map_err(|e| StoreError::QueryExecutionError(e.to_string()))
+                })
+                .transpose()
+        }
+
+        /// The lowest number of a block in the cache that is at least `from`
+        pub(super) fn lowest_block_number_from(
+            &self,
+            conn: &PgConnection,
+            chain: &str,
+            from: BlockNumber,
+        ) -> Result<Option<BlockNumber>, StoreError> {
+            #[derive(QueryableByName)]
+            struct Min {
+                #[sql_type = "diesel::sql_types::Nullable<BigInt>"]
+                number: Option<i64>,
+            }
+
+            let min = match self {
+                Storage::Shared => sql_query(format!(
+                    "select min(number) as number from {} where network_name = $1 and number >= $2",
+                    ETHEREUM_BLOCKS_TABLE_NAME
+                ))
+                .bind::<Text, _>(chain)
+                .bind::<BigInt, _>(from as i64)
+                .get_result::<Min>(conn)?,
+                Storage::Private(Schema { blocks, .. }) => sql_query(format!(
+                    "select min(number) as number from {} where number >= $1",
+                    blocks.qname
+                ))
+                .bind::<BigInt, _>(from as i64)
+                .get_result::<Min>(conn)?,
+            };
+            min.number
+                .map(|number| {
+                    BlockNumber::try_from(number)
+                        .map_err(|e| StoreError::QueryExecutionError(e.to_string()))
+                })
+                .transpose()
         }
 
         /// Insert a block. If the table already contains a block with the
@@ -1252,6 +1344,25 @@ from (
     }
 }
 
+/// Keeps other `graphman chain check-blocks` runs from changing the block
+/// cache of a chain while this one does. The lock is released when this is
+/// dropped
+pub struct BlockCacheLock {
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    chain: String,
+}
+
+impl Drop for BlockCacheLock {
+    fn drop(&mut self) {
+        if let Err(e) = advisory_lock::unlock_check_blocks(&self.conn, &self.chain) {
+            eprintln!(
+                "failed to release the block cache lock for {}: {}",
+                self.chain, e
+            );
+        }
+    }
+}
+
 pub struct ChainStore {
     pool: ConnectionPool,
     pub chain: String,
@@ -1290,6 +1401,19 @@ impl ChainStore {
         self.pool.get().map_err(Error::from)
     }
 
+    /// Lock the block cache against changes from other `check-blocks` runs.
+    /// Returns `None` if another run holds the lock already
+    pub fn lock_block_cache(&self) -> Result<Option<BlockCacheLock>, Error> {
+        let conn = self.get_conn()?;
+        if !advisory_lock::try_lock_check_blocks(&conn, &self.chain)? {
+            return Ok(None);
+        }
+        Ok(Some(BlockCacheLock {
+            conn,
+            chain: self.chain.clone(),
+        }))
+    }
+
     pub(crate) fn create(&self, ident: &ChainIdentifier) -> Result<(), Error> {
         use public::ethereum_networks::dsl::*;
 
@@ -1385,9 +1509,45 @@ impl ChainStore {
             .delete_blocks_by_hash(&conn, &self.chain, block_hashes)
     }
 
-    pub fn truncate_block_cache(&self) -> Result<(), StoreError> {
+    /// Delete all blocks from the cache and return how many were deleted
+    pub fn truncate_block_cache(&self) -> Result<usize, StoreError> {
+        let conn = self.get_conn()?;
+        self.storage.truncate_block_cache(&conn)
+    }
+
+    /// The highest block number in the cache, whether or not that block is
+    /// on the main chain
+    pub fn highest_cached_block_number(&self) -> Result<Option<BlockNumber>, StoreError> {
+        let conn = self.get_conn()?;
+        self.storage.highest_block_number(&conn, &self.chain)
+    }
+
+    /// The lowest block number in the cache that is at least `from`, whether
+    /// or not that block is on the main chain
+    pub fn lowest_cached_block_number_from(
+        &self,
+        from: BlockNumber,
+    ) -> Result<Option<BlockNumber>, StoreError> {
         let conn = self.get_conn()?;
-        self.storage.truncate_block_cache(&conn)?;
+        self.storage
+            .lowest_block_number_from(&conn, &self.chain, from)
+    }
+
+    /// Point the chain head at `ptr` without touching the block cache,
+    /// e.g., after the block the head pointed at was deleted. The cursor of
+    /// the old head is cleared since it does not belong to the new head
+    pub fn set_chain_head_ptr(&self, ptr: &BlockPtr) -> Result<(), StoreError> {
+        use public::ethereum_networks as n;
+
+        let conn = self.get_conn()?;
+        update(n::table.filter(n::name.eq(&self.chain)))
+            .set((
+                n::head_block_hash.eq(ptr.hash_hex()),
+                n::head_block_number.eq(ptr.number as i64),
+                n::head_block_cursor.eq(None::<String>),
+            ))
+            .execute(&conn)?;
+        self.block_cache.set("head", Arc::new(ptr.clone()));
         Ok(())
     }
 }
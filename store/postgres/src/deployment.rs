@@ -4,17 +4,18 @@
 use crate::{detail::GraphNodeVersion, primary::DeploymentId};
 use diesel::{
     connection::SimpleConnection,
-    dsl::{count, delete, insert_into, select, sql, update},
+    dsl::{any, count, delete, insert_into, select, sql, update},
     sql_types::Integer,
 };
 use diesel::{expression::SqlLiteral, pg::PgConnection, sql_types::Numeric};
 use diesel::{
-    prelude::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl},
+    prelude::{ExpressionMethods, JoinOnDsl, OptionalExtension, QueryDsl, RunQueryDsl},
     sql_query,
     sql_types::{Nullable, Text},
 };
 use graph::data::subgraph::{
     schema::{DeploymentCreate, SubgraphManifestEntity},
+    status::HealthTransition,
     SubgraphFeature,
 };
 use graph::prelude::{
@@ -30,7 +31,7 @@ use crate::connection_pool::ForeignServer;
 use crate::{block_range::BLOCK_RANGE_COLUMN, primary::Site};
 use graph::constraint_violation;
 
-#[derive(DbEnum, Debug, Clone, Copy)]
+#[derive(DbEnum, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SubgraphHealth {
     Failed,
     Healthy,
@@ -45,6 +46,19 @@ impl SubgraphHealth {
     }
 }
 
+impl From<graph::data::subgraph::schema::SubgraphHealth> for SubgraphHealth {
+    fn from(health: graph::data::subgraph::schema::SubgraphHealth) -> Self {
+        use graph::data::subgraph::schema::SubgraphHealth as H;
+        use SubgraphHealth as Db;
+
+        match health {
+            H::Failed => Db::Failed,
+            H::Healthy => Db::Healthy,
+            H::Unhealthy => Db::Unhealthy,
+        }
+    }
+}
+
 impl From<SubgraphHealth> for graph::data::subgraph::schema::SubgraphHealth {
     fn from(health: SubgraphHealth) -> Self {
         use graph::data::subgraph::schema::SubgraphHealth as H;
@@ -84,6 +98,8 @@ table! {
         current_reorg_depth -> Integer,
         max_reorg_depth -> Integer,
         firehose_cursor -> Nullable<Text>,
+        /// The number of the last block that was reverted
+        last_reorg_block_number -> Nullable<Integer>,
     }
 }
 
@@ -113,6 +129,8 @@ table! {
         /// Parent of the smallest start block from the manifest
         start_block_number -> Nullable<Integer>,
         start_block_hash -> Nullable<Binary>,
+        raw_yaml -> Nullable<Text>,
+        detected_features -> Nullable<Array<Text>>,
     }
 }
 
@@ -128,7 +146,22 @@ table! {
     }
 }
 
-allow_tables_to_appear_in_same_query!(subgraph_deployment, subgraph_error, subgraph_manifest);
+table! {
+    subgraphs.subgraph_health_history (vid) {
+        vid -> BigInt,
+        id -> Integer,
+        block_number -> Nullable<Integer>,
+        health -> crate::deployment::SubgraphHealthMapping,
+        error -> Nullable<Text>,
+    }
+}
+
+allow_tables_to_appear_in_same_query!(
+    subgraph_deployment,
+    subgraph_error,
+    subgraph_manifest,
+    subgraph_health_history
+);
 
 /// Look up the graft point for the given subgraph in the database and
 /// return it. If `pending_only` is `true`, only return `Some(_)` if the
@@ -260,6 +293,46 @@ pub fn manifest_info(
         .map(|schema| (schema, description, repository, spec_version))
 }
 
+/// Return the raw YAML of the manifest for `site` as it was when the
+/// deployment was created. Deployments that were created before we started
+/// recording the manifest will return `None`
+pub fn raw_manifest(conn: &PgConnection, site: &Site) -> Result<Option<String>, StoreError> {
+    use subgraph_manifest as sm;
+
+    sm::table
+        .select(sm::raw_yaml)
+        .filter(sm::id.eq(site.id))
+        .first::<Option<String>>(conn)
+        .optional()
+        .map(Option::flatten)
+        .map_err(StoreError::from)
+}
+
+/// Return the features that the deployment for `site` uses, as they were
+/// detected when the deployment was created. Deployments that were created
+/// before we started recording them will return `None`
+pub fn detected_features(
+    conn: &PgConnection,
+    site: &Site,
+) -> Result<Option<BTreeSet<SubgraphFeature>>, StoreError> {
+    use subgraph_manifest as sm;
+
+    let features = sm::table
+        .select(sm::detected_features)
+        .filter(sm::id.eq(site.id))
+        .first::<Option<Vec<String>>>(conn)
+        .optional()?
+        .flatten();
+    features
+        .map(|features| {
+            features
+                .iter()
+                .map(|f| SubgraphFeature::from_str(f).map_err(StoreError::from))
+                .collect()
+        })
+        .transpose()
+}
+
 #[allow(dead_code)]
 pub fn features(conn: &PgConnection, site: &Site) -> Result<BTreeSet<SubgraphFeature>, StoreError> {
     use subgraph_manifest as sm;
@@ -416,6 +489,7 @@ pub fn revert_block_ptr(
             d::latest_ethereum_block_hash.eq(ptr.hash_slice()),
             d::firehose_cursor.eq(firehose_cursor.as_ref()),
             d::reorg_count.eq(d::reorg_count + 1),
+            d::last_reorg_block_number.eq(ptr.number + 1),
             d::current_reorg_depth.eq(d::current_reorg_depth + 1),
             d::max_reorg_depth.eq(sql("greatest(current_reorg_depth + 1, max_reorg_depth)")),
         ))
@@ -645,8 +719,9 @@ pub fn fail(
     error: &SubgraphError,
 ) -> Result<(), StoreError> {
     let error_id = insert_subgraph_error(conn, error)?;
+    let block = error.block_ptr.as_ref().map(|ptr| ptr.number);
 
-    update_deployment_status(conn, id, SubgraphHealth::Failed, Some(error_id))?;
+    update_deployment_status(conn, id, SubgraphHealth::Failed, Some(error_id), block)?;
 
     Ok(())
 }
@@ -688,25 +763,122 @@ pub(crate) fn has_non_fatal_errors(
     .map_err(|e| e.into())
 }
 
+/// Set the health of the deployment, and record the change in its health
+/// history if the health is different from what it was. `block` is the
+/// block at which the change happened, if it is known
 pub fn update_deployment_status(
     conn: &PgConnection,
     deployment_id: &DeploymentHash,
     health: SubgraphHealth,
     fatal_error: Option<String>,
+    block: Option<BlockNumber>,
 ) -> Result<(), StoreError> {
     use subgraph_deployment as d;
+    use subgraph_error as e;
+
+    let (id, old_health) = d::table
+        .filter(d::deployment.eq(deployment_id.as_str()))
+        .select((d::id, d::health))
+        .get_result::<(DeploymentId, SubgraphHealth)>(conn)?;
+    let message = fatal_error
+        .as_ref()
+        .map(|error_id| {
+            e::table
+                .filter(e::id.eq(error_id))
+                .select(e::message)
+                .get_result::<String>(conn)
+        })
+        .transpose()?;
 
-    update(d::table.filter(d::deployment.eq(deployment_id.as_str())))
+    update(d::table.filter(d::id.eq(id)))
         .set((
             d::failed.eq(health.is_failed()),
             d::health.eq(health),
             d::fatal_error.eq::<Option<String>>(fatal_error),
         ))
+        .execute(conn)?;
+
+    if health != old_health {
+        record_health_transition(conn, id, block, health, message)?;
+    }
+    Ok(())
+}
+
+fn record_health_transition(
+    conn: &PgConnection,
+    id: DeploymentId,
+    block: Option<BlockNumber>,
+    health: SubgraphHealth,
+    error: Option<String>,
+) -> Result<(), StoreError> {
+    use subgraph_health_history as h;
+
+    insert_into(h::table)
+        .values((
+            h::id.eq(id),
+            h::block_number.eq(block),
+            h::health.eq(health),
+            h::error.eq(error),
+        ))
         .execute(conn)
         .map(|_| ())
         .map_err(StoreError::from)
 }
 
+/// Return `first` of the changes in the health of the deployment, in the
+/// order in which they happened, after skipping the first `skip` ones
+pub(crate) fn health_history(
+    conn: &PgConnection,
+    id: &DeploymentHash,
+    first: usize,
+    skip: usize,
+) -> Result<Vec<HealthTransition>, StoreError> {
+    use subgraph_deployment as d;
+    use subgraph_health_history as h;
+
+    Ok(h::table
+        .inner_join(d::table.on(d::id.eq(h::id)))
+        .filter(d::deployment.eq(id.as_str()))
+        .select((h::block_number, h::health, h::error))
+        .order_by(h::vid)
+        .limit(first as i64)
+        .offset(skip as i64)
+        .load::<(Option<BlockNumber>, SubgraphHealth, Option<String>)>(conn)?
+        .into_iter()
+        .map(|(block, health, error)| HealthTransition {
+            block,
+            health: health.into(),
+            error,
+        })
+        .collect())
+}
+
+/// Return the last change in the health of the deployment that happened
+/// at or before `block`. Changes for which we do not know the block are
+/// ignored
+pub(crate) fn health_at_block(
+    conn: &PgConnection,
+    id: &DeploymentHash,
+    block: BlockNumber,
+) -> Result<Option<HealthTransition>, StoreError> {
+    use subgraph_deployment as d;
+    use subgraph_health_history as h;
+
+    Ok(h::table
+        .inner_join(d::table.on(d::id.eq(h::id)))
+        .filter(d::deployment.eq(id.as_str()))
+        .filter(h::block_number.le(block))
+        .select((h::block_number, h::health, h::error))
+        .order_by(h::vid.desc())
+        .first::<(Option<BlockNumber>, SubgraphHealth, Option<String>)>(conn)
+        .optional()?
+        .map(|(block, health, error)| HealthTransition {
+            block,
+            health: health.into(),
+            error,
+        }))
+}
+
 /// Insert the errors and check if the subgraph needs to be set as unhealthy.
 pub(crate) fn insert_subgraph_errors(
     conn: &PgConnection,
@@ -747,15 +919,55 @@ fn check_health(
         false => (SubgraphHealth::Healthy, SubgraphHealth::Unhealthy),
     };
 
-    update(
+    let changed = update(
         d::table
             .filter(d::deployment.eq(id.as_str()))
             .filter(d::health.eq(old)),
     )
     .set(d::health.eq(new))
-    .execute(conn)
-    .map(|_| ())
-    .map_err(|e| e.into())
+    .returning(d::id)
+    .get_results::<DeploymentId>(conn)?;
+
+    for id in changed {
+        record_health_transition(conn, id, Some(block), new, None)?;
+    }
+    Ok(())
+}
+
+/// The ids of all deployments in this shard whose health is `failed`
+pub(crate) fn failed_deployments(conn: &PgConnection) -> Result<Vec<DeploymentId>, StoreError> {
+    use subgraph_deployment as d;
+
+    d::table
+        .filter(d::health.eq(SubgraphHealth::Failed))
+        .select(d::id)
+        .order_by(d::id)
+        .load::<DeploymentId>(conn)
+        .map_err(StoreError::from)
+}
+
+/// The ids of the deployments out of `ids` whose health is one of `health`.
+/// If `ids` is empty, look at all deployments in this shard
+pub(crate) fn deployments_with_health(
+    conn: &PgConnection,
+    ids: &[DeploymentId],
+    health: &[graph::data::subgraph::schema::SubgraphHealth],
+) -> Result<Vec<DeploymentId>, StoreError> {
+    use subgraph_deployment as d;
+
+    let health: Vec<SubgraphHealth> = health.iter().map(|health| (*health).into()).collect();
+    let query = d::table
+        .filter(d::health.eq(any(health)))
+        .select(d::id)
+        .order_by(d::id);
+    let ids = if ids.is_empty() {
+        query.load::<DeploymentId>(conn)?
+    } else {
+        query
+            .filter(d::id.eq_any(ids.iter().copied()))
+            .load::<DeploymentId>(conn)?
+    };
+    Ok(ids)
 }
 
 pub(crate) fn health(conn: &PgConnection, id: DeploymentId) -> Result<SubgraphHealth, StoreError> {
@@ -909,6 +1121,8 @@ pub fn create_deployment(
                 repository,
                 features,
                 schema,
+                raw_yaml,
+                detected_features,
             },
         earliest_block,
         graft_base,
@@ -952,6 +1166,8 @@ pub fn create_deployment(
         m::use_bytea_prefix.eq(true),
         m::start_block_hash.eq(b(&earliest_block)),
         m::start_block_number.eq(earliest_block_number),
+        m::raw_yaml.eq(raw_yaml),
+        m::detected_features.eq(detected_features),
     );
 
     if exists && replace {
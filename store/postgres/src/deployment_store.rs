@@ -5,7 +5,7 @@ use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, PooledConnection};
 use graph::blockchain::block_stream::FirehoseCursor;
 use graph::components::store::{EntityType, StoredDynamicDataSource};
-use graph::data::subgraph::{status, SPEC_VERSION_0_0_6};
+use graph::data::subgraph::{status, SubgraphFeature};
 use graph::prelude::{
     tokio, CancelHandle, CancelToken, CancelableError, EntityOperation, PoolWaitStats,
     SubgraphDeploymentEntity,
@@ -14,7 +14,7 @@ use graph::semver::Version;
 use lru_time_cache::LruCache;
 use rand::{seq::SliceRandom, thread_rng};
 use std::borrow::Cow;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::Into;
 use std::iter::FromIterator;
 use std::ops::Bound;
@@ -44,7 +44,10 @@ use crate::dynds::DataSourcesTable;
 use crate::relational::{Layout, LayoutCache, SqlName, Table};
 use crate::relational_queries::FromEntityData;
 use crate::{connection_pool::ConnectionPool, detail};
-use crate::{dynds, primary::Site};
+use crate::{
+    dynds,
+    primary::{DeploymentId, Site},
+};
 
 /// When connected to read replicas, this allows choosing which DB server to use for an operation.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -558,11 +561,7 @@ impl DeploymentStore {
         schema.add_subgraph_id_directives(site.deployment.clone());
 
         let spec_version = Version::from_str(&spec_version).map_err(anyhow::Error::from)?;
-        let poi_version = if spec_version.ge(&SPEC_VERSION_0_0_6) {
-            ProofOfIndexingVersion::Fast
-        } else {
-            ProofOfIndexingVersion::Legacy
-        };
+        let poi_version = ProofOfIndexingVersion::for_spec_version(&spec_version);
 
         let info = SubgraphInfo {
             input: Arc::new(input_schema),
@@ -597,6 +596,96 @@ impl DeploymentStore {
         deployment::block_ptr(conn, &site.deployment)
     }
 
+    pub(crate) fn failed_deployments(&self) -> Result<Vec<DeploymentId>, StoreError> {
+        let conn = self.get_conn()?;
+        deployment::failed_deployments(&conn)
+    }
+
+    pub(crate) fn deployments_with_health(
+        &self,
+        ids: &[DeploymentId],
+        health: &[graph::data::subgraph::schema::SubgraphHealth],
+    ) -> Result<Vec<DeploymentId>, StoreError> {
+        let conn = self.get_conn()?;
+        deployment::deployments_with_health(&conn, ids, health)
+    }
+
+    pub(crate) fn raw_manifest(&self, site: &Site) -> Result<Option<String>, StoreError> {
+        let conn = self.get_conn()?;
+        deployment::raw_manifest(&conn, site)
+    }
+
+    pub(crate) fn detected_features(
+        &self,
+        site: &Site,
+    ) -> Result<Option<BTreeSet<SubgraphFeature>>, StoreError> {
+        let conn = self.get_conn()?;
+        deployment::detected_features(&conn, site)
+    }
+
+    pub(crate) fn health_history(
+        &self,
+        id: &DeploymentHash,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<status::HealthTransition>, StoreError> {
+        let conn = self.get_conn()?;
+        deployment::health_history(&conn, id, first, skip)
+    }
+
+    pub(crate) fn health_at_block(
+        &self,
+        id: &DeploymentHash,
+        block: BlockNumber,
+    ) -> Result<Option<status::HealthTransition>, StoreError> {
+        let conn = self.get_conn()?;
+        deployment::health_at_block(&conn, id, block)
+    }
+
+    pub(crate) fn non_fatal_errors(
+        &self,
+        id: &DeploymentHash,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<SubgraphError>, StoreError> {
+        let conn = self.get_conn()?;
+        ErrorDetail::non_fatal(&conn, id, first, skip)?
+            .into_iter()
+            .map(SubgraphError::try_from)
+            .collect()
+    }
+
+    pub(crate) fn entity_counts(&self, site: Arc<Site>) -> Result<Vec<(String, u64)>, StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site)?;
+        layout.entity_counts(&conn)
+    }
+
+    pub(crate) fn deployment_size(
+        &self,
+        site: Arc<Site>,
+    ) -> Result<status::DeploymentSize, StoreError> {
+        let conn = self.get_conn()?;
+        catalog::deployment_size(&conn, &site.namespace)
+    }
+
+    pub(crate) fn network_health(
+        &self,
+        sites: &[Arc<Site>],
+        assigned: &HashSet<DeploymentId>,
+    ) -> Result<Vec<detail::NetworkHealth>, StoreError> {
+        let conn = self.get_conn()?;
+        detail::network_health(&conn, sites, assigned)
+    }
+
+    pub(crate) fn unprocessed_deployments(
+        &self,
+        sites: &[Arc<Site>],
+    ) -> Result<Vec<DeploymentId>, StoreError> {
+        let conn = self.get_conn()?;
+        detail::unprocessed_deployments(&conn, sites)
+    }
+
     pub(crate) fn deployment_details(
         &self,
         ids: Vec<String>,
@@ -611,10 +700,21 @@ impl DeploymentStore {
     ) -> Result<Vec<status::Info>, StoreError> {
         let conn = self.get_conn()?;
         conn.transaction(|| -> Result<Vec<status::Info>, StoreError> {
-            detail::deployment_statuses(&conn, sites)
+            detail::deployment_statuses(&self.logger, &conn, sites)
         })
     }
 
+    pub(crate) fn deployment_order(
+        &self,
+        sites: &[Arc<Site>],
+        heads: &HashMap<String, BlockNumber>,
+        order: status::Order,
+        limit: Option<usize>,
+    ) -> Result<Vec<(DeploymentId, Option<BlockNumber>)>, StoreError> {
+        let conn = self.get_conn()?;
+        detail::deployment_order(&conn, sites, heads, order, limit)
+    }
+
     pub(crate) fn deployment_exists_and_synced(
         &self,
         id: &DeploymentHash,
@@ -819,18 +919,32 @@ impl DeploymentStore {
         indexer: &Option<Address>,
         block: BlockPtr,
     ) -> Result<Option<[u8; 32]>, StoreError> {
+        let mut pois = self
+            .get_proofs_of_indexing(site, indexer, vec![block])
+            .await?;
+        Ok(pois.pop().flatten())
+    }
+
+    /// Computes the proofs of indexing for several blocks of the same
+    /// deployment, in the order of `blocks`. The entities for all blocks
+    /// are read in one transaction, and what the proofs have in common is
+    /// only looked up once
+    pub(crate) async fn get_proofs_of_indexing(
+        &self,
+        site: Arc<Site>,
+        indexer: &Option<Address>,
+        blocks: Vec<BlockPtr>,
+    ) -> Result<Vec<Option<[u8; 32]>>, StoreError> {
         let indexer = *indexer;
-        let site3 = site.cheap_clone();
-        let site4 = site.cheap_clone();
-        let site5 = site.cheap_clone();
+        let site2 = site.cheap_clone();
         let store = self.cheap_clone();
-        let block2 = block.cheap_clone();
+        let numbers: Vec<BlockNumber> = blocks.iter().map(|block| block.number).collect();
 
         let entities = self
             .with_conn(move |conn, cancel| {
                 cancel.check_cancel()?;
 
-                let layout = store.layout(conn, site4.cheap_clone())?;
+                let layout = store.layout(conn, site2.cheap_clone())?;
 
                 if !layout.supports_proof_of_indexing() {
                     return Ok(None);
@@ -838,77 +952,92 @@ impl DeploymentStore {
 
                 conn.transaction::<_, CancelableError<anyhow::Error>, _>(move || {
                     let latest_block_ptr =
-                        match Self::block_ptr_with_conn(conn, site4.cheap_clone())? {
+                        match Self::block_ptr_with_conn(conn, site2.cheap_clone())? {
                             Some(inner) => inner,
                             None => return Ok(None),
                         };
 
-                    cancel.check_cancel()?;
-
-                    // FIXME: (Determinism)
-                    //
-                    // It is vital to ensure that the block hash given in the query
-                    // is a parent of the latest block indexed for the subgraph.
-                    // Unfortunately the machinery needed to do this is not yet in place.
-                    // The best we can do right now is just to make sure that the block number
-                    // is high enough.
-                    if latest_block_ptr.number < block.number {
-                        return Ok(None);
+                    let mut entities = Vec::with_capacity(numbers.len());
+                    for number in numbers {
+                        cancel.check_cancel()?;
+
+                        // FIXME: (Determinism)
+                        //
+                        // It is vital to ensure that the block hash given in the query
+                        // is a parent of the latest block indexed for the subgraph.
+                        // Unfortunately the machinery needed to do this is not yet in place.
+                        // The best we can do right now is just to make sure that the block number
+                        // is high enough.
+                        if latest_block_ptr.number < number {
+                            entities.push(None);
+                            continue;
+                        }
+
+                        let query = EntityQuery::new(
+                            site2.deployment.cheap_clone(),
+                            number,
+                            EntityCollection::All(vec![(
+                                POI_OBJECT.cheap_clone(),
+                                AttributeNames::All,
+                            )]),
+                        );
+                        let block_entities = store
+                            .execute_query::<Entity>(conn, site2.cheap_clone(), query)
+                            .map_err(anyhow::Error::from)?;
+                        entities.push(Some(block_entities));
                     }
 
-                    let query = EntityQuery::new(
-                        site4.deployment.cheap_clone(),
-                        block.number,
-                        EntityCollection::All(vec![(
-                            POI_OBJECT.cheap_clone(),
-                            AttributeNames::All,
-                        )]),
-                    );
-                    let entities = store
-                        .execute_query::<Entity>(conn, site4, query)
-                        .map_err(anyhow::Error::from)?;
-
                     Ok(Some(entities))
                 })
                 .map_err(Into::into)
             })
             .await?;
 
-        let entities = if let Some(entities) = entities {
-            entities
-        } else {
-            return Ok(None);
+        let entities = match entities {
+            Some(entities) => entities,
+            None => return Ok(vec![None; blocks.len()]),
         };
 
-        let mut by_causality_region = entities
-            .into_iter()
-            .map(|e| {
-                let causality_region = e.id()?;
-                let digest = match e.get("digest") {
-                    Some(Value::Bytes(b)) => Ok(b.to_owned()),
-                    other => Err(anyhow::anyhow!(
-                        "Entity has non-bytes digest attribute: {:?}",
-                        other
-                    )),
-                }?;
-
-                Ok((causality_region, digest))
-            })
-            .collect::<Result<HashMap<_, _>, anyhow::Error>>()?;
+        let info = self.subgraph_info(&site).map_err(anyhow::Error::from)?;
 
-        let info = self.subgraph_info(&site5).map_err(anyhow::Error::from)?;
+        blocks
+            .iter()
+            .zip(entities)
+            .map(|(block, entities)| {
+                let entities = match entities {
+                    Some(entities) => entities,
+                    None => return Ok(None),
+                };
 
-        let mut finisher = ProofOfIndexingFinisher::new(
-            &block2,
-            &site3.deployment,
-            &indexer,
-            info.poi_version.clone(),
-        );
-        for (name, region) in by_causality_region.drain() {
-            finisher.add_causality_region(&name, &region);
-        }
+                let mut by_causality_region = entities
+                    .into_iter()
+                    .map(|e| {
+                        let causality_region = e.id()?;
+                        let digest = match e.get("digest") {
+                            Some(Value::Bytes(b)) => Ok(b.to_owned()),
+                            other => Err(anyhow::anyhow!(
+                                "Entity has non-bytes digest attribute: {:?}",
+                                other
+                            )),
+                        }?;
+
+                        Ok((causality_region, digest))
+                    })
+                    .collect::<Result<HashMap<_, _>, anyhow::Error>>()?;
+
+                let mut finisher = ProofOfIndexingFinisher::new(
+                    block,
+                    &site.deployment,
+                    &indexer,
+                    info.poi_version.clone(),
+                );
+                for (name, region) in by_causality_region.drain() {
+                    finisher.add_causality_region(&name, &region);
+                }
 
-        Ok(Some(finisher.finish()))
+                Ok(Some(finisher.finish()))
+            })
+            .collect()
     }
 
     /// Get the entity matching `key` from the deployment `site`. Only
@@ -1101,11 +1230,11 @@ impl DeploymentStore {
 
         // Sanity check on block numbers
         if block_ptr_from.number <= block_ptr_to.number {
-            constraint_violation!(
+            return Err(constraint_violation!(
                 "rewind must go backwards, but would go from block {} to block {}",
                 block_ptr_from.number,
                 block_ptr_to.number
-            );
+            ));
         }
 
         // When rewinding, we reset the firehose cursor. That way, on resume, Firehose will start
@@ -1366,7 +1495,13 @@ impl DeploymentStore {
                     let _ = self.revert_block_operations(site.clone(), parent_ptr.clone(), &FirehoseCursor::None)?;
 
                     // Unfail the deployment.
-                    deployment::update_deployment_status(conn, deployment_id, prev_health, None)?;
+                    deployment::update_deployment_status(
+                        conn,
+                        deployment_id,
+                        prev_health,
+                        None,
+                        Some(parent_ptr.number),
+                    )?;
 
                     Ok(UnfailOutcome::Unfailed)
                 }
@@ -1449,6 +1584,7 @@ impl DeploymentStore {
                             deployment_id,
                             deployment::SubgraphHealth::Healthy,
                             None,
+                            Some(current_ptr.number),
                         )?;
 
                         // Delete the fatal error.
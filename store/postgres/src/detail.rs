@@ -7,17 +7,23 @@ use diesel::prelude::{
     ExpressionMethods, JoinOnDsl, NullableExpressionMethods, OptionalExtension, PgConnection,
     QueryDsl, RunQueryDsl,
 };
+use diesel::sql_query;
+use diesel::sql_types::{Array, Integer, Nullable};
 use diesel_derives::Associations;
 use git_testament::{git_testament, git_testament_macros};
 use graph::blockchain::BlockHash;
+use graph::components::subgraph::ProofOfIndexingVersion;
 use graph::data::subgraph::schema::{SubgraphError, SubgraphManifestEntity};
 use graph::prelude::{
-    bigdecimal::ToPrimitive, BigDecimal, BlockPtr, DeploymentHash, StoreError,
-    SubgraphDeploymentEntity,
+    bigdecimal::ToPrimitive, warn, BigDecimal, BlockNumber, BlockPtr, DeploymentHash, Logger,
+    StoreError, SubgraphDeploymentEntity,
 };
+use graph::semver::Version;
 use graph::{constraint_violation, data::subgraph::status, prelude::web3::types::H256};
 use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
+use std::str::FromStr;
 use std::{ops::Bound, sync::Arc};
 
 use crate::deployment::{
@@ -67,6 +73,7 @@ pub struct DeploymentDetail {
     current_reorg_depth: i32,
     max_reorg_depth: i32,
     firehose_cursor: Option<String>,
+    last_reorg_block_number: Option<i32>,
 }
 
 #[derive(Queryable, QueryableByName)]
@@ -103,6 +110,29 @@ impl ErrorDetail {
             .optional()
             .map_err(StoreError::from)
     }
+
+    /// Fetches `first` of the non-fatal errors associated with the given
+    /// [`DeploymentHash`], in the order in which they happened, after
+    /// skipping the first `skip` ones
+    pub fn non_fatal(
+        conn: &PgConnection,
+        deployment_id: &DeploymentHash,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<Self>, StoreError> {
+        use subgraph_deployment as d;
+        use subgraph_error as e;
+
+        d::table
+            .filter(d::deployment.eq(deployment_id.as_str()))
+            .inner_join(e::table.on(e::id.eq(dsl::any(d::non_fatal_errors))))
+            .select(e::all_columns)
+            .order_by(e::vid)
+            .limit(first as i64)
+            .offset(skip as i64)
+            .load(conn)
+            .map_err(StoreError::from)
+    }
 }
 
 impl TryFrom<ErrorDetail> for SubgraphError {
@@ -180,9 +210,11 @@ pub(crate) fn block(
 }
 
 pub(crate) fn info_from_details(
+    logger: &Logger,
     detail: DeploymentDetail,
     fatal: Option<ErrorDetail>,
     non_fatal: Vec<ErrorDetail>,
+    spec_version: Option<String>,
     sites: &[Arc<Site>],
 ) -> Result<status::Info, StoreError> {
     let DeploymentDetail {
@@ -200,6 +232,8 @@ pub(crate) fn info_from_details(
         graft_base: _,
         graft_block_hash: _,
         graft_block_number: _,
+        reorg_count,
+        last_reorg_block_number,
         ..
     } = detail;
 
@@ -235,8 +269,24 @@ pub(crate) fn info_from_details(
         .into_iter()
         .map(SubgraphError::try_from)
         .collect::<Result<Vec<SubgraphError>, StoreError>>()?;
+    // A deployment whose manifest we can't make sense of should not keep us from reporting
+    // the status of all other deployments
+    let poi_version = match spec_version.as_deref().map(Version::from_str) {
+        Some(Ok(spec_version)) => Some(ProofOfIndexingVersion::for_spec_version(&spec_version)),
+        Some(Err(e)) => {
+            warn!(logger, "Invalid spec version in manifest";
+                "deployment" => &deployment,
+                "spec_version" => spec_version.as_deref().unwrap_or_default(),
+                "error" => e.to_string());
+            None
+        }
+        None => {
+            warn!(logger, "Missing manifest"; "deployment" => &deployment);
+            None
+        }
+    };
 
-    // 'node' needs to be filled in later from a different shard
+    // 'node' and 'paused' need to be filled in later from a different shard
     Ok(status::Info {
         id: id.into(),
         subgraph: deployment,
@@ -246,7 +296,15 @@ pub(crate) fn info_from_details(
         non_fatal_errors,
         chains: vec![chain],
         entity_count,
+        entities_by_type: None,
+        deployment_size: None,
+        reorg_count,
+        last_reorg_block: last_reorg_block_number,
+        earliest_block_number,
+        poi_version,
+        sync_rate: None,
         node: None,
+        paused: None,
     })
 }
 
@@ -268,12 +326,66 @@ pub(crate) fn deployment_details(
     Ok(details)
 }
 
+/// The ids and latest block numbers of the deployments of `sites` in `order`, at most `limit`
+/// of them. `heads` has the number of the chain head of each network, which is needed to order
+/// by lag. Sorting the result by `status::Order::key` keeps it in the same order.
+pub(crate) fn deployment_order(
+    conn: &PgConnection,
+    sites: &[Arc<Site>],
+    heads: &HashMap<String, BlockNumber>,
+    order: status::Order,
+    limit: Option<usize>,
+) -> Result<Vec<(DeploymentId, Option<BlockNumber>)>, StoreError> {
+    #[derive(QueryableByName)]
+    struct Row {
+        #[sql_type = "Integer"]
+        id: DeploymentId,
+        #[sql_type = "Nullable<Integer>"]
+        latest: Option<BlockNumber>,
+    }
+
+    // Keep this in sync with `status::Order::key`
+    const LAG: &str = "greatest(h.head - coalesce(d.latest_ethereum_block_number, 0), 0)";
+    let order_by = match order {
+        status::Order::Created => "d.id".to_string(),
+        status::Order::LatestBlock => {
+            "d.latest_ethereum_block_number asc nulls first, d.id".to_string()
+        }
+        status::Order::LagAsc => format!("{} asc nulls last, d.id", LAG),
+        status::Order::LagDesc => format!("{} desc nulls last, d.id", LAG),
+    };
+    let limit = limit.map_or_else(|| "all".to_string(), |limit| limit.to_string());
+    let query = format!(
+        "select d.id, d.latest_ethereum_block_number::int4 as latest
+           from subgraphs.subgraph_deployment d
+           join unnest($1::int4[], $2::int4[]) as h(id, head) on h.id = d.id
+          order by {}
+          limit {}",
+        order_by, limit
+    );
+
+    let ids: Vec<_> = sites.iter().map(|site| site.id).collect();
+    let heads: Vec<_> = sites
+        .iter()
+        .map(|site| heads.get(&site.network).copied())
+        .collect();
+    Ok(sql_query(query)
+        .bind::<Array<Integer>, _>(ids)
+        .bind::<Array<Nullable<Integer>>, _>(heads)
+        .load::<Row>(conn)?
+        .into_iter()
+        .map(|row| (row.id, row.latest))
+        .collect())
+}
+
 pub(crate) fn deployment_statuses(
+    logger: &Logger,
     conn: &PgConnection,
     sites: &[Arc<Site>],
 ) -> Result<Vec<status::Info>, StoreError> {
     use subgraph_deployment as d;
     use subgraph_error as e;
+    use subgraph_manifest as m;
 
     // First, we fetch all deployment information along with any fatal errors.
     // Subsequently, we fetch non-fatal errors and we group them by deployment
@@ -314,11 +426,27 @@ pub(crate) fn deployment_statuses(
         .into_group_map()
     };
 
+    let mut spec_versions = {
+        if sites.is_empty() {
+            m::table
+                .select((m::id, m::spec_version))
+                .load::<(DeploymentId, String)>(conn)?
+        } else {
+            m::table
+                .filter(m::id.eq_any(sites.iter().map(|site| site.id)))
+                .select((m::id, m::spec_version))
+                .load::<(DeploymentId, String)>(conn)?
+        }
+        .into_iter()
+        .collect::<HashMap<_, _>>()
+    };
+
     details_with_fatal_error
         .into_iter()
         .map(|(detail, fatal)| {
             let non_fatal = non_fatal_errors.remove(&detail.id).unwrap_or(vec![]);
-            info_from_details(detail, fatal, non_fatal, sites)
+            let spec_version = spec_versions.remove(&detail.id);
+            info_from_details(logger, detail, fatal, non_fatal, spec_version, sites)
         })
         .collect()
 }
@@ -340,6 +468,8 @@ struct StoredSubgraphManifest {
     use_bytea_prefix: bool,
     start_block_number: Option<i32>,
     start_block_hash: Option<Bytes>,
+    raw_yaml: Option<String>,
+    detected_features: Option<Vec<String>>,
 }
 
 impl From<StoredSubgraphManifest> for SubgraphManifestEntity {
@@ -350,6 +480,8 @@ impl From<StoredSubgraphManifest> for SubgraphManifestEntity {
             repository: value.repository,
             features: value.features,
             schema: value.schema,
+            raw_yaml: value.raw_yaml,
+            detected_features: value.detected_features,
         }
     }
 }
@@ -417,6 +549,80 @@ impl TryFrom<StoredDeploymentEntity> for SubgraphDeploymentEntity {
     }
 }
 
+/// How many of the deployments in a shard that index `network` there are
+/// in each state, and the lowest block any of the assigned deployments
+/// that haven't failed is at
+#[derive(Default)]
+pub(crate) struct NetworkHealth {
+    pub network: String,
+    pub total: i64,
+    pub failed: i64,
+    pub syncing: i64,
+    pub min_block: Option<i32>,
+}
+
+/// The health of the deployments in `sites`, which must all be in the shard
+/// of `conn`, by network. The sites and which of them are `assigned` come
+/// from the primary since only the deployment details are in the shard
+pub(crate) fn network_health(
+    conn: &PgConnection,
+    sites: &[Arc<Site>],
+    assigned: &HashSet<DeploymentId>,
+) -> Result<Vec<NetworkHealth>, StoreError> {
+    use subgraph_deployment as d;
+
+    if sites.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let networks: HashMap<_, _> = sites
+        .iter()
+        .map(|site| (site.id, site.network.as_str()))
+        .collect();
+    let details = d::table
+        .filter(d::id.eq_any(sites.iter().map(|site| site.id)))
+        .select((d::id, d::failed, d::synced, d::latest_ethereum_block_number))
+        .load::<(DeploymentId, bool, bool, Option<BigDecimal>)>(conn)?;
+
+    let mut health: HashMap<&str, NetworkHealth> = HashMap::new();
+    for (id, failed, synced, latest_block) in details {
+        let network = networks[&id];
+        let health = health.entry(network).or_insert_with(|| NetworkHealth {
+            network: network.to_string(),
+            ..Default::default()
+        });
+        health.total += 1;
+        if failed {
+            health.failed += 1;
+        } else if !synced {
+            health.syncing += 1;
+        }
+        if !failed && assigned.contains(&id) {
+            let latest_block = latest_block.and_then(|block| block.to_i32());
+            health.min_block = match (health.min_block, latest_block) {
+                (Some(min), Some(block)) => Some(min.min(block)),
+                (min, block) => min.or(block),
+            };
+        }
+    }
+    Ok(health.into_values().collect())
+}
+
+/// The ids of the deployments in `sites` that have not processed any block
+/// yet. All `sites` must be in the shard of `conn`
+pub(crate) fn unprocessed_deployments(
+    conn: &PgConnection,
+    sites: &[Arc<Site>],
+) -> Result<Vec<DeploymentId>, StoreError> {
+    use subgraph_deployment as d;
+
+    Ok(d::table
+        .filter(d::id.eq_any(sites.iter().map(|site| site.id)))
+        .filter(d::latest_ethereum_block_number.is_null())
+        .select(d::id)
+        .load::<DeploymentId>(conn)?)
+}
+
 pub fn deployment_entity(
     conn: &PgConnection,
     site: &Site,
@@ -7,7 +7,7 @@ use diesel::{
     dsl::{any, exists, not, select},
     pg::Pg,
     serialize::Output,
-    sql_types::{Array, Integer, Text},
+    sql_types::{Array, BigInt, Integer, Text},
     types::{FromSql, ToSql},
 };
 use diesel::{
@@ -93,6 +93,7 @@ table! {
     subgraphs.subgraph_deployment_assignment {
         id -> Integer,
         node_id -> Text,
+        paused_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -271,7 +272,7 @@ impl Borrow<str> for Namespace {
 
 /// A marker that an `i32` references a deployment. Values of this type hold
 /// the primary key from the `deployment_schemas` table
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, AsExpression, FromSqlRow)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, AsExpression, FromSqlRow)]
 #[sql_type = "diesel::sql_types::Integer"]
 pub struct DeploymentId(i32);
 
@@ -484,6 +485,34 @@ mod queries {
             .collect::<Result<Vec<Site>, _>>()
     }
 
+    pub(super) fn subgraph_versions(
+        conn: &PgConnection,
+        name: &SubgraphName,
+    ) -> Result<Vec<status::SubgraphVersion>, StoreError> {
+        Ok(v::table
+            .inner_join(s::table.on(v::subgraph.eq(s::id)))
+            .filter(s::name.eq(name.as_str()))
+            .order_by((v::created_at.asc(), v::vid.asc()))
+            .select((
+                v::id,
+                v::deployment,
+                sql::<BigInt>("subgraphs.subgraph_version.created_at::bigint"),
+                s::current_version,
+                s::pending_version,
+            ))
+            .load::<(String, String, i64, Option<String>, Option<String>)>(conn)?
+            .into_iter()
+            .map(
+                |(id, deployment, created_at, current, pending)| status::SubgraphVersion {
+                    current: current.as_deref() == Some(id.as_str()),
+                    pending: pending.as_deref() == Some(id.as_str()),
+                    deployment,
+                    created_at: created_at.max(0) as u64,
+                },
+            )
+            .collect())
+    }
+
     pub(super) fn subgraph_version(
         conn: &PgConnection,
         name: &str,
@@ -570,6 +599,7 @@ mod queries {
         ds::table
             .inner_join(a::table.on(a::id.eq(ds::id)))
             .filter(a::node_id.eq(node.as_str()))
+            .filter(a::paused_at.is_null())
             .select(ds::all_columns)
             .load::<Schema>(conn)?
             .into_iter()
@@ -585,12 +615,15 @@ mod queries {
         let nodes: HashMap<_, _> = a::table
             .inner_join(ds::table.on(ds::id.eq(a::id)))
             .filter(ds::subgraph.eq(any(ids)))
-            .select((ds::subgraph, a::node_id))
-            .load::<(String, String)>(conn)?
+            .select((ds::subgraph, a::node_id, a::paused_at.is_not_null()))
+            .load::<(String, String, bool)>(conn)?
             .into_iter()
+            .map(|(subgraph, node, paused)| (subgraph, (node, paused)))
             .collect();
         for mut info in infos {
-            info.node = nodes.get(&info.subgraph).cloned()
+            let assignment = nodes.get(&info.subgraph);
+            info.node = assignment.map(|(node, _)| node.clone());
+            info.paused = assignment.map(|(_, paused)| *paused);
         }
         Ok(())
     }
@@ -616,6 +649,53 @@ mod queries {
             .transpose()
     }
 
+    /// The node that `site` is assigned to, and whether the deployment is
+    /// paused
+    pub(super) fn assignment_status(
+        conn: &PgConnection,
+        site: &Site,
+    ) -> Result<Option<(NodeId, bool)>, StoreError> {
+        a::table
+            .filter(a::id.eq(site.id))
+            .select((a::node_id, a::paused_at.is_not_null()))
+            .first::<(String, bool)>(conn)
+            .optional()?
+            .map(|(node, paused)| {
+                let node = NodeId::new(&node).map_err(|()| {
+                    constraint_violation!(
+                        "invalid node id `{}` in assignment for `{}`",
+                        node,
+                        site.deployment
+                    )
+                })?;
+                Ok((node, paused))
+            })
+            .transpose()
+    }
+
+    /// The node that each assigned deployment is assigned to, and whether
+    /// the deployment is paused
+    pub(super) fn assignment_statuses(
+        conn: &PgConnection,
+    ) -> Result<Vec<(DeploymentId, NodeId, bool)>, StoreError> {
+        a::table
+            .select((a::id, a::node_id, a::paused_at.is_not_null()))
+            .order_by(a::id)
+            .load::<(DeploymentId, String, bool)>(conn)?
+            .into_iter()
+            .map(|(id, node, paused)| {
+                let node = NodeId::new(&node).map_err(|()| {
+                    constraint_violation!(
+                        "invalid node id `{}` in assignment for deployment with id {}",
+                        node,
+                        id
+                    )
+                })?;
+                Ok((id, node, paused))
+            })
+            .collect()
+    }
+
     pub(super) fn version_info(
         conn: &PgConnection,
         version: &str,
@@ -1035,6 +1115,65 @@ impl<'a> Connection<'a> {
         }
     }
 
+    /// Pause the deployment `site` so that the node it is assigned to stops
+    /// indexing it, but keep its assignment. Pausing a deployment that is
+    /// already paused does nothing
+    pub fn pause_subgraph(&self, site: &Site) -> Result<Vec<EntityChange>, StoreError> {
+        use subgraph_deployment_assignment as a;
+
+        let conn = self.conn.as_ref();
+        let updates = update(a::table.filter(a::id.eq(site.id)))
+            .filter(a::paused_at.is_null())
+            .set(a::paused_at.eq(sql("now()")))
+            .execute(conn)?;
+        match updates {
+            0 => self.check_assigned(site).map(|()| vec![]),
+            1 => {
+                // Indexing nodes stop the deployment when its assignment is
+                // removed
+                let change =
+                    EntityChange::for_assignment(site.into(), EntityChangeOperation::Removed);
+                Ok(vec![change])
+            }
+            _ => {
+                // `id` is the primary key of the subgraph_deployment_assignment table,
+                // and we can therefore only update no or one entry
+                unreachable!()
+            }
+        }
+    }
+
+    /// Resume indexing the paused deployment `site` on the node it is
+    /// assigned to. Resuming a deployment that is not paused does nothing
+    pub fn resume_subgraph(&self, site: &Site) -> Result<Vec<EntityChange>, StoreError> {
+        use subgraph_deployment_assignment as a;
+
+        let conn = self.conn.as_ref();
+        let updates = update(a::table.filter(a::id.eq(site.id)))
+            .filter(a::paused_at.is_not_null())
+            .set(a::paused_at.eq(sql("null")))
+            .execute(conn)?;
+        match updates {
+            0 => self.check_assigned(site).map(|()| vec![]),
+            1 => {
+                let change = EntityChange::for_assignment(site.into(), EntityChangeOperation::Set);
+                Ok(vec![change])
+            }
+            _ => {
+                // `id` is the primary key of the subgraph_deployment_assignment table,
+                // and we can therefore only update no or one entry
+                unreachable!()
+            }
+        }
+    }
+
+    fn check_assigned(&self, site: &Site) -> Result<(), StoreError> {
+        match queries::assignment_status(self.conn.as_ref(), site)? {
+            Some(_) => Ok(()),
+            None => Err(StoreError::DeploymentNotFound(site.deployment.to_string())),
+        }
+    }
+
     /// Create a new site and possibly set it to the active site. This
     /// function only performs the basic operations for creation, and the
     /// caller must check that other conditions (like whether there already
@@ -1317,6 +1456,35 @@ impl<'a> Connection<'a> {
             .optional()?)
     }
 
+    /// Find all deployments that no subgraph version refers to, e.g.,
+    /// because creating the version failed after the deployment had been
+    /// created. Deployments that are assigned to a node, that are the
+    /// source of a running copy, or whose copy is still in progress are
+    /// left out since they are obviously still needed
+    pub fn orphaned_deployments(&self) -> Result<Vec<Site>, StoreError> {
+        use active_copies as cp;
+        use deployment_schemas as ds;
+        use subgraph_deployment_assignment as a;
+        use subgraph_version as v;
+
+        let assigned = a::table.filter(a::id.eq(ds::id));
+        let versions = v::table.filter(v::deployment.eq(ds::subgraph));
+        let copy_src = cp::table.filter(cp::src.eq(ds::id));
+        let copy_dst = cp::table.filter(cp::dst.eq(ds::id));
+
+        ds::table
+            .filter(not(exists(assigned)))
+            .filter(not(exists(versions)))
+            .filter(not(exists(copy_src)))
+            .filter(not(exists(copy_dst)))
+            .order_by(ds::id)
+            .select(ds::all_columns)
+            .load::<Schema>(self.conn.as_ref())?
+            .into_iter()
+            .map(Site::try_from)
+            .collect()
+    }
+
     /// Find all deployments that are not in use and add them to the
     /// `unused_deployments` table. Only values that are available in the
     /// primary will be filled in `unused_deployments`
@@ -1667,6 +1835,14 @@ impl Mirror {
         self.read(|conn| queries::assigned_node(conn, site))
     }
 
+    pub fn assignment_status(&self, site: &Site) -> Result<Option<(NodeId, bool)>, StoreError> {
+        self.read(|conn| queries::assignment_status(conn, site))
+    }
+
+    pub fn assignment_statuses(&self) -> Result<Vec<(DeploymentId, NodeId, bool)>, StoreError> {
+        self.read(|conn| queries::assignment_statuses(conn))
+    }
+
     pub fn find_active_site(&self, subgraph: &DeploymentHash) -> Result<Option<Site>, StoreError> {
         self.read(|conn| queries::find_active_site(conn, subgraph))
     }
@@ -1690,6 +1866,13 @@ impl Mirror {
         self.read(|conn| queries::subgraph_exists(conn, name))
     }
 
+    pub fn subgraph_versions(
+        &self,
+        name: &SubgraphName,
+    ) -> Result<Vec<status::SubgraphVersion>, StoreError> {
+        self.read(|conn| queries::subgraph_versions(conn, name))
+    }
+
     pub fn subgraph_version(
         &self,
         name: &str,
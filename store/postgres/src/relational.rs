@@ -14,8 +14,9 @@ mod ddl_tests;
 #[cfg(test)]
 mod query_tests;
 
+use diesel::sql_types::{BigInt, Text};
 use diesel::{connection::SimpleConnection, Connection};
-use diesel::{debug_query, OptionalExtension, PgConnection, RunQueryDsl};
+use diesel::{debug_query, sql_query, OptionalExtension, PgConnection, RunQueryDsl};
 use graph::cheap_clone::CheapClone;
 use graph::constraint_violation;
 use graph::data::graphql::TypeExt as _;
@@ -859,6 +860,50 @@ impl Layout {
         Ok(())
     }
 
+    /// Count the current entities of each type in this deployment, sorted
+    /// by entity type. Like `count_query`, this only counts the latest
+    /// version of each entity, and therefore needs to look at every table
+    /// in the deployment which can be slow for large deployments
+    pub fn entity_counts(&self, conn: &PgConnection) -> Result<Vec<(String, u64)>, StoreError> {
+        #[derive(QueryableByName)]
+        struct EntityCount {
+            #[sql_type = "Text"]
+            entity_type: String,
+            #[sql_type = "BigInt"]
+            count: i64,
+        }
+
+        let mut tables: Vec<_> = self.tables.values().collect();
+        if tables.is_empty() {
+            return Ok(vec![]);
+        }
+        tables.sort_by(|a, b| a.object.as_str().cmp(b.object.as_str()));
+
+        let query = tables
+            .iter()
+            .map(|table| {
+                let filter = if table.immutable {
+                    "".to_string()
+                } else {
+                    format!(" where block_range @> {}", BLOCK_NUMBER_MAX)
+                };
+                format!(
+                    "select '{}'::text as entity_type, count(*) from {}{}",
+                    table.object.as_str(),
+                    table.qualified_name.as_str(),
+                    filter
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\nunion all\n");
+
+        Ok(sql_query(query)
+            .load::<EntityCount>(conn)?
+            .into_iter()
+            .map(|count| (count.entity_type, count.count as u64))
+            .collect())
+    }
+
     pub fn is_cacheable(&self) -> bool {
         // This would be false if we still needed to migrate the Layout, but
         // since there are no migrations in the code right now, it is always
@@ -1,4 +1,5 @@
 use async_trait::async_trait;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use graph::{
@@ -9,7 +10,7 @@ use graph::{
         },
     },
     constraint_violation,
-    data::subgraph::status,
+    data::subgraph::{schema::SubgraphError, status},
     prelude::{
         tokio, web3::types::Address, BlockNumber, BlockPtr, CheapClone, DeploymentHash,
         PartialBlockPtr, QueryExecutionError, StoreError,
@@ -101,14 +102,82 @@ impl StatusStore for Store {
     fn status(&self, filter: status::Filter) -> Result<Vec<status::Info>, StoreError> {
         let mut infos = self.subgraph_store.status(filter)?;
         let ptrs = self.block_store.chain_head_pointers()?;
-        for info in &mut infos {
-            for chain in &mut info.chains {
-                chain.chain_head_block = ptrs.get(&chain.network).map(|ptr| ptr.to_owned().into());
-            }
-        }
+        fill_chain_heads(&mut infos, &ptrs);
         Ok(infos)
     }
 
+    fn status_page(
+        &self,
+        filter: status::Filter,
+        page: status::Page,
+    ) -> Result<Vec<status::Info>, StoreError> {
+        let ptrs = self.block_store.chain_head_pointers()?;
+        let heads = ptrs
+            .iter()
+            .map(|(network, ptr)| (network.clone(), ptr.number))
+            .collect();
+        let mut infos = self.subgraph_store.status_page(filter, page, &heads)?;
+        fill_chain_heads(&mut infos, &ptrs);
+        Ok(infos)
+    }
+
+    fn entity_counts(&self, id: &DeploymentHash) -> Result<Vec<(String, u64)>, StoreError> {
+        self.subgraph_store.entity_counts(id)
+    }
+
+    fn deployment_size(&self, id: &DeploymentHash) -> Result<status::DeploymentSize, StoreError> {
+        self.subgraph_store.deployment_size(id)
+    }
+
+    fn node_health(&self) -> Result<status::NodeHealth, StoreError> {
+        let ptrs = self.block_store.chain_head_pointers()?;
+        let mut health = status::NodeHealth {
+            store_reachable: true,
+            ..Default::default()
+        };
+        for network in self.subgraph_store.network_health()? {
+            health.total_deployments += network.total as u64;
+            health.failed_deployments += network.failed as u64;
+            health.syncing_deployments += network.syncing as u64;
+            let lag = ptrs
+                .get(&network.network)
+                .zip(network.min_block)
+                .map(|(head, block)| (head.number - block).max(0));
+            health.max_lag_blocks = health.max_lag_blocks.max(lag);
+        }
+        Ok(health)
+    }
+
+    fn pending_assignments(&self) -> Result<Vec<status::PendingAssignment>, StoreError> {
+        self.subgraph_store.pending_assignments()
+    }
+
+    fn health_history(
+        &self,
+        id: &DeploymentHash,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<status::HealthTransition>, StoreError> {
+        self.subgraph_store.health_history(id, first, skip)
+    }
+
+    fn health_at_block(
+        &self,
+        id: &DeploymentHash,
+        block: BlockNumber,
+    ) -> Result<Option<status::HealthTransition>, StoreError> {
+        self.subgraph_store.health_at_block(id, block)
+    }
+
+    fn non_fatal_errors(
+        &self,
+        id: &DeploymentHash,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<SubgraphError>, StoreError> {
+        self.subgraph_store.non_fatal_errors(id, first, skip)
+    }
+
     fn version_info(&self, version_id: &str) -> Result<VersionInfo, StoreError> {
         let mut info = self.subgraph_store.version_info(version_id)?;
 
@@ -143,6 +212,17 @@ impl StatusStore for Store {
             .await
     }
 
+    async fn get_proofs_of_indexing(
+        &self,
+        subgraph_id: &DeploymentHash,
+        indexer: &Option<Address>,
+        blocks: Vec<BlockPtr>,
+    ) -> Result<Vec<Option<[u8; 32]>>, StoreError> {
+        self.subgraph_store
+            .get_proofs_of_indexing(subgraph_id, indexer, blocks)
+            .await
+    }
+
     async fn get_public_proof_of_indexing(
         &self,
         subgraph_id: &DeploymentHash,
@@ -158,3 +238,12 @@ impl StatusStore for Store {
         Ok(self.block_store.query_permit_primary().await)
     }
 }
+
+/// Fill in the chain head of each chain of `infos` from `ptrs`, which lives in a different shard
+fn fill_chain_heads(infos: &mut [status::Info], ptrs: &HashMap<String, BlockPtr>) {
+    for info in infos {
+        for chain in &mut info.chains {
+            chain.chain_head_block = ptrs.get(&chain.network).map(|ptr| ptr.to_owned().into());
+        }
+    }
+}
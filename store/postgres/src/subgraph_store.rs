@@ -5,7 +5,7 @@ use diesel::{
     types::{FromSql, ToSql},
 };
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 use std::{fmt, io::Write};
@@ -19,7 +19,10 @@ use graph::{
     },
     constraint_violation,
     data::query::QueryTarget,
-    data::subgraph::{schema::DeploymentCreate, status},
+    data::subgraph::{
+        schema::{DeploymentCreate, SubgraphError},
+        status, SubgraphFeature,
+    },
     prelude::StoreEvent,
     prelude::{
         anyhow, futures03::future::join_all, lazy_static, o, web3::types::Address, ApiSchema,
@@ -43,7 +46,7 @@ use crate::{
 };
 use crate::{
     deployment_store::{DeploymentStore, ReplicaId},
-    detail::DeploymentDetail,
+    detail::{self, DeploymentDetail},
     primary::UnusedDeployment,
 };
 
@@ -234,6 +237,15 @@ impl SubgraphStore {
         self.inner.get_proof_of_indexing(id, indexer, block).await
     }
 
+    pub(crate) async fn get_proofs_of_indexing(
+        &self,
+        id: &DeploymentHash,
+        indexer: &Option<Address>,
+        blocks: Vec<BlockPtr>,
+    ) -> Result<Vec<Option<[u8; 32]>>, StoreError> {
+        self.inner.get_proofs_of_indexing(id, indexer, blocks).await
+    }
+
     pub(crate) async fn get_public_proof_of_indexing(
         &self,
         id: &DeploymentHash,
@@ -406,6 +418,119 @@ impl SubgraphStoreInner {
         store.find_layout(site)
     }
 
+    pub(crate) fn health_history(
+        &self,
+        id: &DeploymentHash,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<status::HealthTransition>, StoreError> {
+        let (store, _) = self.store(id)?;
+        store.health_history(id, first, skip)
+    }
+
+    pub(crate) fn health_at_block(
+        &self,
+        id: &DeploymentHash,
+        block: BlockNumber,
+    ) -> Result<Option<status::HealthTransition>, StoreError> {
+        let (store, _) = self.store(id)?;
+        store.health_at_block(id, block)
+    }
+
+    pub(crate) fn non_fatal_errors(
+        &self,
+        id: &DeploymentHash,
+        first: usize,
+        skip: usize,
+    ) -> Result<Vec<SubgraphError>, StoreError> {
+        let (store, _) = self.store(id)?;
+        store.non_fatal_errors(id, first, skip)
+    }
+
+    pub(crate) fn entity_counts(
+        &self,
+        id: &DeploymentHash,
+    ) -> Result<Vec<(String, u64)>, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.entity_counts(site)
+    }
+
+    pub(crate) fn deployment_size(
+        &self,
+        id: &DeploymentHash,
+    ) -> Result<status::DeploymentSize, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.deployment_size(site)
+    }
+
+    /// The health of the deployments in each shard, grouped by network
+    pub(crate) fn network_health(&self) -> Result<Vec<detail::NetworkHealth>, StoreError> {
+        // Which deployments exist and are assigned is only known to the
+        // primary; the shards only have the details of their deployments
+        let assigned: HashSet<_> = self
+            .mirror
+            .assignment_statuses()?
+            .into_iter()
+            .map(|(id, _, _)| id)
+            .collect();
+        let sites = self.mirror.find_sites(&[], true)?;
+
+        let mut health = Vec::new();
+        for (shard, sites) in self.deployments_by_shard(sites)? {
+            let store = self
+                .stores
+                .get(&shard)
+                .ok_or_else(|| StoreError::UnknownShard(shard.to_string()))?;
+            health.extend(store.network_health(&sites, &assigned)?);
+        }
+        Ok(health)
+    }
+
+    pub(crate) fn pending_assignments(&self) -> Result<Vec<status::PendingAssignment>, StoreError> {
+        // Assignments are in the primary, but whether a deployment has
+        // processed a block yet is only known to its shard
+        let assignments: HashMap<_, _> = self
+            .mirror
+            .assignment_statuses()?
+            .into_iter()
+            .map(|(id, node, paused)| (id, (node, paused)))
+            .collect();
+        if assignments.is_empty() {
+            return Ok(Vec::new());
+        }
+        let ids: Vec<_> = assignments.keys().copied().collect();
+        let sites = self.mirror.find_sites_by_id(&ids)?;
+
+        let mut pending = Vec::new();
+        for (shard, sites) in self.deployments_by_shard(sites)? {
+            let store = self
+                .stores
+                .get(&shard)
+                .ok_or_else(|| StoreError::UnknownShard(shard.to_string()))?;
+            let unprocessed: HashSet<_> =
+                store.unprocessed_deployments(&sites)?.into_iter().collect();
+            pending.extend(
+                sites
+                    .into_iter()
+                    .filter(|site| unprocessed.contains(&site.id)),
+            );
+        }
+        pending.sort_by_key(|site| site.id);
+
+        Ok(pending
+            .into_iter()
+            .map(|site| {
+                let (node, paused) = &assignments[&site.id];
+                status::PendingAssignment {
+                    deployment: site.deployment.to_string(),
+                    node: node.to_string(),
+                    paused: *paused,
+                    network: site.network.clone(),
+                }
+            })
+            .collect())
+    }
+
     fn place_on_node(
         &self,
         mut nodes: Vec<NodeId>,
@@ -773,6 +898,12 @@ impl SubgraphStoreInner {
         self.primary_conn()?.list_unused_deployments(filter)
     }
 
+    /// Deployments that no subgraph version refers to, see
+    /// `primary::Connection::orphaned_deployments`
+    pub fn orphaned_deployments(&self) -> Result<Vec<Site>, StoreError> {
+        self.primary_conn()?.orphaned_deployments()
+    }
+
     /// Remove a deployment, i.e., all its data and metadata. This is only permissible
     /// if the deployment is unused in the sense that it is neither the current nor
     /// pending version of any subgraph, and is not currently assigned to any node
@@ -807,7 +938,8 @@ impl SubgraphStoreInner {
         Ok(())
     }
 
-    pub(crate) fn status(&self, filter: status::Filter) -> Result<Vec<status::Info>, StoreError> {
+    /// The sites of the deployments that `filter` selects
+    fn filtered_sites(&self, filter: status::Filter) -> Result<Vec<Site>, StoreError> {
         let sites = match filter {
             status::Filter::SubgraphName(name) => {
                 let deployments = self.mirror.deployments_for_subgraph(&name)?;
@@ -828,12 +960,66 @@ impl SubgraphStoreInner {
             status::Filter::Deployments(deployments) => {
                 self.mirror.find_sites(&deployments, true)?
             }
+            status::Filter::DeploymentsWithHealth(deployments, health)
+                if deployments.is_empty() =>
+            {
+                let mut ids = Vec::new();
+                for store in self.stores.values() {
+                    ids.extend(store.deployments_with_health(&[], &health)?);
+                }
+                if ids.is_empty() {
+                    return Ok(Vec::new());
+                }
+                self.mirror
+                    .find_sites_by_id(&ids)?
+                    .into_iter()
+                    .filter(|site| site.active)
+                    .collect()
+            }
+            status::Filter::DeploymentsWithHealth(deployments, health) => {
+                let sites = self.mirror.find_sites(&deployments, true)?;
+                let mut ids_by_shard: HashMap<&Shard, Vec<DeploymentId>> = HashMap::new();
+                for site in &sites {
+                    ids_by_shard.entry(&site.shard).or_default().push(site.id);
+                }
+                let mut matching = HashSet::new();
+                for (shard, ids) in ids_by_shard {
+                    let store = self
+                        .stores
+                        .get(shard)
+                        .ok_or_else(|| StoreError::UnknownShard(shard.to_string()))?;
+                    matching.extend(store.deployments_with_health(&ids, &health)?);
+                }
+                sites
+                    .into_iter()
+                    .filter(|site| matching.contains(&site.id))
+                    .collect()
+            }
             status::Filter::DeploymentIds(ids) => {
                 let ids: Vec<_> = ids.into_iter().map(|id| id.into()).collect();
                 self.mirror.find_sites_by_id(&ids)?
             }
+            status::Filter::Failed => {
+                let mut ids = Vec::new();
+                for store in self.stores.values() {
+                    ids.extend(store.failed_deployments()?);
+                }
+                if ids.is_empty() {
+                    return Ok(Vec::new());
+                }
+                self.mirror
+                    .find_sites_by_id(&ids)?
+                    .into_iter()
+                    .filter(|site| site.active)
+                    .collect()
+            }
         };
 
+        Ok(sites)
+    }
+
+    pub(crate) fn status(&self, filter: status::Filter) -> Result<Vec<status::Info>, StoreError> {
+        let sites = self.filtered_sites(filter)?;
         let by_shard: HashMap<Shard, Vec<Arc<Site>>> = self.deployments_by_shard(sites)?;
 
         // Go shard-by-shard to look up deployment statuses
@@ -849,6 +1035,81 @@ impl SubgraphStoreInner {
         Ok(infos)
     }
 
+    /// The statuses that `page` picks from those that `filter` selects. `heads` has the number
+    /// of the chain head of each network, which is needed to order by lag.
+    pub(crate) fn status_page(
+        &self,
+        filter: status::Filter,
+        page: status::Page,
+        heads: &HashMap<String, BlockNumber>,
+    ) -> Result<Vec<status::Info>, StoreError> {
+        let mut sites = self.filtered_sites(filter)?;
+        if let Some(network) = &page.network {
+            sites.retain(|site| &site.network == network);
+        }
+        if let Some(node) = &page.node {
+            let assigned: HashSet<_> = self
+                .mirror
+                .assignment_statuses()?
+                .into_iter()
+                .filter(|(_, node_id, _)| node_id.as_str() == node)
+                .map(|(id, _, _)| id)
+                .collect();
+            sites.retain(|site| assigned.contains(&site.id));
+        }
+        let by_shard: HashMap<Shard, Vec<Arc<Site>>> = self.deployments_by_shard(sites)?;
+
+        // Each shard returns its deployments in order, but only as many as could be on the
+        // page; the page is then picked from all of those
+        let limit = page.first.map(|first| first + page.skip);
+        let mut candidates = Vec::new();
+        for (shard, sites) in &by_shard {
+            let store = self
+                .stores
+                .get(shard)
+                .ok_or_else(|| StoreError::UnknownShard(shard.to_string()))?;
+            let networks: HashMap<_, _> = sites
+                .iter()
+                .map(|site| (site.id, site.network.as_str()))
+                .collect();
+            for (id, latest) in store.deployment_order(sites, heads, page.order, limit)? {
+                let head = networks
+                    .get(&id)
+                    .and_then(|network| heads.get(*network))
+                    .copied();
+                candidates.push((page.order.key(id.into(), latest, head), id));
+            }
+        }
+        candidates.sort();
+        let ids: Vec<_> = candidates
+            .into_iter()
+            .map(|(_, id)| id)
+            .skip(page.skip)
+            .take(page.first.unwrap_or(usize::MAX))
+            .collect();
+
+        let on_page: HashSet<_> = ids.iter().collect();
+        let mut infos = Vec::new();
+        for (shard, sites) in by_shard {
+            let sites: Vec<_> = sites
+                .into_iter()
+                .filter(|site| on_page.contains(&site.id))
+                .collect();
+            // No sites would mean all of them to `deployment_statuses`
+            if sites.is_empty() {
+                continue;
+            }
+            let store = self
+                .stores
+                .get(&shard)
+                .ok_or_else(|| StoreError::UnknownShard(shard.to_string()))?;
+            infos.extend(store.deployment_statuses(&sites)?);
+        }
+        self.mirror.fill_assignments(&mut infos)?;
+        infos.sort_by_key(|info| ids.iter().position(|id| *id == DeploymentId::from(info.id)));
+        Ok(infos)
+    }
+
     pub(crate) fn version_info(&self, version: &str) -> Result<VersionInfo, StoreError> {
         if let Some((deployment_id, created_at)) = self.mirror.version_info(version)? {
             let id = DeploymentHash::new(deployment_id.clone())
@@ -910,8 +1171,13 @@ impl SubgraphStoreInner {
         join_all(self.stores.values().map(|store| store.vacuum())).await
     }
 
-    pub fn rewind(&self, id: DeploymentHash, block_ptr_to: BlockPtr) -> Result<(), StoreError> {
-        let (store, site) = self.store(&id)?;
+    pub fn rewind(
+        &self,
+        deployment: &DeploymentLocator,
+        block_ptr_to: BlockPtr,
+    ) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let store = self.for_site(&site)?;
         let event = store.rewind(site, block_ptr_to)?;
         self.send_store_event(&event)
     }
@@ -926,6 +1192,16 @@ impl SubgraphStoreInner {
         store.get_proof_of_indexing(site, indexer, block).await
     }
 
+    pub(crate) async fn get_proofs_of_indexing(
+        &self,
+        id: &DeploymentHash,
+        indexer: &Option<Address>,
+        blocks: Vec<BlockPtr>,
+    ) -> Result<Vec<Option<[u8; 32]>>, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.get_proofs_of_indexing(site, indexer, blocks).await
+    }
+
     pub(crate) async fn get_public_proof_of_indexing(
         &self,
         id: &DeploymentHash,
@@ -1110,6 +1386,40 @@ impl SubgraphStoreTrait for SubgraphStore {
         self.mirror.assigned_node(site.as_ref())
     }
 
+    fn rewind(
+        &self,
+        deployment: &DeploymentLocator,
+        block_ptr_to: BlockPtr,
+    ) -> Result<(), StoreError> {
+        self.inner.rewind(deployment, block_ptr_to)
+    }
+
+    fn assignment_status(
+        &self,
+        deployment: &DeploymentLocator,
+    ) -> Result<Option<(NodeId, bool)>, StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        self.mirror.assignment_status(site.as_ref())
+    }
+
+    fn pause_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let pconn = self.primary_conn()?;
+        pconn.transaction(|| -> Result<_, StoreError> {
+            let changes = pconn.pause_subgraph(site.as_ref())?;
+            pconn.send_store_event(&self.sender, &StoreEvent::new(changes))
+        })
+    }
+
+    fn resume_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let pconn = self.primary_conn()?;
+        pconn.transaction(|| -> Result<_, StoreError> {
+            let changes = pconn.resume_subgraph(site.as_ref())?;
+            pconn.send_store_event(&self.sender, &StoreEvent::new(changes))
+        })
+    }
+
     fn assignments(&self, node: &NodeId) -> Result<Vec<DeploymentLocator>, StoreError> {
         self.mirror
             .assignments(node)
@@ -1120,6 +1430,13 @@ impl SubgraphStoreTrait for SubgraphStore {
         self.mirror.subgraph_exists(name)
     }
 
+    fn subgraph_versions(
+        &self,
+        name: &SubgraphName,
+    ) -> Result<Vec<status::SubgraphVersion>, StoreError> {
+        self.mirror.subgraph_versions(name)
+    }
+
     fn entity_changes_in_block(
         &self,
         subgraph_id: &DeploymentHash,
@@ -1213,6 +1530,27 @@ impl SubgraphStoreTrait for SubgraphStore {
         Ok(matches!(health, SubgraphHealth::Healthy))
     }
 
+    fn raw_manifest(&self, id: &DeploymentHash) -> Result<Option<String>, StoreError> {
+        let (store, site) = match self.store(id) {
+            Ok(store_and_site) => store_and_site,
+            Err(StoreError::DeploymentNotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        store.raw_manifest(&site)
+    }
+
+    fn detected_features(
+        &self,
+        id: &DeploymentHash,
+    ) -> Result<Option<BTreeSet<SubgraphFeature>>, StoreError> {
+        let (store, site) = match self.store(id) {
+            Ok(store_and_site) => store_and_site,
+            Err(StoreError::DeploymentNotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        store.detected_features(&site)
+    }
+
     /// Find the deployment locators for the subgraph with the given hash
     fn locators(&self, hash: &str) -> Result<Vec<DeploymentLocator>, StoreError> {
         Ok(self
@@ -1222,4 +1560,14 @@ impl SubgraphStoreTrait for SubgraphStore {
             .map(|site| site.into())
             .collect())
     }
+
+    fn active_locator(
+        &self,
+        hash: &DeploymentHash,
+    ) -> Result<Option<DeploymentLocator>, StoreError> {
+        Ok(self
+            .mirror
+            .find_active_site(hash)?
+            .map(|site| (&site).into()))
+    }
 }
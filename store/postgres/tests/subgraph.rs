@@ -96,6 +96,44 @@ fn reassign_subgraph() {
     })
 }
 
+#[test]
+fn pause_and_resume_subgraph() {
+    async fn setup() -> DeploymentLocator {
+        let id = DeploymentHash::new("pauseSubgraph").unwrap();
+        remove_subgraphs();
+        create_test_subgraph(&id, SUBGRAPH_GQL).await
+    }
+
+    fn is_paused(store: &SubgraphStore, deployment: &DeploymentLocator) -> bool {
+        let (_, paused) = store.assignment_status(deployment).unwrap().unwrap();
+        paused
+    }
+
+    run_test_sequentially(|store| async move {
+        let id = setup().await;
+        let store = store.subgraph_store();
+        let node = store.assigned_node(&id).unwrap().unwrap();
+
+        assert!(!is_paused(store.as_ref(), &id));
+
+        // Pausing twice only stops the deployment once, and keeps it assigned
+        let (_, events) = tap_store_events(|| store.pause_subgraph(&id).unwrap());
+        assert_eq!(vec![StoreEvent::new(vec![unassigned(&id)])], events);
+        let (_, events) = tap_store_events(|| store.pause_subgraph(&id).unwrap());
+        assert!(events.is_empty());
+        assert!(is_paused(store.as_ref(), &id));
+        assert_eq!(Some(node.clone()), store.assigned_node(&id).unwrap());
+        assert!(!store.assignments(&node).unwrap().contains(&id));
+
+        let (_, events) = tap_store_events(|| store.resume_subgraph(&id).unwrap());
+        assert_eq!(vec![StoreEvent::new(vec![assigned(&id)])], events);
+        let (_, events) = tap_store_events(|| store.resume_subgraph(&id).unwrap());
+        assert!(events.is_empty());
+        assert!(!is_paused(store.as_ref(), &id));
+        assert!(store.assignments(&node).unwrap().contains(&id));
+    })
+}
+
 #[test]
 fn create_subgraph() {
     const SUBGRAPH_NAME: &str = "create/subgraph";
@@ -282,6 +320,22 @@ fn create_subgraph() {
         assert_eq!(Some(ID2), current.as_deref());
         assert_eq!(Some(ID3), pending.as_deref());
 
+        // All versions are listed, oldest first
+        let all_versions: Vec<_> = store
+            .subgraph_versions(&name)
+            .unwrap()
+            .into_iter()
+            .map(|version| (version.deployment, version.current, version.pending))
+            .collect();
+        assert_eq!(
+            vec![
+                (ID1.to_string(), false, false),
+                (ID2.to_string(), true, false),
+                (ID3.to_string(), false, true),
+            ],
+            all_versions
+        );
+
         // Deploying that same thing again changes nothing
         let (deployment3_again, events) = deploy(store.as_ref(), ID3, MODE);
         assert!(events.is_empty());
@@ -429,6 +483,69 @@ fn status() {
     })
 }
 
+#[test]
+fn status_page() {
+    use graph::data::subgraph::status;
+
+    const NAMES: [&str; 3] = ["pageSubgraph1", "pageSubgraph2", "pageSubgraph3"];
+
+    fn page(order: status::Order, first: Option<usize>, skip: usize) -> status::Page {
+        status::Page {
+            network: None,
+            node: None,
+            order,
+            first,
+            skip,
+        }
+    }
+
+    fn names(infos: Vec<status::Info>) -> Vec<String> {
+        infos.into_iter().map(|info| info.subgraph).collect()
+    }
+
+    run_test_sequentially(|store| async move {
+        remove_subgraphs();
+        let mut deployments = Vec::new();
+        for name in NAMES {
+            let id = DeploymentHash::new(name).unwrap();
+            deployments.push(create_test_subgraph(&id, SUBGRAPH_GQL).await);
+        }
+        // Only the first deployment has processed a block
+        transact_and_wait(
+            &store.subgraph_store(),
+            &deployments[0],
+            BLOCK_ONE.clone(),
+            vec![],
+        )
+        .await
+        .unwrap();
+        let all = || status::Filter::Deployments(vec![]);
+
+        let infos = store
+            .status_page(all(), page(status::Order::Created, None, 0))
+            .unwrap();
+        assert_eq!(NAMES.to_vec(), names(infos));
+
+        let infos = store
+            .status_page(all(), page(status::Order::Created, Some(1), 1))
+            .unwrap();
+        assert_eq!(vec![NAMES[1]], names(infos));
+
+        let infos = store
+            .status_page(all(), page(status::Order::LatestBlock, Some(2), 1))
+            .unwrap();
+        assert_eq!(vec![NAMES[2], NAMES[0]], names(infos));
+
+        let mut other_network = page(status::Order::Created, None, 0);
+        other_network.network = Some("notANetwork".to_string());
+        assert!(store.status_page(all(), other_network).unwrap().is_empty());
+
+        let mut other_node = page(status::Order::Created, None, 0);
+        other_node.node = Some("notANode".to_string());
+        assert!(store.status_page(all(), other_node).unwrap().is_empty());
+    })
+}
+
 #[test]
 fn version_info() {
     const NAME: &str = "versionInfoSubgraph";
@@ -471,6 +588,53 @@ fn version_info() {
     })
 }
 
+#[test]
+fn raw_manifest() {
+    const NAME: &str = "rawManifestSubgraph";
+    // Comments and flow mappings don't survive parsing and serializing the YAML again
+    const MANIFEST: &str = "# Deployed as written\nspecVersion: 0.0.4\ndataSources: [ ]\n";
+
+    run_test_sequentially(|store| async move {
+        remove_subgraphs();
+        let id = DeploymentHash::new(NAME).unwrap();
+        let schema = Schema::parse(SUBGRAPH_GQL, id.clone()).unwrap();
+        let manifest = SubgraphManifest::<graph_chain_ethereum::Chain> {
+            id: id.clone(),
+            spec_version: Version::new(1, 0, 0),
+            features: Default::default(),
+            description: None,
+            repository: None,
+            schema: schema.clone(),
+            data_sources: vec![],
+            graft: None,
+            templates: vec![],
+            offchain_data_sources: vec![],
+            chain: PhantomData,
+        };
+        let deployment =
+            DeploymentCreate::new(&manifest, None).raw_yaml(Some(MANIFEST.to_string()));
+        let name = SubgraphName::new(NAME).unwrap();
+        store
+            .subgraph_store()
+            .create_subgraph(name.clone())
+            .unwrap();
+        store
+            .subgraph_store()
+            .create_subgraph_deployment(
+                name,
+                &schema,
+                deployment,
+                NODE_ID.clone(),
+                NETWORK_NAME.to_string(),
+                SubgraphVersionSwitchingMode::Instant,
+            )
+            .unwrap();
+
+        let raw = store.subgraph_store().raw_manifest(&id).unwrap();
+        assert_eq!(Some(MANIFEST), raw.as_deref());
+    })
+}
+
 #[test]
 fn subgraph_error() {
     test_store::run_test_sequentially(|store| async move {
@@ -573,6 +737,8 @@ fn fatal_vs_non_fatal() {
 
 #[test]
 fn fail_unfail_deterministic_error() {
+    use graph::data::subgraph::status;
+
     const NAME: &str = "failUnfailDeterministic";
 
     async fn setup() -> DeploymentLocator {
@@ -647,6 +813,29 @@ fn fail_unfail_deterministic_error() {
         assert_eq!(true, vi.failed);
         assert_eq!(Some(1), vi.latest_ethereum_block_number);
 
+        // The failed deployment is the only one that is listed as failed
+        let failed = store.status(status::Filter::Failed).unwrap();
+        assert_eq!(1, failed.len());
+        assert_eq!(NAME, failed[0].subgraph);
+        let with_health = |health| {
+            store
+                .status(status::Filter::DeploymentsWithHealth(
+                    vec![NAME.to_string()],
+                    vec![health],
+                ))
+                .unwrap()
+                .len()
+        };
+        assert_eq!(1, with_health(SubgraphHealth::Failed));
+        assert_eq!(0, with_health(SubgraphHealth::Healthy));
+        assert_eq!(
+            Some("test"),
+            failed[0]
+                .fatal_error
+                .as_ref()
+                .map(|error| error.message.as_str())
+        );
+
         // Unfail the subgraph.
         let outcome = writable
             .unfail_deterministic_error(&BLOCKS[1], &BLOCKS[0])
@@ -660,6 +849,7 @@ fn fail_unfail_deterministic_error() {
         assert_eq!(&*NAME, vi.deployment_id.as_str());
         assert_eq!(false, vi.failed);
         assert_eq!(Some(0), vi.latest_ethereum_block_number);
+        assert!(store.status(status::Filter::Failed).unwrap().is_empty());
 
         test_store::remove_subgraphs();
     })
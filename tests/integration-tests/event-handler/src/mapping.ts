@@ -0,0 +1,8 @@
+import { Trigger as TriggerEvent } from "../generated/Contract/Contract";
+import { Trigger } from "../generated/schema";
+
+export function handleTrigger(event: TriggerEvent): void {
+  let trigger = new Trigger(event.block.number.toString());
+  trigger.x = event.params.x;
+  trigger.save();
+}
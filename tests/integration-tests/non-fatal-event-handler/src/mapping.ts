@@ -0,0 +1,12 @@
+import { Trigger as TriggerEvent } from "../generated/Contract/Contract";
+import { Trigger } from "../generated/schema";
+
+// An event with `x == 0` makes the handler fail deterministically, which the
+// subgraph records as a non-fatal error before it moves on to the next block
+export function handleTrigger(event: TriggerEvent): void {
+  assert(event.params.x != 0, "x must not be 0");
+
+  let trigger = new Trigger(event.block.number.toString());
+  trigger.x = event.params.x;
+  trigger.save();
+}
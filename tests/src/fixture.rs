@@ -1,10 +1,11 @@
 pub mod ethereum;
 
+use std::collections::{BTreeMap, HashMap};
 use std::marker::PhantomData;
 use std::process::Command;
 
 use crate::helpers::run_cmd;
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use async_stream::stream;
 use futures::{Stream, StreamExt};
 use graph::blockchain::block_stream::{
@@ -15,17 +16,25 @@ use graph::blockchain::{
     TriggersAdapter, TriggersAdapterSelector,
 };
 use graph::cheap_clone::CheapClone;
-use graph::components::store::{BlockStore, DeploymentLocator};
-use graph::env::ENV_VARS;
+use graph::components::link_resolver::{
+    JsonStreamValue, JsonValueStream, LinkResolver as LinkResolverTrait,
+};
+use graph::components::store::{BlockStore, DeploymentLocator, EntityType, StatusStore};
+use graph::data::query::QueryTarget;
+use graph::data::subgraph::schema::SubgraphHealth;
+use graph::data::subgraph::status;
 use graph::ipfs_client::IpfsClient;
 use graph::prelude::ethabi::ethereum_types::H256;
 use graph::prelude::{
-    async_trait, BlockNumber, DeploymentHash, LoggerFactory, MetricsRegistry, NodeId, SubgraphName,
-    SubgraphRegistrar, SubgraphStore as _, SubgraphVersionSwitchingMode,
+    async_trait, r, serde_json, AttributeNames, BlockNumber, DeploymentHash, EntityCollection,
+    EntityFilter, EntityQuery, Link, LoggerFactory, MetricsRegistry, NodeId, QueryStoreManager,
+    StoreError, SubgraphAssignmentProvider, SubgraphName, SubgraphRegistrar, SubgraphStore as _,
+    SubgraphVersionSwitchingMode,
 };
 use graph_core::{
-    LinkResolver, SubgraphAssignmentProvider as IpfsSubgraphAssignmentProvider,
-    SubgraphInstanceManager, SubgraphRegistrar as IpfsSubgraphRegistrar,
+    EventSink, LinkResolver, ProcessedEvent,
+    SubgraphAssignmentProvider as IpfsSubgraphAssignmentProvider, SubgraphInstanceManager,
+    SubgraphRegistrar as IpfsSubgraphRegistrar,
 };
 use graph_mock::MockMetricsRegistry;
 use graph_node::manager::PanicSubscriptionManager;
@@ -34,11 +43,18 @@ use graph_store_postgres::{ChainHeadUpdateListener, ChainStore, Store, SubgraphS
 use slog::Logger;
 use std::env::VarError;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
+use std::time::Duration;
 use tokio::fs::read_to_string;
 
-const NODE_ID: &str = "default";
+/// The node that tests run as unless they pick a different one with `stores_for_node`.
+pub const NODE_ID: &str = "default";
+
+/// How long `TestContext::run_to_block` waits for a subgraph to get to its target block.
+const RUN_TO_BLOCK_TIMEOUT: Duration = Duration::from_secs(60);
+/// How often `TestContext::run_to_block` checks how far the subgraph got.
+const RUN_TO_BLOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 pub async fn build_subgraph(dir: &str) -> DeploymentHash {
     // Test that IPFS is up.
@@ -75,12 +91,299 @@ pub async fn build_subgraph(dir: &str) -> DeploymentHash {
     DeploymentHash::new(line.trim_start_matches(ID_PREFIX)).unwrap()
 }
 
+/// The link resolver for tests that use the IPFS node at port 5001.
+pub fn ipfs_link_resolver() -> Arc<dyn LinkResolverTrait> {
+    let ipfs = IpfsClient::localhost();
+    Arc::new(LinkResolver::new(vec![ipfs], Default::default()))
+}
+
+/// A link resolver that serves files from memory, for tests that shouldn't depend on a running
+/// IPFS node. Links are looked up with or without an `/ipfs/` prefix.
+#[derive(Clone, Debug, Default)]
+pub struct MockLinkResolver {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl MockLinkResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves `contents` for `link`, e.g. the manifest for a deployment hash.
+    pub fn add(mut self, link: &str, contents: impl Into<Vec<u8>>) -> Self {
+        self.files.insert(
+            link.trim_start_matches("/ipfs/").to_string(),
+            contents.into(),
+        );
+        self
+    }
+
+    fn file(&self, link: &Link) -> Result<Vec<u8>, Error> {
+        self.files
+            .get(link.link.trim_start_matches("/ipfs/"))
+            .cloned()
+            .ok_or_else(|| anyhow!("no file for {}", link.link))
+    }
+}
+
+#[async_trait]
+impl LinkResolverTrait for MockLinkResolver {
+    fn with_timeout(&self, _timeout: Duration) -> Box<dyn LinkResolverTrait> {
+        Box::new(self.clone())
+    }
+
+    fn with_retries(&self) -> Box<dyn LinkResolverTrait> {
+        Box::new(self.clone())
+    }
+
+    async fn cat(&self, _logger: &Logger, link: &Link) -> Result<Vec<u8>, Error> {
+        self.file(link)
+    }
+
+    async fn get_block(&self, _logger: &Logger, link: &Link) -> Result<Vec<u8>, Error> {
+        self.file(link)
+    }
+
+    async fn json_stream(&self, _logger: &Logger, link: &Link) -> Result<JsonValueStream, Error> {
+        let file = String::from_utf8(self.file(link)?)?;
+        let values = file
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(line, text)| {
+                serde_json::from_str(text)
+                    .map(|value| JsonStreamValue { value, line })
+                    .map_err(Error::from)
+            })
+            .collect::<Vec<_>>();
+        Ok(Box::pin(futures::stream::iter(values)))
+    }
+}
+
+/// Serves the manifest of `hash` with a graft onto `base` at `block` added to it, and
+/// everything else from `inner`.
+#[derive(Clone)]
+struct GraftingLinkResolver {
+    inner: Arc<dyn LinkResolverTrait>,
+    hash: DeploymentHash,
+    base: DeploymentHash,
+    block: BlockNumber,
+}
+
+impl GraftingLinkResolver {
+    fn is_manifest(&self, link: &Link) -> bool {
+        link.link.trim_start_matches("/ipfs/") == self.hash.as_str()
+    }
+
+    fn add_graft(&self, manifest: Vec<u8>) -> Result<Vec<u8>, Error> {
+        use graph::prelude::serde_yaml::{self, Mapping, Value};
+
+        let mut manifest: Mapping = serde_yaml::from_slice(&manifest)?;
+
+        let mut graft = Mapping::new();
+        graft.insert("base".into(), self.base.to_string().into());
+        graft.insert("block".into(), serde_yaml::to_value(self.block)?);
+        manifest.insert("graft".into(), Value::Mapping(graft));
+
+        // Grafting has to be declared as a feature
+        let mut features = match manifest.remove(&"features".into()) {
+            Some(Value::Sequence(features)) => features,
+            _ => vec![],
+        };
+        if !features.contains(&"grafting".into()) {
+            features.push("grafting".into());
+        }
+        manifest.insert("features".into(), Value::Sequence(features));
+
+        Ok(serde_yaml::to_vec(&manifest)?)
+    }
+}
+
+#[async_trait]
+impl LinkResolverTrait for GraftingLinkResolver {
+    fn with_timeout(&self, timeout: Duration) -> Box<dyn LinkResolverTrait> {
+        Box::new(Self {
+            inner: self.inner.with_timeout(timeout).into(),
+            ..self.clone()
+        })
+    }
+
+    fn with_retries(&self) -> Box<dyn LinkResolverTrait> {
+        Box::new(Self {
+            inner: self.inner.with_retries().into(),
+            ..self.clone()
+        })
+    }
+
+    async fn cat(&self, logger: &Logger, link: &Link) -> Result<Vec<u8>, Error> {
+        let file = self.inner.cat(logger, link).await?;
+        if self.is_manifest(link) {
+            self.add_graft(file)
+        } else {
+            Ok(file)
+        }
+    }
+
+    async fn get_block(&self, logger: &Logger, link: &Link) -> Result<Vec<u8>, Error> {
+        self.inner.get_block(logger, link).await
+    }
+
+    async fn json_stream(&self, logger: &Logger, link: &Link) -> Result<JsonValueStream, Error> {
+        self.inner.json_stream(logger, link).await
+    }
+}
+
+/// The cursor that the fixture's block stream sends along with the event that leaves a subgraph at
+/// `ptr`. It identifies the block, so that a stream that is restarted with the cursor picks up
+/// right after it, like the firehose does.
+pub fn firehose_cursor(ptr: &BlockPtr) -> FirehoseCursor {
+    FirehoseCursor::from(format!("{}:{}", ptr.number, ptr.hash_hex()))
+}
+
+/// The cursor that the fixture's block stream sends along with a revert to `ptr`. Like an undo
+/// cursor of the firehose, it differs from the cursor that was sent when `ptr` was processed:
+/// it also says where in the chain the stream went on after the revert, so that a stream that is
+/// restarted with it continues on the branch that caused the revert.
+fn firehose_undo_cursor(ptr: &BlockPtr, next: usize) -> FirehoseCursor {
+    FirehoseCursor::from(format!("{}:undo:{}", firehose_cursor(ptr), next))
+}
+
 pub fn test_ptr(n: BlockNumber) -> BlockPtr {
     BlockPtr {
         hash: H256::from_low_u64_be(n as u64).into(),
         number: n,
     }
 }
+
+/// The pointer for block `n` on the `fork`-th fork of the test chain. Fork `0` is the main
+/// chain, whose pointers are the ones from `test_ptr`; blocks with the same number on different
+/// forks get different hashes, so that reorgs between them can be told apart.
+pub fn test_ptr_fork(n: BlockNumber, fork: u64) -> BlockPtr {
+    if fork == 0 {
+        return test_ptr(n);
+    }
+    BlockPtr {
+        hash: H256::from_low_u64_be(fork << 32 | n as u64).into(),
+        number: n,
+    }
+}
+
+/// Builds the blocks for a test chain, making sure that every block points to the right parent.
+/// Blocks are appended to the current branch with `block`; `fork_at` starts a new branch at an
+/// earlier block, and the blocks on it replace the ones after that block through a reorg.
+pub struct ChainBuilder<C: Blockchain> {
+    blocks: Vec<BlockWithTriggers<C>>,
+    /// Builds a block from its parent pointer and its own pointer.
+    make_block: fn(BlockPtr, BlockPtr) -> BlockWithTriggers<C>,
+    /// The pointers of the blocks on the current branch, starting with the genesis block.
+    branch: Vec<BlockPtr>,
+    fork: u64,
+}
+
+impl<C: Blockchain> ChainBuilder<C> {
+    pub fn new(
+        genesis: BlockWithTriggers<C>,
+        make_block: fn(BlockPtr, BlockPtr) -> BlockWithTriggers<C>,
+    ) -> Self {
+        Self {
+            branch: vec![genesis.ptr()],
+            blocks: vec![genesis],
+            make_block,
+            fork: 0,
+        }
+    }
+
+    /// Appends block `number` to the current branch.
+    pub fn block(mut self, number: BlockNumber) -> Self {
+        let parent = self.branch.last().unwrap().clone();
+        assert!(
+            number > parent.number,
+            "block {} must come after its parent {}",
+            number,
+            parent.number
+        );
+
+        let ptr = test_ptr_fork(number, self.fork);
+        self.blocks.push((self.make_block)(parent, ptr.clone()));
+        self.branch.push(ptr);
+        self
+    }
+
+    /// Starts a new branch at block `number` of the current branch. The blocks appended next
+    /// get new hashes, and the stream reorgs to them from the blocks of the current branch.
+    pub fn fork_at(mut self, number: BlockNumber) -> Self {
+        let idx = self
+            .branch
+            .iter()
+            .position(|ptr| ptr.number == number)
+            .unwrap_or_else(|| panic!("block {} is not on the current branch", number));
+        self.branch.truncate(idx + 1);
+        self.fork += 1;
+        self
+    }
+
+    /// Reorgs the last `depth` blocks of the current branch, which has to end at `at_block`: the
+    /// chain forks right before them, and blocks with the same numbers but new hashes are
+    /// appended to the new branch. The stream then reverts exactly `depth` blocks when it gets to
+    /// the first block of the new branch, and processing continues on the new branch up to
+    /// `at_block`. See `TestContext::assert_reverted_to`.
+    pub fn reorg(self, at_block: BlockNumber, depth: usize) -> Self {
+        let tip = self.branch.last().unwrap().number;
+        assert_eq!(
+            tip, at_block,
+            "the reorg must happen at the tip {} of the current branch",
+            tip
+        );
+        assert!(
+            depth > 0 && depth < self.branch.len(),
+            "can't reorg {} blocks, the current branch has only {} blocks after genesis",
+            depth,
+            self.branch.len() - 1
+        );
+
+        let base = self.branch.len() - 1 - depth;
+        let numbers: Vec<_> = self.branch[base + 1..]
+            .iter()
+            .map(|ptr| ptr.number)
+            .collect();
+        let base_number = self.branch[base].number;
+        numbers
+            .into_iter()
+            .fold(self.fork_at(base_number), |builder, number| {
+                builder.block(number)
+            })
+    }
+
+    /// Replaces the triggers of the last block.
+    pub fn with_triggers(mut self, triggers: Vec<C::TriggerData>) -> Self {
+        self.blocks.last_mut().unwrap().trigger_data = triggers;
+        self
+    }
+
+    /// Makes the stream revert to block `number` of the current branch without processing a
+    /// block after it, like the firehose does when it undoes blocks before the new branch shows
+    /// up. Blocks appended next start a new branch at block `number`.
+    pub fn rewind_to(self, number: BlockNumber) -> Self
+    where
+        C::TriggerData: Clone,
+    {
+        let mut builder = self.fork_at(number);
+        let ptr = builder.branch.last().unwrap().clone();
+        let block = builder
+            .blocks
+            .iter()
+            .find(|block| block.ptr() == ptr)
+            .unwrap()
+            .clone();
+        builder.blocks.push(block);
+        builder
+    }
+
+    /// The blocks in the order in which they were added, ready for a `StaticStreamBuilder`.
+    pub fn build(self) -> Vec<BlockWithTriggers<C>> {
+        self.blocks
+    }
+}
 pub struct TestContext {
     pub logger_factory: LoggerFactory,
     pub provider: Arc<
@@ -90,83 +393,612 @@ pub struct TestContext {
     >,
     pub store: Arc<SubgraphStore>,
     pub deployment_locator: DeploymentLocator,
+    subgraph_name: SubgraphName,
+    hash: DeploymentHash,
+    network_store: Arc<Store>,
+    event_sink: EventSink,
+    stream_recorder: StreamRecorder,
+}
+
+impl TestContext {
+    /// The fields of the entity of `entity_type` with `id` as of block `block`, or `None` if
+    /// there was no such entity at that block.
+    pub async fn query_entity(
+        &self,
+        entity_type: &str,
+        id: &str,
+        block: &BlockPtr,
+    ) -> Option<BTreeMap<String, r::Value>> {
+        let query_store = self
+            .network_store
+            .query_store(QueryTarget::Deployment(self.hash.clone()), false)
+            .await
+            .expect("failed to get a query store");
+        let query = EntityQuery::new(
+            self.hash.clone(),
+            block.number,
+            EntityCollection::All(vec![(
+                EntityType::new(entity_type.to_string()),
+                AttributeNames::All,
+            )]),
+        )
+        .filter(EntityFilter::Equal("id".to_string(), id.into()));
+        let mut entities = query_store
+            .find_query_values(query)
+            .expect("failed to query entity");
+        assert!(
+            entities.len() <= 1,
+            "found {} entities of type {} with id {}",
+            entities.len(),
+            entity_type,
+            id
+        );
+        entities.pop().map(|entity| {
+            entity
+                .into_iter()
+                .map(|(field, value)| (field.to_string(), value))
+                .collect()
+        })
+    }
+
+    /// Starts the subgraph and waits until it has processed `stop_block`. Fails if the subgraph
+    /// fails deterministically, or if it doesn't get to `stop_block` within
+    /// `RUN_TO_BLOCK_TIMEOUT`. Non-deterministic failures don't stop the wait, since the subgraph
+    /// retries them, e.g. after `faulty_chain` failed on purpose.
+    pub async fn run_to_block(&self, stop_block: &BlockPtr) -> Result<(), Error> {
+        SubgraphAssignmentProvider::start(
+            self.provider.as_ref(),
+            self.deployment_locator.clone(),
+            None,
+        )
+        .await?;
+
+        let last_error = Mutex::new(None);
+        let wait = async {
+            loop {
+                tokio::time::sleep(RUN_TO_BLOCK_POLL_INTERVAL).await;
+
+                let info = self
+                    .network_store
+                    .status(status::Filter::Deployments(vec![self.hash.to_string()]))?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow!("no status for deployment {}", self.hash))?;
+                if info.health == SubgraphHealth::Failed {
+                    match info.fatal_error {
+                        Some(error) if !error.deterministic => {
+                            *last_error.lock().unwrap() = Some(error.message);
+                        }
+                        error => {
+                            return Err(anyhow!(
+                                "subgraph failed unexpectedly: {}",
+                                error.map_or_else(|| "no error".to_string(), |error| error.message)
+                            ))
+                        }
+                    }
+                }
+                let latest_block = info
+                    .chains
+                    .into_iter()
+                    .next()
+                    .and_then(|chain| chain.latest_block)
+                    .map(|block| block.to_ptr());
+                if latest_block.as_ref() == Some(stop_block) {
+                    return Ok(());
+                }
+            }
+        };
+        tokio::time::timeout(RUN_TO_BLOCK_TIMEOUT, wait)
+            .await
+            .map_err(|_| {
+                let last_error = last_error.lock().unwrap().take();
+                anyhow!(
+                    "subgraph did not reach block {} within {:?}{}",
+                    stop_block,
+                    RUN_TO_BLOCK_TIMEOUT,
+                    last_error.map_or_else(String::new, |error| format!(
+                        ", it last failed with: {}",
+                        error
+                    ))
+                )
+            })?
+    }
+
+    /// Starts the subgraph and waits until the last event it handled is a revert to
+    /// `revert_to`, e.g., for a chain that ends with `ChainBuilder::rewind_to`. Unlike
+    /// `run_to_block`, this doesn't stop early when the subgraph gets to `revert_to` the first
+    /// time. Fails if the subgraph doesn't get there within `RUN_TO_BLOCK_TIMEOUT`.
+    pub async fn run_to_revert(&self, revert_to: &BlockPtr) -> Result<(), Error> {
+        SubgraphAssignmentProvider::start(
+            self.provider.as_ref(),
+            self.deployment_locator.clone(),
+            None,
+        )
+        .await?;
+
+        let expected = ProcessedEvent::Revert(revert_to.clone());
+        let wait = async {
+            while self.processed_events().last() != Some(&expected) {
+                tokio::time::sleep(RUN_TO_BLOCK_POLL_INTERVAL).await;
+            }
+        };
+        tokio::time::timeout(RUN_TO_BLOCK_TIMEOUT, wait)
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "subgraph did not revert to block {} within {:?}",
+                    revert_to,
+                    RUN_TO_BLOCK_TIMEOUT
+                )
+            })
+    }
+
+    /// The block stream events that the subgraph handled so far, in the order in which it
+    /// handled them.
+    pub fn processed_events(&self) -> Vec<ProcessedEvent> {
+        self.event_sink.events()
+    }
+
+    /// Starts the subgraph and waits until its block stream yields an error, e.g. for a reorg
+    /// deeper than the maximum reorg depth of the chain, and returns the message of that error.
+    /// Fails if the stream doesn't yield an error within `RUN_TO_BLOCK_TIMEOUT`.
+    pub async fn run_to_stream_error(&self) -> Result<String, Error> {
+        SubgraphAssignmentProvider::start(
+            self.provider.as_ref(),
+            self.deployment_locator.clone(),
+            None,
+        )
+        .await?;
+
+        let wait = async {
+            loop {
+                if let Some(error) = self.stream_errors().into_iter().next() {
+                    return error;
+                }
+                tokio::time::sleep(RUN_TO_BLOCK_POLL_INTERVAL).await;
+            }
+        };
+        tokio::time::timeout(RUN_TO_BLOCK_TIMEOUT, wait)
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "the block stream did not fail within {:?}",
+                    RUN_TO_BLOCK_TIMEOUT
+                )
+            })
+    }
+
+    /// The events that the fixture's block streams handed to the subgraph so far, in order.
+    /// Unlike `processed_events`, this includes events that the subgraph received but failed
+    /// to handle; errors that the streams yield are in `stream_errors`.
+    pub fn stream_events(&self) -> Vec<ExpectedEvent> {
+        self.stream_recorder.events(&self.hash)
+    }
+
+    /// The messages of the errors that the fixture's block streams yielded to the subgraph so
+    /// far, in order.
+    pub fn stream_errors(&self) -> Vec<String> {
+        self.stream_recorder.errors(&self.hash)
+    }
+
+    /// Asserts that the subgraph received exactly the block stream events in `expected`, and
+    /// shows both side by side if it didn't.
+    pub fn assert_events(&self, expected: Vec<ExpectedEvent>) {
+        let actual = self.stream_events();
+        if actual == expected {
+            return;
+        }
+        let show = |event: Option<&ExpectedEvent>| {
+            event.map_or_else(|| "-".to_string(), |event| event.to_string())
+        };
+        let mut diff = String::new();
+        for i in 0..actual.len().max(expected.len()) {
+            let (expected, actual) = (expected.get(i), actual.get(i));
+            let marker = if expected == actual { ' ' } else { '!' };
+            diff.push_str(&format!(
+                "{} {:>3}  {:<40} {}\n",
+                marker,
+                i,
+                show(expected),
+                show(actual)
+            ));
+        }
+        panic!(
+            "the subgraph received different block stream events than expected\n      {:<40} {}\n{}",
+            "expected", "actual", diff
+        );
+    }
+
+    /// Asserts that the subgraph went through a reorg that reverted exactly `depth` blocks,
+    /// one at a time, and left it at `block`, like the ones that `ChainBuilder::reorg` sets up.
+    pub fn assert_reverted_to(&self, block: &BlockPtr, depth: usize) {
+        let events = self.processed_events();
+        // The number of reverts in each run of consecutive reverts, and where the run ended
+        let mut reorgs: Vec<(BlockPtr, usize)> = Vec::new();
+        let mut in_run = false;
+        for event in events {
+            match event {
+                ProcessedEvent::Revert(ptr) if in_run => {
+                    let (to, reverted) = reorgs.last_mut().unwrap();
+                    *to = ptr;
+                    *reverted += 1;
+                }
+                ProcessedEvent::Revert(ptr) => {
+                    reorgs.push((ptr, 1));
+                    in_run = true;
+                }
+                ProcessedEvent::ProcessBlock(_) => in_run = false,
+            }
+        }
+        assert!(
+            reorgs.contains(&(block.clone(), depth)),
+            "the subgraph was not reverted by {} blocks to block {}; the reorgs were {:?}",
+            depth,
+            block,
+            reorgs
+        );
+    }
+
+    /// The firehose cursor that the store has for the deployment. The cursor is written together
+    /// with the block pointer, so after `run_to_block` it is the cursor for the stop block.
+    pub async fn block_cursor(&self) -> FirehoseCursor {
+        let logger = self
+            .logger_factory
+            .subgraph_logger(&self.deployment_locator);
+        self.store
+            .cheap_clone()
+            .writable(logger, self.deployment_locator.id)
+            .await
+            .expect("failed to get writable store")
+            .block_cursor()
+            .await
+    }
+
+    /// Asserts that the store has the cursor that the block stream sent along with `block`, so
+    /// that a restarted subgraph resumes right after it.
+    pub async fn assert_block_cursor(&self, block: &BlockPtr) {
+        assert_eq!(
+            firehose_cursor(block).to_string(),
+            self.block_cursor().await.to_string(),
+            "the deployment does not have the cursor for block {}",
+            block
+        );
+    }
+
+    /// Asserts that the store has the cursor that the block stream sent along with the revert to
+    /// `block`, rather than the one it sent when it processed `block`, so that a restarted
+    /// subgraph continues on the branch that caused the revert.
+    pub async fn assert_revert_cursor(&self, block: &BlockPtr) {
+        let cursor = self.block_cursor().await.to_string();
+        let prefix = format!("{}:undo:", firehose_cursor(block));
+        assert!(
+            cursor.starts_with(&prefix),
+            "the deployment does not have the cursor for the revert to block {}, it has `{}`",
+            block,
+            cursor
+        );
+    }
+
+    /// Asserts that handling block `block` recorded a deterministic non-fatal error, and that the
+    /// deployment kept going past that block instead of failing. Only subgraphs with the
+    /// `nonFatalErrors` feature do that; others fail on the error.
+    pub fn assert_non_fatal_error(&self, block: BlockNumber) {
+        let info = self
+            .network_store
+            .status(status::Filter::Deployments(vec![self.hash.to_string()]))
+            .expect("failed to get the status of the deployment")
+            .into_iter()
+            .next()
+            .expect("the deployment has a status");
+
+        assert_eq!(SubgraphHealth::Unhealthy, info.health);
+        assert!(
+            info.fatal_error.is_none(),
+            "the deployment failed: {:?}",
+            info.fatal_error
+        );
+        let error = info
+            .non_fatal_errors
+            .iter()
+            .find(|error| error.block_ptr.as_ref().map(|ptr| ptr.number) == Some(block))
+            .unwrap_or_else(|| {
+                panic!(
+                    "no non-fatal error at block {}; the errors are {:?}",
+                    block, info.non_fatal_errors
+                )
+            });
+        assert!(error.deterministic, "the error is not deterministic");
+
+        let latest_block = info
+            .chains
+            .into_iter()
+            .next()
+            .and_then(|chain| chain.latest_block)
+            .map(|block| block.number());
+        assert!(
+            latest_block.map_or(false, |latest| latest > block),
+            "the deployment did not get past block {}, it is at {:?}",
+            block,
+            latest_block
+        );
+    }
+
+    /// The number of entities of `entity_type` that the deployment currently has.
+    pub fn entity_count(&self, entity_type: &str) -> u64 {
+        self.network_store
+            .entity_counts(&self.hash)
+            .expect("failed to count entities")
+            .into_iter()
+            .find(|(name, _)| name == entity_type)
+            .map_or(0, |(_, count)| count)
+    }
+}
+
+impl Drop for TestContext {
+    fn drop(&mut self) {
+        // Also runs when a test panics, so that it doesn't leave its subgraph behind in the test
+        // database. Panicking here while already panicking would abort, so only report errors.
+        if let Err(e) = try_cleanup(&self.store, &self.subgraph_name, &self.hash) {
+            eprintln!("failed to clean up subgraph {}: {}", self.subgraph_name, e);
+        }
+    }
 }
 
 pub struct Stores {
+    /// The network that single-chain tests use; it is the first of the networks that the
+    /// stores were set up for.
     network_name: String,
+    /// The node that the test runs as, and that its deployments are created for.
+    node_id: NodeId,
     chain_head_listener: Arc<ChainHeadUpdateListener>,
     network_store: Arc<Store>,
-    chain_store: Arc<ChainStore>,
+    chain_stores: BTreeMap<String, Arc<ChainStore>>,
+    /// The block stream events that the chains built for these stores hand to deployments
+    stream_recorder: StreamRecorder,
 }
 
+impl Stores {
+    fn chain_store(&self, network_name: &str) -> Arc<ChainStore> {
+        self.chain_stores
+            .get(network_name)
+            .unwrap_or_else(|| panic!("no chain store for {}", network_name))
+            .cheap_clone()
+    }
+}
+
+/// Stores for the first network in the store config.
 pub async fn stores(store_config_path: &str) -> Stores {
-    let config = {
-        let config = read_to_string(store_config_path).await.unwrap();
-        let db_url = match std::env::var("THEGRAPH_STORE_POSTGRES_DIESEL_URL") {
-            Ok(url) => url,
-            Err(VarError::NotPresent) => panic!(
-                "to run end-to-end tests it is required to set \
-                                            $THEGRAPH_STORE_POSTGRES_DIESEL_URL to the test db url"
-            ),
-            Err(e) => panic!("{}", e.to_string()),
-        };
-        let config = config.replace("$THEGRAPH_STORE_POSTGRES_DIESEL_URL", &db_url);
-        Config::from_str(&config).expect("failed to create configuration")
+    let config = read_config(store_config_path).await;
+    let network_name = config.chains.chains.keys().next().unwrap().clone();
+    build_stores(config, &[network_name.as_str()], NODE_ID).await
+}
+
+/// Like `stores`, but for a test that runs as `node_id`, e.g. to create deployments that are
+/// assigned to different nodes. Deployment rules in the store config that list `indexers`
+/// still decide which node a deployment is assigned to.
+pub async fn stores_for_node(store_config_path: &str, node_id: &str) -> Stores {
+    let config = read_config(store_config_path).await;
+    let network_name = config.chains.chains.keys().next().unwrap().clone();
+    build_stores(config, &[network_name.as_str()], node_id).await
+}
+
+/// Stores for all of `network_names`, which must be configured in the store config. The first
+/// of them is the one that single-chain helpers use.
+pub async fn stores_for_networks(store_config_path: &str, network_names: &[&str]) -> Stores {
+    let config = read_config(store_config_path).await;
+    build_stores(config, network_names, NODE_ID).await
+}
+
+async fn read_config(store_config_path: &str) -> Config {
+    let config = read_to_string(store_config_path).await.unwrap();
+    let db_url = match std::env::var("THEGRAPH_STORE_POSTGRES_DIESEL_URL") {
+        Ok(url) => url,
+        Err(VarError::NotPresent) => panic!(
+            "to run end-to-end tests it is required to set \
+                                        $THEGRAPH_STORE_POSTGRES_DIESEL_URL to the test db url"
+        ),
+        Err(e) => panic!("{}", e.to_string()),
     };
+    let config = config.replace("$THEGRAPH_STORE_POSTGRES_DIESEL_URL", &db_url);
+    Config::from_str(&config).expect("failed to create configuration")
+}
+
+async fn build_stores(config: Config, network_names: &[&str], node_id: &str) -> Stores {
+    assert!(
+        !network_names.is_empty(),
+        "stores need at least one network"
+    );
 
     let logger = graph::log::logger(true);
     let mock_registry: Arc<dyn MetricsRegistry> = Arc::new(MockMetricsRegistry::new());
-    let node_id = NodeId::new(NODE_ID).unwrap();
+    let node_id = NodeId::new(node_id).unwrap();
     let store_builder =
         StoreBuilder::new(&logger, &node_id, &config, None, mock_registry.clone()).await;
 
-    let network_name: String = config.chains.chains.iter().next().unwrap().0.to_string();
     let chain_head_listener = store_builder.chain_head_update_listener();
-    let network_identifiers = vec![(
-        network_name.clone(),
-        (vec![ChainIdentifier {
-            net_version: "".into(),
-            genesis_block_hash: test_ptr(0).hash,
-        }]),
-    )];
+    let network_identifiers = network_names
+        .iter()
+        .map(|network_name| {
+            assert!(
+                config.chains.chains.contains_key(*network_name),
+                "network {} is not configured",
+                network_name
+            );
+            (
+                network_name.to_string(),
+                vec![ChainIdentifier {
+                    net_version: "".into(),
+                    genesis_block_hash: test_ptr(0).hash,
+                }],
+            )
+        })
+        .collect();
     let network_store = store_builder.network_store(network_identifiers);
-    let chain_store = network_store
-        .block_store()
-        .chain_store(network_name.as_ref())
-        .expect(format!("No chain store for {}", &network_name).as_ref());
+    let chain_stores = network_names
+        .iter()
+        .map(|network_name| {
+            let chain_store = network_store
+                .block_store()
+                .chain_store(network_name)
+                .expect(format!("No chain store for {}", network_name).as_ref());
+            (network_name.to_string(), chain_store)
+        })
+        .collect();
 
     Stores {
-        network_name,
+        network_name: network_names[0].to_string(),
+        node_id,
         chain_head_listener,
         network_store,
-        chain_store,
+        chain_stores,
+        stream_recorder: StreamRecorder::default(),
     }
 }
 
+/// Sets up a test for a subgraph that indexes a single chain, which is registered for the
+/// network of single-chain tests. The subgraph runs with static filters if `static_filters` is
+/// set, no matter what `EXPERIMENTAL_STATIC_FILTERS` says, so that tests can exercise both
+/// kinds of filters.
 pub async fn setup<C: Blockchain>(
     subgraph_name: SubgraphName,
     hash: &DeploymentHash,
     stores: &Stores,
     chain: C,
+    link_resolver: Arc<dyn LinkResolverTrait>,
+    static_filters: bool,
+) -> TestContext {
+    let mut blockchain_map = BlockchainMap::new();
+    blockchain_map.insert(stores.network_name.clone(), Arc::new(chain));
+    setup_with_chains(
+        subgraph_name,
+        hash,
+        stores,
+        blockchain_map,
+        link_resolver,
+        static_filters,
+    )
+    .await
+}
+
+/// Like `setup`, but the deployment `hash` grafts onto the deployment `base` at block
+/// `graft_block`, no matter what its manifest says about grafting. The registrar checks the
+/// graft like for any other deployment, and the base therefore has to have processed
+/// `graft_block` already, e.g. with `TestContext::run_to_block`. The test context of the base
+/// has to stay around as long as the grafted deployment is used, since dropping it removes the
+/// base.
+pub async fn setup_grafted<C: Blockchain>(
+    subgraph_name: SubgraphName,
+    hash: &DeploymentHash,
+    base: &DeploymentLocator,
+    graft_block: BlockNumber,
+    stores: &Stores,
+    chain: C,
+    link_resolver: Arc<dyn LinkResolverTrait>,
+    static_filters: bool,
+) -> TestContext {
+    let link_resolver = Arc::new(GraftingLinkResolver {
+        inner: link_resolver,
+        hash: hash.clone(),
+        base: base.hash.clone(),
+        block: graft_block,
+    });
+    setup(
+        subgraph_name,
+        hash,
+        stores,
+        chain,
+        link_resolver,
+        static_filters,
+    )
+    .await
+}
+
+/// Uploads a copy of the manifest of `hash` to IPFS in which all data sources start at
+/// `start_block`, and returns the hash of the copy. Everything else the manifest links to stays
+/// the same.
+pub async fn with_start_block(hash: &DeploymentHash, start_block: BlockNumber) -> DeploymentHash {
+    use graph::prelude::serde_yaml::{self, Mapping, Value};
+
+    let ipfs = IpfsClient::localhost();
+    let manifest = ipfs
+        .cat_all(hash.to_string(), Duration::from_secs(30))
+        .await
+        .expect("failed to get the manifest from IPFS");
+    let mut manifest: Mapping =
+        serde_yaml::from_slice(&manifest).expect("the manifest is valid YAML");
+    if let Some(Value::Sequence(data_sources)) = manifest.get_mut(&"dataSources".into()) {
+        for data_source in data_sources {
+            if let Some(Value::Mapping(source)) = data_source.get_mut("source") {
+                let start_block =
+                    serde_yaml::to_value(start_block).expect("a block number is valid YAML");
+                source.insert("startBlock".into(), start_block);
+            }
+        }
+    }
+    let manifest = serde_yaml::to_vec(&manifest).expect("failed to serialize the manifest");
+    let added = ipfs
+        .add(manifest)
+        .await
+        .expect("failed to add the manifest to IPFS");
+    DeploymentHash::new(added.hash).unwrap()
+}
+
+/// Sets up one deployment of the subgraph `hash` for each of `start_blocks`, side by side over
+/// the chains in `blockchain_map`, e.g. to check that the deployments progress independently.
+/// The deployments only differ in the start block of their data sources, see
+/// `with_start_block`, and each is the current version of its own subgraph, named after
+/// `subgraph_name` and its position in `start_blocks`. Dropping the test contexts removes all
+/// of the subgraphs.
+pub async fn setup_deployments(
+    subgraph_name: &SubgraphName,
+    hash: &DeploymentHash,
+    start_blocks: &[BlockNumber],
+    stores: &Stores,
+    blockchain_map: BlockchainMap,
+    link_resolver: Arc<dyn LinkResolverTrait>,
+    static_filters: bool,
+) -> Vec<TestContext> {
+    let mut contexts = Vec::with_capacity(start_blocks.len());
+    for (i, start_block) in start_blocks.iter().enumerate() {
+        let name = SubgraphName::new(format!("{}-{}", subgraph_name, i))
+            .expect("Subgraph name must contain only a-z, A-Z, 0-9, '-' and '_'");
+        let hash = with_start_block(hash, *start_block).await;
+        let ctx = setup_with_chains(
+            name,
+            &hash,
+            stores,
+            blockchain_map.clone(),
+            link_resolver.cheap_clone(),
+            static_filters,
+        )
+        .await;
+        contexts.push(ctx);
+    }
+    contexts
+}
+
+/// Sets up a test with all the chains in `blockchain_map`, for example to test a subgraph that
+/// indexes several networks. The deployment is created for the node of `stores`.
+pub async fn setup_with_chains(
+    subgraph_name: SubgraphName,
+    hash: &DeploymentHash,
+    stores: &Stores,
+    blockchain_map: BlockchainMap,
+    link_resolver: Arc<dyn LinkResolverTrait>,
+    static_filters: bool,
 ) -> TestContext {
     let logger = graph::log::logger(true);
     let logger_factory = LoggerFactory::new(logger.clone(), None);
     let mock_registry: Arc<dyn MetricsRegistry> = Arc::new(MockMetricsRegistry::new());
-    let node_id = NodeId::new(NODE_ID).unwrap();
+    let node_id = stores.node_id.clone();
 
     // Make sure we're starting from a clean state.
     let subgraph_store = stores.network_store.subgraph_store();
     cleanup(&subgraph_store, &subgraph_name, hash);
-
-    let mut blockchain_map = BlockchainMap::new();
-    blockchain_map.insert(stores.network_name.clone(), Arc::new(chain));
-
-    let static_filters = ENV_VARS.experimental_static_filters;
-
-    let ipfs = IpfsClient::localhost();
-    let link_resolver = Arc::new(LinkResolver::new(vec![ipfs], Default::default()));
+    stores.stream_recorder.clear(hash);
 
     let blockchain_map = Arc::new(blockchain_map);
+    let event_sink = EventSink::new();
     let subgraph_instance_manager = SubgraphInstanceManager::new(
         &logger_factory,
         subgraph_store.clone(),
@@ -174,7 +1006,8 @@ pub async fn setup<C: Blockchain>(
         mock_registry.clone(),
         link_resolver.cheap_clone(),
         static_filters,
-    );
+    )
+    .with_event_sink(event_sink.clone());
 
     // Create IPFS-based subgraph provider
     let subgraph_provider = Arc::new(IpfsSubgraphAssignmentProvider::new(
@@ -216,22 +1049,113 @@ pub async fn setup<C: Blockchain>(
         provider: subgraph_provider,
         store: subgraph_store,
         deployment_locator,
+        subgraph_name,
+        hash: hash.clone(),
+        network_store: stores.network_store.cheap_clone(),
+        event_sink,
+        stream_recorder: stores.stream_recorder.clone(),
     }
 }
 
 pub fn cleanup(subgraph_store: &SubgraphStore, name: &SubgraphName, hash: &DeploymentHash) {
-    let locators = subgraph_store.locators(hash).unwrap();
-    subgraph_store.remove_subgraph(name.clone()).unwrap();
+    try_cleanup(subgraph_store, name, hash).unwrap()
+}
+
+/// Removes the subgraph and its deployments. Either of them not existing, e.g. because an earlier
+/// run already removed them or on the first run against a fresh database, is not an error.
+fn try_cleanup(
+    subgraph_store: &SubgraphStore,
+    name: &SubgraphName,
+    hash: &DeploymentHash,
+) -> Result<(), StoreError> {
+    let locators = subgraph_store.locators(hash)?;
+    ignore_not_found(subgraph_store.remove_subgraph(name.clone()))?;
     for locator in locators {
-        subgraph_store.remove_deployment(locator.id.into()).unwrap();
+        ignore_not_found(subgraph_store.remove_deployment(locator.id.into()))?;
+    }
+    Ok(())
+}
+
+fn ignore_not_found(result: Result<(), StoreError>) -> Result<(), StoreError> {
+    match result {
+        Err(StoreError::DeploymentNotFound(_)) => Ok(()),
+        result => result,
     }
 }
 
 /// `chain` is the sequence of chain heads to be processed. If the next block to be processed in the
-/// chain is not a descendant of the previous one, reorgs will be emitted until it is.
+/// chain is not a descendant of the previous one, reorgs will be emitted until it is; blocks on
+/// a fork therefore need hashes that differ from the main chain, see `test_ptr_fork`. If
+/// `max_reorg_depth` is set, the stream fails instead once more blocks than that would have to be
+/// reverted. A block that the stream just reverted to is skipped, see `ChainBuilder::rewind_to`.
 /// See also: static-stream-builder
 struct StaticStreamBuilder<C: Blockchain> {
     chain: Vec<BlockWithTriggers<C>>,
+    max_reorg_depth: Option<usize>,
+    recorder: StreamRecorder,
+}
+
+impl<C: Blockchain> StaticStreamBuilder<C>
+where
+    C::TriggerData: Clone,
+{
+    /// The block that `cursor` was sent along with, and the index of the block in the chain that
+    /// the stream went on with after sending it. Fails if the cursor didn't come from this stream.
+    fn resume_point(&self, cursor: &FirehoseCursor) -> Result<(BlockPtr, usize), Error> {
+        let cursor = cursor.to_string();
+        let unknown = || anyhow!("unknown firehose cursor `{}`", cursor);
+        // An undo cursor leads to the branch that the stream reverted for, not the one that
+        // the block it reverted to was processed on
+        let (block_cursor, next) = match cursor.rsplit_once(":undo:") {
+            Some((block_cursor, next)) => (
+                block_cursor,
+                Some(next.parse::<usize>().map_err(|_| unknown())?),
+            ),
+            None => (cursor.as_str(), None),
+        };
+        let idx = self
+            .chain
+            .iter()
+            .position(|block| firehose_cursor(&block.ptr()).to_string() == block_cursor)
+            .ok_or_else(unknown)?;
+        Ok((self.chain[idx].ptr(), next.unwrap_or(idx + 1)))
+    }
+
+    /// Streams the chain to `deployment`, starting right after `current_block` if it is given.
+    fn build_stream(
+        &self,
+        deployment: &DeploymentLocator,
+        current_block: Option<BlockPtr>,
+    ) -> Box<dyn BlockStream<C>> {
+        let start = current_block.as_ref().map_or(0, |current_block| {
+            self.chain
+                .iter()
+                .position(|b| &b.ptr() == current_block)
+                .unwrap()
+                + 1
+        });
+        self.build_stream_from(deployment, current_block, start)
+    }
+
+    /// Streams the chain to `deployment`, which is at `current_block`, starting with the block
+    /// at index `start`.
+    fn build_stream_from(
+        &self,
+        deployment: &DeploymentLocator,
+        current_block: Option<BlockPtr>,
+        start: usize,
+    ) -> Box<dyn BlockStream<C>> {
+        Box::new(StaticStream {
+            stream: Box::pin(stream_events(
+                self.chain.clone(),
+                current_block,
+                start,
+                self.max_reorg_depth,
+            )),
+            deployment: deployment.hash.clone(),
+            recorder: self.recorder.clone(),
+        })
+    }
 }
 
 #[async_trait]
@@ -242,41 +1166,115 @@ where
     fn build_firehose(
         &self,
         _chain: &C,
-        _deployment: DeploymentLocator,
-        _block_cursor: FirehoseCursor,
+        deployment: DeploymentLocator,
+        block_cursor: FirehoseCursor,
         _start_blocks: Vec<graph::prelude::BlockNumber>,
         current_block: Option<graph::blockchain::BlockPtr>,
         _filter: Arc<C::TriggerFilter>,
         _unified_api_version: graph::data::subgraph::UnifiedMappingApiVersion,
     ) -> anyhow::Result<Box<dyn BlockStream<C>>> {
-        let current_idx = current_block.map(|current_block| {
-            self.chain
-                .iter()
-                .enumerate()
-                .find(|(_, b)| b.ptr() == current_block)
-                .unwrap()
-                .0 as usize
-        });
-        Ok(Box::new(StaticStream {
-            stream: Box::pin(stream_events(self.chain.clone(), current_idx)),
-        }))
+        // Like the firehose, resume from the cursor rather than the block pointer when there is one
+        if block_cursor.is_none() {
+            return Ok(self.build_stream(&deployment, current_block));
+        }
+        let (current_block, start) = self.resume_point(&block_cursor)?;
+        Ok(self.build_stream_from(&deployment, Some(current_block), start))
     }
 
     async fn build_polling(
         &self,
         _chain: Arc<C>,
-        _deployment: DeploymentLocator,
+        deployment: DeploymentLocator,
         _start_blocks: Vec<graph::prelude::BlockNumber>,
-        _subgraph_current_block: Option<graph::blockchain::BlockPtr>,
+        subgraph_current_block: Option<graph::blockchain::BlockPtr>,
         _filter: Arc<C::TriggerFilter>,
         _unified_api_version: graph::data::subgraph::UnifiedMappingApiVersion,
     ) -> anyhow::Result<Box<dyn BlockStream<C>>> {
-        unimplemented!("only firehose mode should be used for tests")
+        Ok(self.build_stream(&deployment, subgraph_current_block))
+    }
+}
+
+/// A block stream event, as tests expect the fixture's block streams to hand them to a
+/// subgraph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExpectedEvent {
+    ProcessBlock(BlockPtr),
+    Revert(BlockPtr),
+}
+
+impl<C: Blockchain> From<&BlockStreamEvent<C>> for ExpectedEvent {
+    fn from(event: &BlockStreamEvent<C>) -> Self {
+        match event {
+            BlockStreamEvent::ProcessBlock(block, _) => ExpectedEvent::ProcessBlock(block.ptr()),
+            BlockStreamEvent::Revert(ptr, _) => ExpectedEvent::Revert(ptr.clone()),
+        }
+    }
+}
+
+impl std::fmt::Display for ExpectedEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpectedEvent::ProcessBlock(ptr) => write!(f, "ProcessBlock({})", ptr),
+            ExpectedEvent::Revert(ptr) => write!(f, "Revert({})", ptr),
+        }
+    }
+}
+
+/// Records the events and errors that the fixture's block streams hand to each deployment, in
+/// order
+#[derive(Clone, Default)]
+struct StreamRecorder {
+    events: Arc<Mutex<HashMap<DeploymentHash, Vec<ExpectedEvent>>>>,
+    errors: Arc<Mutex<HashMap<DeploymentHash, Vec<String>>>>,
+}
+
+impl StreamRecorder {
+    fn record(&self, deployment: &DeploymentHash, event: ExpectedEvent) {
+        self.events
+            .lock()
+            .unwrap()
+            .entry(deployment.clone())
+            .or_default()
+            .push(event);
+    }
+
+    fn record_error(&self, deployment: &DeploymentHash, error: &Error) {
+        self.errors
+            .lock()
+            .unwrap()
+            .entry(deployment.clone())
+            .or_default()
+            .push(error.to_string());
+    }
+
+    fn events(&self, deployment: &DeploymentHash) -> Vec<ExpectedEvent> {
+        self.events
+            .lock()
+            .unwrap()
+            .get(deployment)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn errors(&self, deployment: &DeploymentHash) -> Vec<String> {
+        self.errors
+            .lock()
+            .unwrap()
+            .get(deployment)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn clear(&self, deployment: &DeploymentHash) {
+        self.events.lock().unwrap().remove(deployment);
+        self.errors.lock().unwrap().remove(deployment);
     }
 }
 
 struct StaticStream<C: Blockchain> {
     stream: Pin<Box<dyn Stream<Item = Result<BlockStreamEvent<C>, Error>> + Send>>,
+    deployment: DeploymentHash,
+    recorder: StreamRecorder,
 }
 
 impl<C: Blockchain> BlockStream<C> for StaticStream<C> {}
@@ -285,40 +1283,70 @@ impl<C: Blockchain> Stream for StaticStream<C> {
     type Item = Result<BlockStreamEvent<C>, Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.stream.poll_next_unpin(cx)
+        let poll = self.stream.poll_next_unpin(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(event))) => self.recorder.record(&self.deployment, event.into()),
+            Poll::Ready(Some(Err(e))) => self.recorder.record_error(&self.deployment, e),
+            _ => {}
+        }
+        poll
     }
 }
 
+/// Streams `blocks` to a subgraph that is at `current_block`, starting with the block at index
+/// `start`.
 fn stream_events<C: Blockchain>(
     blocks: Vec<BlockWithTriggers<C>>,
-    current_idx: Option<usize>,
+    current_block: Option<BlockPtr>,
+    start: usize,
+    max_reorg_depth: Option<usize>,
 ) -> impl Stream<Item = Result<BlockStreamEvent<C>, Error>>
 where
     C::TriggerData: Clone,
 {
     // See also: static-stream-builder
     stream! {
-        let current_block = current_idx.map(|idx| &blocks[idx]);
-        let mut current_ptr = current_block.map(|b| b.ptr());
-        let mut current_parent_ptr = current_block.and_then(|b| b.parent_ptr());
-        let skip = current_idx.map(|idx| idx + 1).unwrap_or(0);
-        let mut blocks_iter = blocks.iter().skip(skip).peekable();
-        while let Some(&block) = blocks_iter.peek() {
-            if block.parent_ptr() == current_ptr {
+        let parent_of = |ptr: &BlockPtr| {
+            blocks
+                .iter()
+                .find(|b| &b.ptr() == ptr)
+                .unwrap()
+                .block
+                .parent_ptr()
+        };
+        let mut current_parent_ptr = current_block.as_ref().and_then(|ptr| parent_of(ptr));
+        let mut current_ptr = current_block;
+        let mut blocks_iter = blocks.iter().enumerate().skip(start).peekable();
+        // Number of blocks reverted since the last block was processed
+        let mut reorg_depth = 0;
+        while let Some(&(idx, block)) = blocks_iter.peek() {
+            if current_ptr == Some(block.ptr()) {
+                // A block that the stream reverted to, see `ChainBuilder::rewind_to`
+                blocks_iter.next();
+            } else if block.parent_ptr() == current_ptr {
                 current_ptr = Some(block.ptr());
                 current_parent_ptr = block.parent_ptr();
+                reorg_depth = 0;
                 blocks_iter.next(); // Block consumed, advance the iterator.
-                yield Ok(BlockStreamEvent::ProcessBlock(block.clone(), FirehoseCursor::None));
+                let cursor = firehose_cursor(&block.ptr());
+                yield Ok(BlockStreamEvent::ProcessBlock(block.clone(), cursor));
             } else {
+                reorg_depth += 1;
+                if let Some(max_reorg_depth) = max_reorg_depth {
+                    if reorg_depth > max_reorg_depth {
+                        yield Err(anyhow::anyhow!(
+                            "reorg to block {} is deeper than the maximum reorg depth of {}",
+                            block.ptr(),
+                            max_reorg_depth
+                        ));
+                        break;
+                    }
+                }
                 let revert_to = current_parent_ptr.unwrap();
                 current_ptr = Some(revert_to.clone());
-                current_parent_ptr = blocks
-                    .iter()
-                    .find(|b| b.ptr() == revert_to)
-                    .unwrap()
-                    .block
-                    .parent_ptr();
-                yield Ok(BlockStreamEvent::Revert(revert_to, FirehoseCursor::None));
+                current_parent_ptr = parent_of(&revert_to);
+                let cursor = firehose_undo_cursor(&revert_to, idx);
+                yield Ok(BlockStreamEvent::Revert(revert_to, cursor));
             }
         }
     }
@@ -337,8 +1365,14 @@ impl<C: Blockchain> RuntimeAdapter<C> for NoopRuntimeAdapter<C> {
     }
 }
 
-struct NoopAdapterSelector<C> {
-    x: PhantomData<C>,
+struct NoopAdapterSelector<C: Blockchain> {
+    blocks: Vec<C::Block>,
+}
+
+impl<C: Blockchain> NoopAdapterSelector<C> {
+    fn new(blocks: Vec<C::Block>) -> Self {
+        Self { blocks }
+    }
 }
 
 impl<C: Blockchain> TriggersAdapterSelector<C> for NoopAdapterSelector<C> {
@@ -348,12 +1382,138 @@ impl<C: Blockchain> TriggersAdapterSelector<C> for NoopAdapterSelector<C> {
         _capabilities: &<C as Blockchain>::NodeCapabilities,
         _unified_api_version: graph::data::subgraph::UnifiedMappingApiVersion,
     ) -> Result<Arc<dyn graph::blockchain::TriggersAdapter<C>>, Error> {
-        Ok(Arc::new(NoopTriggersAdapter { x: PhantomData }))
+        Ok(Arc::new(NoopTriggersAdapter::new(self.blocks.clone())))
     }
 }
 
-struct NoopTriggersAdapter<C> {
-    x: PhantomData<C>,
+/// Selects a `FaultyTriggersAdapter` that fails according to `faults`. All adapters it selects
+/// share the same faults, so that a fault is used up no matter which adapter runs into it.
+struct FaultyAdapterSelector<C: Blockchain> {
+    blocks: Vec<C::Block>,
+    faults: Arc<Faults>,
+}
+
+impl<C: Blockchain> FaultyAdapterSelector<C> {
+    fn new(blocks: Vec<C::Block>, faults: Arc<Faults>) -> Self {
+        Self { blocks, faults }
+    }
+}
+
+impl<C: Blockchain> TriggersAdapterSelector<C> for FaultyAdapterSelector<C> {
+    fn triggers_adapter(
+        &self,
+        _loc: &DeploymentLocator,
+        _capabilities: &<C as Blockchain>::NodeCapabilities,
+        _unified_api_version: graph::data::subgraph::UnifiedMappingApiVersion,
+    ) -> Result<Arc<dyn graph::blockchain::TriggersAdapter<C>>, Error> {
+        Ok(Arc::new(FaultyTriggersAdapter {
+            inner: NoopTriggersAdapter::new(self.blocks.clone()),
+            faults: self.faults.clone(),
+        }))
+    }
+}
+
+/// A method fails `times` more times with `message`.
+#[derive(Default)]
+struct Fault {
+    times: usize,
+    message: String,
+}
+
+impl Fault {
+    fn trip(fault: &Mutex<Fault>) -> Result<(), Error> {
+        let mut fault = fault.lock().unwrap();
+        if fault.times == 0 {
+            return Ok(());
+        }
+        fault.times -= 1;
+        Err(anyhow!("{}", fault.message))
+    }
+}
+
+/// The errors that a test wants the triggers adapter to return. Once a method has failed as
+/// often as requested, it behaves like the `NoopTriggersAdapter` again.
+#[derive(Default)]
+pub struct Faults {
+    scan_triggers: Mutex<Fault>,
+    triggers_in_block: Mutex<Fault>,
+}
+
+impl Faults {
+    /// Makes the next `times` calls to `scan_triggers` fail with `message`.
+    pub fn scan_triggers(self, times: usize, message: &str) -> Self {
+        *self.scan_triggers.lock().unwrap() = Fault {
+            times,
+            message: message.to_string(),
+        };
+        self
+    }
+
+    /// Makes the next `times` calls to `triggers_in_block` fail with `message`.
+    pub fn triggers_in_block(self, times: usize, message: &str) -> Self {
+        *self.triggers_in_block.lock().unwrap() = Fault {
+            times,
+            message: message.to_string(),
+        };
+        self
+    }
+}
+
+/// A `NoopTriggersAdapter` that fails according to `faults`.
+struct FaultyTriggersAdapter<C: Blockchain> {
+    inner: NoopTriggersAdapter<C>,
+    faults: Arc<Faults>,
+}
+
+#[async_trait]
+impl<C: Blockchain> TriggersAdapter<C> for FaultyTriggersAdapter<C> {
+    async fn ancestor_block(
+        &self,
+        ptr: BlockPtr,
+        offset: BlockNumber,
+    ) -> Result<Option<<C as Blockchain>::Block>, Error> {
+        self.inner.ancestor_block(ptr, offset).await
+    }
+
+    async fn scan_triggers(
+        &self,
+        from: BlockNumber,
+        to: BlockNumber,
+        filter: &<C as Blockchain>::TriggerFilter,
+    ) -> Result<Vec<BlockWithTriggers<C>>, Error> {
+        Fault::trip(&self.faults.scan_triggers)?;
+        self.inner.scan_triggers(from, to, filter).await
+    }
+
+    async fn triggers_in_block(
+        &self,
+        logger: &Logger,
+        block: <C as Blockchain>::Block,
+        filter: &<C as Blockchain>::TriggerFilter,
+    ) -> Result<BlockWithTriggers<C>, Error> {
+        Fault::trip(&self.faults.triggers_in_block)?;
+        self.inner.triggers_in_block(logger, block, filter).await
+    }
+
+    async fn is_on_main_chain(&self, ptr: BlockPtr) -> Result<bool, Error> {
+        self.inner.is_on_main_chain(ptr).await
+    }
+
+    async fn parent_ptr(&self, block: &BlockPtr) -> Result<Option<BlockPtr>, Error> {
+        self.inner.parent_ptr(block).await
+    }
+}
+
+/// A triggers adapter that finds no triggers. `blocks` are the blocks that `scan_triggers` knows
+/// about.
+struct NoopTriggersAdapter<C: Blockchain> {
+    blocks: Vec<C::Block>,
+}
+
+impl<C: Blockchain> NoopTriggersAdapter<C> {
+    fn new(blocks: Vec<C::Block>) -> Self {
+        Self { blocks }
+    }
 }
 
 #[async_trait]
@@ -368,11 +1528,17 @@ impl<C: Blockchain> TriggersAdapter<C> for NoopTriggersAdapter<C> {
 
     async fn scan_triggers(
         &self,
-        _from: BlockNumber,
-        _to: BlockNumber,
+        from: BlockNumber,
+        to: BlockNumber,
         _filter: &<C as Blockchain>::TriggerFilter,
     ) -> Result<Vec<BlockWithTriggers<C>>, Error> {
-        todo!()
+        // Like `triggers_in_block`, every block in the range comes with no triggers.
+        Ok(self
+            .blocks
+            .iter()
+            .filter(|block| (from..=to).contains(&block.number()))
+            .map(|block| BlockWithTriggers::new(block.clone(), Vec::new()))
+            .collect())
     }
 
     async fn triggers_in_block(
@@ -2,13 +2,16 @@ use std::marker::PhantomData;
 use std::sync::Arc;
 
 use super::{
-    test_ptr, NoopAdapterSelector, NoopRuntimeAdapter, StaticStreamBuilder, Stores, NODE_ID,
+    test_ptr, ChainBuilder, Faults, FaultyAdapterSelector, NoopAdapterSelector, NoopRuntimeAdapter,
+    StaticStreamBuilder, Stores,
 };
-use graph::blockchain::BlockPtr;
+use graph::blockchain::{BlockPtr, TriggersAdapterSelector};
 use graph::cheap_clone::CheapClone;
 use graph::firehose::{FirehoseEndpoint, FirehoseEndpoints};
 use graph::prelude::ethabi::ethereum_types::H256;
-use graph::prelude::{LightEthereumBlock, LoggerFactory, NodeId};
+use graph::prelude::ethabi::{self, Event, Function, Token};
+use graph::prelude::web3::types::{Address, Bytes, Log, Transaction, U256};
+use graph::prelude::{EthereumCall, LightEthereumBlock, LoggerFactory};
 use graph::{blockchain::block_stream::BlockWithTriggers, prelude::ethabi::ethereum_types::U64};
 use graph_chain_ethereum::network::EthereumNetworkAdapters;
 use graph_chain_ethereum::{
@@ -19,12 +22,77 @@ use graph_chain_ethereum::{Chain, ENV_VARS};
 use graph_mock::MockMetricsRegistry;
 
 pub async fn chain(blocks: Vec<BlockWithTriggers<Chain>>, stores: &Stores) -> Chain {
+    chain_for_network(&stores.network_name, blocks, stores).await
+}
+
+/// Like `chain`, but for `network_name`, which must be one of the networks of `stores`.
+pub async fn chain_for_network(
+    network_name: &str,
+    blocks: Vec<BlockWithTriggers<Chain>>,
+    stores: &Stores,
+) -> Chain {
+    let known_blocks = blocks.iter().map(|b| b.block.clone()).collect();
+    let adapter_selector = NoopAdapterSelector::new(known_blocks);
+    build_chain(
+        network_name,
+        blocks,
+        stores,
+        Arc::new(adapter_selector),
+        None,
+    )
+    .await
+}
+
+/// Like `chain`, but its block stream fails instead of reverting more than `max_reorg_depth`
+/// blocks for a reorg.
+pub async fn chain_with_max_reorg_depth(
+    blocks: Vec<BlockWithTriggers<Chain>>,
+    stores: &Stores,
+    max_reorg_depth: usize,
+) -> Chain {
+    let known_blocks = blocks.iter().map(|b| b.block.clone()).collect();
+    let adapter_selector = NoopAdapterSelector::new(known_blocks);
+    build_chain(
+        &stores.network_name,
+        blocks,
+        stores,
+        Arc::new(adapter_selector),
+        Some(max_reorg_depth),
+    )
+    .await
+}
+
+/// Like `chain`, but its triggers adapter fails according to `faults`.
+pub async fn faulty_chain(
+    blocks: Vec<BlockWithTriggers<Chain>>,
+    stores: &Stores,
+    faults: Arc<Faults>,
+) -> Chain {
+    let known_blocks = blocks.iter().map(|b| b.block.clone()).collect();
+    let adapter_selector = FaultyAdapterSelector::new(known_blocks, faults);
+    build_chain(
+        &stores.network_name,
+        blocks,
+        stores,
+        Arc::new(adapter_selector),
+        None,
+    )
+    .await
+}
+
+async fn build_chain(
+    network_name: &str,
+    blocks: Vec<BlockWithTriggers<Chain>>,
+    stores: &Stores,
+    adapter_selector: Arc<dyn TriggersAdapterSelector<Chain>>,
+    max_reorg_depth: Option<usize>,
+) -> Chain {
     let logger = graph::log::logger(true);
     let logger_factory = LoggerFactory::new(logger.cheap_clone(), None);
-    let node_id = NodeId::new(NODE_ID).unwrap();
+    let node_id = stores.node_id.clone();
     let mock_registry = Arc::new(MockMetricsRegistry::new());
 
-    let chain_store = stores.chain_store.cheap_clone();
+    let chain_store = stores.chain_store(network_name);
 
     // This is needed bacause the stream builder only works for firehose and this will only be called if there
     // are > 1 firehose endpoints. The endpoint itself is never used because it's mocked.
@@ -37,7 +105,7 @@ pub async fn chain(blocks: Vec<BlockWithTriggers<Chain>>, stores: &Stores) -> Ch
 
     Chain::new(
         logger_factory.clone(),
-        stores.network_name.clone(),
+        network_name.to_string(),
         node_id,
         mock_registry.clone(),
         chain_store.cheap_clone(),
@@ -45,8 +113,12 @@ pub async fn chain(blocks: Vec<BlockWithTriggers<Chain>>, stores: &Stores) -> Ch
         firehose_endpoints,
         EthereumNetworkAdapters { adapters: vec![] },
         stores.chain_head_listener.cheap_clone(),
-        Arc::new(StaticStreamBuilder { chain: blocks }),
-        Arc::new(NoopAdapterSelector { x: PhantomData }),
+        Arc::new(StaticStreamBuilder {
+            chain: blocks,
+            max_reorg_depth,
+            recorder: stores.stream_recorder.clone(),
+        }),
+        adapter_selector,
         Arc::new(NoopRuntimeAdapter { x: PhantomData }),
         ENV_VARS.reorg_threshold,
         // We assume the tested chain is always ingestible for now
@@ -66,6 +138,134 @@ pub fn genesis() -> BlockWithTriggers<graph_chain_ethereum::Chain> {
     }
 }
 
+/// A `ChainBuilder` that starts with `genesis` and appends empty blocks.
+pub fn chain_builder() -> ChainBuilder<Chain> {
+    ChainBuilder::new(genesis(), empty_block)
+}
+
+impl ChainBuilder<Chain> {
+    /// Makes the last block emit `event` from the contract at `address` with `params`, in a
+    /// transaction of its own, so that the event handlers for it fire. Indexed params must have
+    /// a static type, since only their hash would end up in the log otherwise.
+    pub fn with_event(self, address: Address, event: &Event, params: &[Token]) -> Self {
+        assert_eq!(
+            event.inputs.len(),
+            params.len(),
+            "event {} takes {} params",
+            event.name,
+            event.inputs.len()
+        );
+
+        let mut topics = vec![event.signature()];
+        let mut data = Vec::new();
+        for (input, param) in event.inputs.iter().zip(params) {
+            if input.indexed {
+                let topic = ethabi::encode(&[param.clone()]);
+                assert_eq!(
+                    topic.len(),
+                    32,
+                    "indexed param {} of event {} must have a static type",
+                    input.name,
+                    event.name
+                );
+                topics.push(H256::from_slice(&topic));
+            } else {
+                data.push(param.clone());
+            }
+        }
+        self.with_log(address, topics, ethabi::encode(&data))
+    }
+
+    /// Like `with_event`, but with the raw `topics` and `data` of the log.
+    pub fn with_log(mut self, address: Address, topics: Vec<H256>, data: Vec<u8>) -> Self {
+        let tx = self.add_transaction(address, address);
+        let block = self.blocks.last_mut().unwrap();
+        let log_index = block
+            .trigger_data
+            .iter()
+            .filter(|trigger| matches!(trigger, EthereumTrigger::Log(..)))
+            .count();
+
+        let log = Log {
+            address,
+            topics,
+            data: Bytes(data),
+            block_hash: tx.block_hash,
+            block_number: tx.block_number,
+            transaction_hash: Some(tx.hash),
+            transaction_index: tx.transaction_index,
+            log_index: Some(U256::from(log_index)),
+            transaction_log_index: Some(U256::zero()),
+            log_type: None,
+            removed: Some(false),
+        };
+        block
+            .trigger_data
+            .push(EthereumTrigger::Log(Arc::new(log), None));
+        block.trigger_data.sort();
+        self
+    }
+
+    /// Makes the last block contain a call from `from` to `function` of the contract at `to`
+    /// with `inputs` that returned `outputs`, so that the call handlers for it fire.
+    pub fn with_call(
+        mut self,
+        from: Address,
+        to: Address,
+        function: &Function,
+        inputs: &[Token],
+        outputs: &[Token],
+    ) -> Self {
+        let input = function
+            .encode_input(inputs)
+            .unwrap_or_else(|e| panic!("invalid inputs for {}: {}", function.name, e));
+        let tx = self.add_transaction(from, to);
+        let block = self.blocks.last_mut().unwrap();
+
+        let call = EthereumCall {
+            from,
+            to,
+            input: Bytes(input),
+            output: Bytes(ethabi::encode(outputs)),
+            block_number: block.ptr().number,
+            block_hash: block.ptr().hash_as_h256(),
+            transaction_hash: Some(tx.hash),
+            transaction_index: tx.transaction_index.unwrap().as_u64(),
+            ..Default::default()
+        };
+        block
+            .trigger_data
+            .push(EthereumTrigger::Call(Arc::new(call)));
+        block.trigger_data.sort();
+        self
+    }
+
+    /// Adds a transaction from `from` to `to` to the last block; logs and calls need one so
+    /// that their handlers can be passed the transaction.
+    fn add_transaction(&mut self, from: Address, to: Address) -> Transaction {
+        let block = self.blocks.last_mut().unwrap();
+        let block = match &mut block.block {
+            BlockFinality::Final(block) => Arc::make_mut(block),
+            BlockFinality::NonFinal(_) => panic!("test blocks are always final"),
+        };
+
+        let index = block.transactions.len() as u64;
+        // Only needs to be unique within the block, since that's where handlers look it up
+        let number = block.number.unwrap().as_u64();
+        let tx = Transaction {
+            hash: H256::from_low_u64_be((number << 32) | index),
+            block_hash: block.hash,
+            block_number: block.number,
+            transaction_index: Some(index.into()),
+            from: Some(from),
+            to: Some(to),
+            ..Default::default()
+        };
+        block.transactions.push(tx.clone());
+        tx
+    }
+}
+
 pub fn empty_block(
     parent_ptr: BlockPtr,
     ptr: BlockPtr,
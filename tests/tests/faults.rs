@@ -0,0 +1,57 @@
+//! Tests for subgraphs whose chain fails on purpose, see `faulty_chain`. They live in a test
+//! binary of their own since they shorten how long the runner waits before it retries a failed
+//! block, which has to happen before anything reads the environment.
+
+use graph_tests::fixture::ethereum::{chain_builder, faulty_chain};
+use graph_tests::fixture::{self, stores, ExpectedEvent, Faults};
+
+use std::sync::Arc;
+
+use graph::blockchain::Block;
+use graph::prelude::{SubgraphName, SubgraphStore as _};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn triggers_in_block_fails() -> anyhow::Result<()> {
+    // Retry after a second instead of after two minutes
+    std::env::set_var("GRAPH_SUBGRAPH_ERROR_RETRY_CEIL_SECS", "1");
+
+    let subgraph_name = SubgraphName::new("triggers-in-block-fails")
+        .expect("Subgraph name must contain only a-z, A-Z, 0-9, '-' and '_'");
+
+    // The block handler of this subgraph creates a data source in every block, which makes the
+    // runner look for the triggers of the new data source in that block
+    let hash = fixture::build_subgraph("./integration-tests/data-source-revert").await;
+
+    let blocks = chain_builder().block(1).block(2).build();
+
+    let ptrs: Vec<_> = blocks.iter().map(|b| b.ptr()).collect();
+    let stop_block = blocks.last().unwrap().block.ptr();
+
+    // The first two attempts to process the genesis block fail
+    let faults = Faults::default().triggers_in_block(2, "failed to get the triggers in block");
+
+    let stores = stores("./integration-tests/config.simple.toml").await;
+    let chain = faulty_chain(blocks, &stores, Arc::new(faults)).await;
+    let ctx = fixture::setup(
+        subgraph_name.clone(),
+        &hash,
+        &stores,
+        chain,
+        fixture::ipfs_link_resolver(),
+        false,
+    )
+    .await;
+
+    ctx.run_to_block(&stop_block).await?;
+
+    assert!(ctx.store.is_healthy(&hash).await.unwrap());
+    ctx.assert_events(vec![
+        ExpectedEvent::ProcessBlock(ptrs[0].clone()),
+        ExpectedEvent::ProcessBlock(ptrs[0].clone()),
+        ExpectedEvent::ProcessBlock(ptrs[0].clone()),
+        ExpectedEvent::ProcessBlock(ptrs[1].clone()),
+        ExpectedEvent::ProcessBlock(ptrs[2].clone()),
+    ]);
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use graph_tests::fixture::MockLinkResolver;
+
+use futures::TryStreamExt;
+use graph::components::link_resolver::LinkResolver;
+use graph::prelude::serde_json::json;
+use graph::prelude::Link;
+
+fn link(link: &str) -> Link {
+    Link::from(link.to_string())
+}
+
+#[tokio::test]
+async fn serves_added_files() -> anyhow::Result<()> {
+    let logger = graph::log::logger(true);
+    let resolver = MockLinkResolver::new()
+        .add("QmManifest", "specVersion: 0.0.4")
+        .add("/ipfs/QmJson", "{\"a\":1}\n\n{\"b\":2}\n");
+
+    // Links are looked up with or without an `/ipfs/` prefix
+    for manifest in ["QmManifest", "/ipfs/QmManifest"] {
+        assert_eq!(
+            b"specVersion: 0.0.4".to_vec(),
+            resolver.cat(&logger, &link(manifest)).await?
+        );
+    }
+    assert_eq!(
+        b"specVersion: 0.0.4".to_vec(),
+        resolver.get_block(&logger, &link("QmManifest")).await?
+    );
+
+    // Blank lines are skipped, and values keep their line numbers
+    let values: Vec<_> = resolver
+        .json_stream(&logger, &link("QmJson"))
+        .await?
+        .map_ok(|value| (value.line, value.value))
+        .try_collect()
+        .await?;
+    assert_eq!(vec![(0, json!({"a": 1})), (2, json!({"b": 2}))], values);
+
+    assert!(resolver.cat(&logger, &link("QmMissing")).await.is_err());
+
+    Ok(())
+}
@@ -1,12 +1,16 @@
-use graph_tests::fixture::ethereum::{chain, empty_block, genesis};
-use graph_tests::fixture::{self, stores, test_ptr};
-use std::time::Duration;
+use graph_tests::fixture::ethereum::{
+    chain, chain_builder, chain_for_network, chain_with_max_reorg_depth,
+};
+use graph_tests::fixture::{self, stores, stores_for_networks, ExpectedEvent};
 
-use anyhow::anyhow;
-use graph::blockchain::{Block, BlockPtr};
-use graph::prelude::ethabi::ethereum_types::H256;
-use graph::prelude::{SubgraphAssignmentProvider, SubgraphName, SubgraphStore as _};
-use slog::{debug, info};
+use std::fs::File;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use graph::blockchain::{Block, BlockchainMap};
+use graph::prelude::ethabi::{self, Token};
+use graph::prelude::web3::types::Address;
+use graph::prelude::{r, SubgraphName, SubgraphStore as _};
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn data_source_revert() -> anyhow::Result<()> {
@@ -18,61 +22,367 @@ async fn data_source_revert() -> anyhow::Result<()> {
         fixture::build_subgraph(&test_dir).await
     };
 
-    let blocks = {
-        let block_0 = genesis();
-        let block_1 = empty_block(block_0.ptr(), test_ptr(1));
-        let block_1_reorged_ptr = BlockPtr {
-            number: 1,
-            hash: H256::from_low_u64_be(12).into(),
-        };
-        let block_1_reorged = empty_block(block_0.ptr(), block_1_reorged_ptr);
-        vec![block_0, block_1, block_1_reorged]
-    };
+    // Block 1 is reorged out by a different block 1
+    let blocks = chain_builder().block(1).reorg(1, 1).build();
 
+    let ptrs: Vec<_> = blocks.iter().map(|b| b.ptr()).collect();
     let stop_block = blocks.last().unwrap().block.ptr();
 
     let stores = stores("./integration-tests/config.simple.toml").await;
     let chain = chain(blocks, &stores).await;
-    let ctx = fixture::setup(subgraph_name.clone(), &hash, &stores, chain).await;
+    let ctx = fixture::setup(
+        subgraph_name.clone(),
+        &hash,
+        &stores,
+        chain,
+        fixture::ipfs_link_resolver(),
+        false,
+    )
+    .await;
 
-    let provider = ctx.provider.clone();
     let store = ctx.store.clone();
 
-    let logger = ctx.logger_factory.subgraph_logger(&ctx.deployment_locator);
+    ctx.run_to_block(&stop_block).await?;
+
+    assert!(store.is_healthy(&hash).await.unwrap());
+    ctx.assert_block_cursor(&stop_block).await;
+    ctx.assert_reverted_to(&fixture::test_ptr(0), 1);
+    ctx.assert_events(vec![
+        ExpectedEvent::ProcessBlock(ptrs[0].clone()),
+        ExpectedEvent::ProcessBlock(ptrs[1].clone()),
+        ExpectedEvent::Revert(ptrs[0].clone()),
+        ExpectedEvent::ProcessBlock(ptrs[2].clone()),
+    ]);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn revert_cursor() -> anyhow::Result<()> {
+    let subgraph_name = SubgraphName::new("revert-cursor")
+        .expect("Subgraph name must contain only a-z, A-Z, 0-9, '-' and '_'");
+
+    // A copy of the data-source-revert subgraph, so that this test has a deployment of its own
+    let hash = {
+        let hash = fixture::build_subgraph("./integration-tests/data-source-revert").await;
+        fixture::with_start_block(&hash, 0).await
+    };
+
+    // Block 2 is undone, and the stream ends before a new block 2 shows up
+    let blocks = chain_builder().block(1).block(2).rewind_to(1).build();
 
-    SubgraphAssignmentProvider::start(provider.as_ref(), ctx.deployment_locator.clone(), None)
+    let ptrs: Vec<_> = blocks.iter().map(|b| b.ptr()).collect();
+
+    let stores = stores("./integration-tests/config.simple.toml").await;
+    let chain = chain(blocks, &stores).await;
+    let ctx = fixture::setup(
+        subgraph_name.clone(),
+        &hash,
+        &stores,
+        chain,
+        fixture::ipfs_link_resolver(),
+        false,
+    )
+    .await;
+
+    ctx.run_to_revert(&ptrs[1]).await?;
+
+    assert!(ctx.store.is_healthy(&hash).await.unwrap());
+    ctx.assert_revert_cursor(&ptrs[1]).await;
+    ctx.assert_events(vec![
+        ExpectedEvent::ProcessBlock(ptrs[0].clone()),
+        ExpectedEvent::ProcessBlock(ptrs[1].clone()),
+        ExpectedEvent::ProcessBlock(ptrs[2].clone()),
+        ExpectedEvent::Revert(ptrs[1].clone()),
+    ]);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn reorg_too_deep() -> anyhow::Result<()> {
+    let subgraph_name = SubgraphName::new("reorg-too-deep")
+        .expect("Subgraph name must contain only a-z, A-Z, 0-9, '-' and '_'");
+
+    // A copy of the event-handler subgraph, so that this test has a deployment of its own
+    let hash = {
+        let hash = fixture::build_subgraph("./integration-tests/event-handler").await;
+        fixture::with_start_block(&hash, 0).await
+    };
+
+    // Blocks 1 and 2 are reorged out, but the stream only reverts one block
+    let blocks = chain_builder().block(1).block(2).reorg(2, 2).build();
+
+    let ptrs: Vec<_> = blocks.iter().map(|b| b.ptr()).collect();
+
+    let stores = stores("./integration-tests/config.simple.toml").await;
+    let chain = chain_with_max_reorg_depth(blocks, &stores, 1).await;
+    let ctx = fixture::setup(
+        subgraph_name.clone(),
+        &hash,
+        &stores,
+        chain,
+        fixture::ipfs_link_resolver(),
+        false,
+    )
+    .await;
+
+    let error = ctx.run_to_stream_error().await?;
+
+    assert_eq!(
+        format!(
+            "reorg to block {} is deeper than the maximum reorg depth of 1",
+            ptrs[3]
+        ),
+        error
+    );
+    ctx.assert_events(vec![
+        ExpectedEvent::ProcessBlock(ptrs[0].clone()),
+        ExpectedEvent::ProcessBlock(ptrs[1].clone()),
+        ExpectedEvent::ProcessBlock(ptrs[2].clone()),
+        ExpectedEvent::Revert(ptrs[1].clone()),
+    ]);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn event_handler() -> anyhow::Result<()> {
+    let subgraph_name = SubgraphName::new("event-handler")
+        .expect("Subgraph name must contain only a-z, A-Z, 0-9, '-' and '_'");
+
+    let hash = {
+        let test_dir = format!("./integration-tests/{}", subgraph_name);
+        fixture::build_subgraph(&test_dir).await
+    };
+
+    let abi = File::open("./integration-tests/event-handler/abis/Contract.abi")?;
+    let contract = ethabi::Contract::load(abi)?;
+    let address = Address::from_str("0xCfEB869F69431e42cdB54A4F4f105C19C080A601")?;
+    let other_address = Address::from_str("0x2E645469f354BB4F5c8a05B3b30A929361cf77eC")?;
+    let trigger = contract.event("Trigger")?;
+
+    // Only the event from the data source's contract is handled
+    let blocks = chain_builder()
+        .block(1)
+        .with_event(address, trigger, &[Token::Uint(7u64.into())])
+        .block(2)
+        .with_event(other_address, trigger, &[Token::Uint(8u64.into())])
+        .build();
+
+    let stop_block = blocks.last().unwrap().block.ptr();
+
+    let stores = stores("./integration-tests/config.simple.toml").await;
+    let chain = chain(blocks, &stores).await;
+    let ctx = fixture::setup(
+        subgraph_name.clone(),
+        &hash,
+        &stores,
+        chain,
+        fixture::ipfs_link_resolver(),
+        false,
+    )
+    .await;
+
+    ctx.run_to_block(&stop_block).await?;
+
+    assert_eq!(1, ctx.entity_count("Trigger"));
+    let entity = ctx
+        .query_entity("Trigger", "1", &stop_block)
         .await
-        .expect("unabel to start subgraph");
-
-    loop {
-        tokio::time::sleep(Duration::from_millis(1000)).await;
-
-        let block_ptr = match store.least_block_ptr(&hash).await {
-            Ok(Some(ptr)) => ptr,
-            res => {
-                info!(&logger, "{:?}", res);
-                continue;
-            }
-        };
-
-        debug!(&logger, "subgraph block: {:?}", block_ptr);
-
-        if block_ptr == stop_block {
-            info!(
-                &logger,
-                "subgraph now at block {}, reached stop block {}", block_ptr.number, stop_block
-            );
-            break;
-        }
-
-        if !store.is_healthy(&hash).await.unwrap() {
-            return Err(anyhow!("subgraph failed unexpectedly"));
-        }
+        .expect("the event in block 1 was handled");
+    assert_eq!(Some(&r::Value::Int(7)), entity.get("x"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn grafted() -> anyhow::Result<()> {
+    let base_name = SubgraphName::new("grafted-base")
+        .expect("Subgraph name must contain only a-z, A-Z, 0-9, '-' and '_'");
+    let subgraph_name = SubgraphName::new("grafted")
+        .expect("Subgraph name must contain only a-z, A-Z, 0-9, '-' and '_'");
+
+    // Copies of the event-handler subgraph, so that the base and the grafted deployment have
+    // deployments of their own
+    let (base_hash, hash) = {
+        let hash = fixture::build_subgraph("./integration-tests/event-handler").await;
+        (
+            fixture::with_start_block(&hash, 1).await,
+            fixture::with_start_block(&hash, 2).await,
+        )
+    };
+
+    let abi = File::open("./integration-tests/event-handler/abis/Contract.abi")?;
+    let contract = ethabi::Contract::load(abi)?;
+    let address = Address::from_str("0xCfEB869F69431e42cdB54A4F4f105C19C080A601")?;
+    let trigger = contract.event("Trigger")?;
+
+    // The base handles the event in block 1, the grafted deployment the one in block 2
+    let blocks = || {
+        chain_builder()
+            .block(1)
+            .with_event(address, trigger, &[Token::Uint(7u64.into())])
+            .block(2)
+            .with_event(address, trigger, &[Token::Uint(8u64.into())])
+            .build()
+    };
+
+    let stop_block = blocks().last().unwrap().block.ptr();
+
+    let stores = stores("./integration-tests/config.simple.toml").await;
+    let base_chain = chain(blocks(), &stores).await;
+    let base = fixture::setup(
+        base_name,
+        &base_hash,
+        &stores,
+        base_chain,
+        fixture::ipfs_link_resolver(),
+        false,
+    )
+    .await;
+    base.run_to_block(&stop_block).await?;
+
+    let chain = chain(blocks(), &stores).await;
+    let ctx = fixture::setup_grafted(
+        subgraph_name,
+        &hash,
+        &base.deployment_locator,
+        1,
+        &stores,
+        chain,
+        fixture::ipfs_link_resolver(),
+        false,
+    )
+    .await;
+    ctx.run_to_block(&stop_block).await?;
+
+    assert!(ctx.store.is_healthy(&hash).await.unwrap());
+    let entity = ctx
+        .query_entity("Trigger", "1", &stop_block)
+        .await
+        .expect("the entity for block 1 was copied from the base");
+    assert_eq!(Some(&r::Value::Int(7)), entity.get("x"));
+    let entity = ctx
+        .query_entity("Trigger", "2", &stop_block)
+        .await
+        .expect("the event in block 2 was handled after the graft");
+    assert_eq!(Some(&r::Value::Int(8)), entity.get("x"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn start_blocks() -> anyhow::Result<()> {
+    let subgraph_name = SubgraphName::new("start-blocks")
+        .expect("Subgraph name must contain only a-z, A-Z, 0-9, '-' and '_'");
+
+    let hash = fixture::build_subgraph("./integration-tests/non-fatal-event-handler").await;
+
+    let abi = File::open("./integration-tests/non-fatal-event-handler/abis/Contract.abi")?;
+    let contract = ethabi::Contract::load(abi)?;
+    let address = Address::from_str("0xCfEB869F69431e42cdB54A4F4f105C19C080A601")?;
+    let trigger = contract.event("Trigger")?;
+
+    let blocks = chain_builder()
+        .block(1)
+        .with_event(address, trigger, &[Token::Uint(7u64.into())])
+        .block(2)
+        .with_event(address, trigger, &[Token::Uint(8u64.into())])
+        .build();
+
+    let stop_block = blocks.last().unwrap().block.ptr();
+
+    let stores = stores_for_networks("./integration-tests/config.simple.toml", &["test"]).await;
+    let mut blockchain_map = BlockchainMap::new();
+    blockchain_map.insert(
+        "test".to_string(),
+        Arc::new(chain_for_network("test", blocks, &stores).await),
+    );
+    let ctxs = fixture::setup_deployments(
+        &subgraph_name,
+        &hash,
+        &[1, 2],
+        &stores,
+        blockchain_map,
+        fixture::ipfs_link_resolver(),
+        false,
+    )
+    .await;
+
+    for ctx in &ctxs {
+        ctx.run_to_block(&stop_block).await?;
     }
 
-    assert!(store.is_healthy(&hash).await.unwrap());
+    // Only the deployment that starts at block 1 handles the event in block 1
+    let entity = ctxs[0]
+        .query_entity("Trigger", "1", &stop_block)
+        .await
+        .expect("the event in block 1 was handled");
+    assert_eq!(Some(&r::Value::Int(7)), entity.get("x"));
+    assert_eq!(
+        None,
+        ctxs[1].query_entity("Trigger", "1", &stop_block).await
+    );
+    for ctx in &ctxs {
+        let entity = ctx
+            .query_entity("Trigger", "2", &stop_block)
+            .await
+            .expect("the event in block 2 was handled");
+        assert_eq!(Some(&r::Value::Int(8)), entity.get("x"));
+    }
 
-    fixture::cleanup(&ctx.store, &subgraph_name, &hash);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn non_fatal_error() -> anyhow::Result<()> {
+    let subgraph_name = SubgraphName::new("non-fatal-event-handler")
+        .expect("Subgraph name must contain only a-z, A-Z, 0-9, '-' and '_'");
+
+    let hash = {
+        let test_dir = format!("./integration-tests/{}", subgraph_name);
+        fixture::build_subgraph(&test_dir).await
+    };
+
+    let abi = File::open("./integration-tests/non-fatal-event-handler/abis/Contract.abi")?;
+    let contract = ethabi::Contract::load(abi)?;
+    let address = Address::from_str("0xCfEB869F69431e42cdB54A4F4f105C19C080A601")?;
+    let trigger = contract.event("Trigger")?;
+
+    // The handler fails deterministically on the event in block 2
+    let blocks = chain_builder()
+        .block(1)
+        .with_event(address, trigger, &[Token::Uint(7u64.into())])
+        .block(2)
+        .with_event(address, trigger, &[Token::Uint(0u64.into())])
+        .block(3)
+        .with_event(address, trigger, &[Token::Uint(8u64.into())])
+        .build();
+
+    let stop_block = blocks.last().unwrap().block.ptr();
+
+    let stores = stores("./integration-tests/config.simple.toml").await;
+    let chain = chain(blocks, &stores).await;
+    let ctx = fixture::setup(
+        subgraph_name.clone(),
+        &hash,
+        &stores,
+        chain,
+        fixture::ipfs_link_resolver(),
+        false,
+    )
+    .await;
+
+    ctx.run_to_block(&stop_block).await?;
+
+    ctx.assert_non_fatal_error(2);
+    assert_eq!(2, ctx.entity_count("Trigger"));
+    let entity = ctx
+        .query_entity("Trigger", "3", &stop_block)
+        .await
+        .expect("the event in block 3 was handled after the error");
+    assert_eq!(Some(&r::Value::Int(8)), entity.get("x"));
 
     Ok(())
 }